@@ -0,0 +1,149 @@
+// Shared by the rule sets: species, visibility and the forces of the environment
+//!include obstacles.wgsl
+//!include motion.wgsl
+//!include random.wgsl
+
+// Species ids above the species count (after it was reduced) fall back to the last species
+fn clampSpecies(species_id: u32) -> u32 {
+    return min(species_id, simulationParameters.species_count - 1u);
+}
+
+fn speciesViewRadius(species_id: u32) -> f32 {
+    return simulationParameters.view_radius * species[species_id].view_radius_factor;
+}
+
+// The blind spot is behind the boid, a null offset (NaN) counts as visible
+fn isVisible(current_to_other: vecN, currentVelocity: vecN) -> bool {
+    return !(dot(normalize(current_to_other), normalize(currentVelocity)) < cos(radians(simulationParameters.field_of_view * 0.5)));
+}
+
+fn normalizeOr(v: vecN, fallback: vecN) -> vecN {
+    let v_length = length(v);
+    if (v_length > 0.0) {
+        return v / v_length;
+    }
+    return fallback;
+}
+
+// Random unit vector perpendicular to direction, zero in the unlikely case the random vector is colinear
+fn randomPerpendicular(direction: vecN, randomState: ptr<function, u32>) -> vecN {
+    let random = randomVecN(randomState);
+    return normalizeOr(random - dot(random, direction) * direction, vecN(0.0));
+}
+
+// Heading of the boid, along x if it does not move
+fn boidHeading(currentVelocity: vecN) -> vecN {
+    var forward = vecN(0.0);
+    forward.x = 1.0;
+    return normalizeOr(currentVelocity, forward);
+}
+
+// Forces of the environment, added to the rules whatever the rule set
+fn environmentAcceleration(currentPosition: vecN, currentVelocity: vecN) -> vecN {
+    var acceleration : vecN = vecN(0.0);
+
+    if (simulationParameters.boundary_mode == BOUNDARY_MODE_SOFT_REPULSION) {
+        acceleration += edge_repulsion(currentPosition, currentVelocity, simulationParameters.repulsion_margin/2.0, simulationParameters.repulsion_strength);
+    }
+
+    acceleration += obstacleAvoidance(currentPosition);
+    acceleration += pointerForce(currentPosition);
+    return acceleration;
+}
+
+// Limit speed of the new velocity
+fn limitSpeed(velocity: vecN, min_speed: f32, max_speed: f32) -> vecN {
+    let speed = length(velocity);
+    if (speed > 0.0) {
+        return (velocity / speed) * clamp(speed, min_speed, max_speed);
+    }
+    return velocity;
+}
+
+// Push away from the nearest obstacle along the gradient of its signed distance, growing from 0 at the margin
+fn obstacleAvoidance(currentPosition: vecN) -> vecN {
+    if (simulationParameters.obstacle_count == 0u) {
+        return vecN(0.0);
+    }
+
+    let p = currentPosition.xy;
+    var nearest = obstacles[0];
+    var nearest_distance = obstacleDistance(nearest, p);
+    for (var i = 1u; i < simulationParameters.obstacle_count; i++) {
+        let distance = obstacleDistance(obstacles[i], p);
+        if (distance < nearest_distance) {
+            nearest = obstacles[i];
+            nearest_distance = distance;
+        }
+    }
+
+    let margin = simulationParameters.obstacle_margin;
+    if (nearest_distance > margin) {
+        return vecN(0.0);
+    }
+
+    // Central differences work for every kind of obstacle
+    let e = vec2<f32>(OBSTACLE_GRADIENT_STEP, 0.0);
+    let gradient = vec2<f32>(
+        obstacleDistance(nearest, p + e.xy) - obstacleDistance(nearest, p - e.xy),
+        obstacleDistance(nearest, p + e.yx) - obstacleDistance(nearest, p - e.yx),
+    );
+    if (all(gradient == vec2<f32>(0.0))) {
+        return vecN(0.0);
+    }
+
+    return vecNFromPlane(normalize(gradient) * simulationParameters.obstacle_strength * (1.0 - nearest_distance / max(margin, 0.0001)));
+}
+
+// Direction away from the predators within the alarm radius, closer ones weighting more, zero if there is none
+fn predatorFlee(currentPosition: vecN) -> vecN {
+    let alarm_radius = simulationParameters.predator_alarm_radius;
+    var flee = vecN(0.0);
+    for (var i = 0u; i < simulationParameters.predator_count; i++) {
+        let offset = positionOffset(currentPosition, vecNFromVec4(predators[i].position));
+        let distance = length(offset);
+        if (distance < alarm_radius && distance > 0.0) {
+            flee -= offset / distance * (1.0 - distance / alarm_radius);
+        }
+    }
+    return flee;
+}
+
+fn caughtByPredator(position: vecN) -> bool {
+    if (simulationParameters.predator_catch == 0u) {
+        return false;
+    }
+
+    let sqrt_catch_radius = simulationParameters.predator_catch_radius * simulationParameters.predator_catch_radius;
+    for (var i = 0u; i < simulationParameters.predator_count; i++) {
+        let offset = positionOffset(position, vecNFromVec4(predators[i].position));
+        if (dot(offset, offset) < sqrt_catch_radius) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Uniform position in the whole domain for a caught boid, drawn from its random stream
+fn respawnPosition(randomState: ptr<function, u32>) -> vecN {
+    return randomVecN(randomState) * 0.5 + 0.5;
+}
+
+// Attraction or repulsion toward the cursor while a mouse button is held, growing from 0 at the radius
+fn pointerForce(currentPosition: vecN) -> vecN {
+    if (pointer.mode == POINTER_FORCE_MODE_NONE) {
+        return vecN(0.0);
+    }
+
+    let offset = pointer.position - currentPosition.xy;
+    let distance = length(offset);
+    if (distance > pointer.radius || distance == 0.0) {
+        return vecN(0.0);
+    }
+
+    var force = offset / distance * pointer.strength * (1.0 - distance / pointer.radius);
+    if (pointer.mode == POINTER_FORCE_MODE_REPULSE) {
+        force = -force;
+    }
+    return vecNFromPlane(force);
+}
@@ -1,30 +1,237 @@
-struct SimulationParameters {
-  view_radius: f32,
-  separation_radius_factor: f32,
-  cohesion_scale: f32,
-  aligment_scale: f32,
-  separation_scale: f32,
-  repulsion_margin: f32,
-  repulsion_strength: f32,
-  boids_count: u32,
-  grid_size: u32,
-}
+//!include simulationParameters.wgsl
+//!include dimension.wgsl
 
 @group(0) @binding(0) var<uniform> simulationParameters : SimulationParameters;
 
-@group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vec2<f32>>;
-@group(1) @binding(1) var<storage, read> boidsVelocitySrc : array<vec2<f32>>;
-@group(1) @binding(2) var<storage, read> boidsCellIdSrc : array<u32>;
+@group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vecN>;
+@group(1) @binding(1) var<storage, read> boidsVelocitySrc : array<vecN>;
 
-@group(1) @binding(3) var<storage, read_write> boidsPositionDst : array<vec2<f32>>;
-@group(1) @binding(4) var<storage, read_write> boidsVelocityDst : array<vec2<f32>>;
+@group(1) @binding(3) var<storage, read_write> boidsPositionDst : array<vecN>;
+@group(1) @binding(4) var<storage, read_write> boidsVelocityDst : array<vecN>;
 @group(1) @binding(5) var<storage, read_write> boidsCellIdDst : array<u32>;
+@group(1) @binding(6) var<storage, read> boidsSpeciesIdSrc : array<u32>;
+@group(1) @binding(7) var<storage, read_write> boidsSpeciesIdDst : array<u32>;
+@group(1) @binding(8) var<storage, read> boidsRandomStateSrc : array<u32>;
+@group(1) @binding(9) var<storage, read_write> boidsRandomStateDst : array<u32>;
+@group(1) @binding(10) var<storage, read> boidsIdSrc : array<u32>;
+@group(1) @binding(11) var<storage, read_write> boidsIdDst : array<u32>;
 
 // boid_sorting_id
 @group(2) @binding(0) var<storage, read> sorting_id : array<u32>;
 @group(3) @binding(0) var<storage, read> cell_count_partial_sum : array<u32>;
 
-//!include flocking.wgsl
+// static obstacles, species, predators and the pointer force
+@group(4) @binding(0) var<storage, read> obstacles : array<Obstacle>;
+@group(4) @binding(1) var<storage, read> obstacleVertices : array<vec2<f32>>;
+@group(4) @binding(2) var<storage, read> species : array<Species>;
+@group(4) @binding(3) var<storage, read> speciesInteractions : array<SpeciesInteraction>;
+@group(4) @binding(4) var<storage, read> predators : array<Predator>;
+@group(4) @binding(5) var<uniform> pointer : PointerForce;
+
+// Rule set chosen at pipeline creation, flocking.wgsl, couzin.wgsl or vicsek.wgsl
+//!include rules.wgsl
+//!include topologicalNeighbors.wgsl
+
+// Neighbor cells range on one axis around cell, it may go across the edges in toroidal mode
+fn neighborCellRange(cell: i32, search_range: i32) -> vec2<i32> {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+
+  // In toroidal mode ranges go across the edges and are wrapped, a range covering the whole axis is the whole axis so each cell is visited once
+  if (simulationParameters.boundary_mode == BOUNDARY_MODE_TOROIDAL) {
+    if (2 * search_range + 1 >= grid_size) {
+      return vec2<i32>(0, grid_size - 1);
+    }
+    return vec2<i32>(cell - search_range, cell + search_range);
+  }
+
+  // Take care of edge cases by clamping the neighbor cells range
+  return vec2<i32>(max(cell - search_range, 0), min(cell + search_range, grid_size - 1));
+}
+
+fn wrapCell(cell: i32) -> i32 {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+  return (cell + grid_size) % grid_size;
+}
+
+// Accumulate boids of the cells [begin_cell_x, end_cell_x] of a row
+fn accumulateRowRange(
+  index: u32,
+  currentPosition: vecN,
+  currentVelocity: vecN,
+  currentSpecies: u32,
+  row: i32,
+  begin_cell_x: i32,
+  end_cell_x: i32,
+  flockingParameters: ptr<function, FlockingParameters>,
+  ) {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+
+  // Cells of a line are contiguous, boids of the range [begin_cell_x, end_cell_x] too
+  let begin_range_id : u32 = cell_count_partial_sum[u32(row * grid_size + begin_cell_x)];
+  let end_range_id : u32 = cell_count_partial_sum[u32(row * grid_size + end_cell_x) + 1u];
+
+  for (var j : u32 = begin_range_id; j < end_range_id; j = j + 1u) {
+    if (j == index) { continue; } // skip self
+    let otherIndex = sorting_id[j];
+    flockingAccumulate(currentPosition, currentVelocity, currentSpecies, boidsPositionSrc[otherIndex], boidsVelocitySrc[otherIndex], boidsSpeciesIdSrc[otherIndex], flockingParameters);
+  }
+}
+
+// Accumulate boids of a row in the range x_range, a wrapped range is split in two ranges of contiguous cells
+fn accumulateRow(
+  index: u32,
+  currentPosition: vecN,
+  currentVelocity: vecN,
+  currentSpecies: u32,
+  row: i32,
+  x_range: vec2<i32>,
+  flockingParameters: ptr<function, FlockingParameters>,
+  ) {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+
+  if (x_range.x < 0) {
+    accumulateRowRange(index, currentPosition, currentVelocity, currentSpecies, row, x_range.x + grid_size, grid_size - 1, flockingParameters);
+    accumulateRowRange(index, currentPosition, currentVelocity, currentSpecies, row, 0, x_range.y, flockingParameters);
+  } else if (x_range.y >= grid_size) {
+    accumulateRowRange(index, currentPosition, currentVelocity, currentSpecies, row, x_range.x, grid_size - 1, flockingParameters);
+    accumulateRowRange(index, currentPosition, currentVelocity, currentSpecies, row, 0, x_range.y - grid_size, flockingParameters);
+  } else {
+    accumulateRowRange(index, currentPosition, currentVelocity, currentSpecies, row, x_range.x, x_range.y, flockingParameters);
+  }
+}
+
+// Accumulate over neighbors using cell_count_partial_sum and neighbor cells
+// for loops for each ligne of the grid, on a single layer in 2D
+fn accumulateMetricNeighbors(
+  index: u32,
+  currentPosition: vecN,
+  currentVelocity: vecN,
+  currentSpecies: u32,
+  cell: vec3<i32>,
+  flockingParameters: ptr<function, FlockingParameters>,
+  ) {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+
+  // Number of cells to look at on each side so that the view radius of the species is covered whatever the cell size
+  let search_range : i32 = i32(ceil(speciesViewRadius(currentSpecies) * f32(simulationParameters.grid_size)));
+
+  let x_range = neighborCellRange(cell.x, search_range);
+  let y_range = neighborCellRange(cell.y, search_range);
+  var z_range = vec2<i32>(0, 0);
+  if (DIMENSION == 3u) {
+    z_range = neighborCellRange(cell.z, search_range);
+  }
+
+  for (var z : i32 = z_range.x; z <= z_range.y; z = z + 1) {
+    for (var y : i32 = y_range.x; y <= y_range.y; y = y + 1) {
+      accumulateRow(index, currentPosition, currentVelocity, currentSpecies, wrapCell(z) * grid_size + wrapCell(y), x_range, flockingParameters);
+    }
+  }
+}
+
+// Same as accumulateRowRange for the candidates of the topological neighbors list
+fn collectRowRange(
+  index: u32,
+  currentPosition: vecN,
+  currentVelocity: vecN,
+  row: i32,
+  begin_cell_x: i32,
+  end_cell_x: i32,
+  neighborList: ptr<function, NeighborList>,
+  ) {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+
+  let begin_range_id : u32 = cell_count_partial_sum[u32(row * grid_size + begin_cell_x)];
+  let end_range_id : u32 = cell_count_partial_sum[u32(row * grid_size + end_cell_x) + 1u];
+
+  for (var j : u32 = begin_range_id; j < end_range_id; j = j + 1u) {
+    if (j == index) { continue; } // skip self
+    neighborListConsider(neighborList, currentPosition, currentVelocity, sorting_id[j]);
+  }
+}
+
+fn collectRow(
+  index: u32,
+  currentPosition: vecN,
+  currentVelocity: vecN,
+  row: i32,
+  x_range: vec2<i32>,
+  neighborList: ptr<function, NeighborList>,
+  ) {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+
+  if (x_range.x < 0) {
+    collectRowRange(index, currentPosition, currentVelocity, row, x_range.x + grid_size, grid_size - 1, neighborList);
+    collectRowRange(index, currentPosition, currentVelocity, row, 0, x_range.y, neighborList);
+  } else if (x_range.y >= grid_size) {
+    collectRowRange(index, currentPosition, currentVelocity, row, x_range.x, grid_size - 1, neighborList);
+    collectRowRange(index, currentPosition, currentVelocity, row, 0, x_range.y - grid_size, neighborList);
+  } else {
+    collectRowRange(index, currentPosition, currentVelocity, row, x_range.x, x_range.y, neighborList);
+  }
+}
+
+// Cells range on one axis of the cube of cells at most ring cells away, in toroidal mode a wrapped range never covers a cell twice
+fn ringRange(cell: i32, ring: i32) -> vec2<i32> {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+
+  if (simulationParameters.boundary_mode == BOUNDARY_MODE_TOROIDAL) {
+    return vec2<i32>(cell - ring, cell + ring - select(0, 1, 2 * ring + 1 > grid_size));
+  }
+  return vec2<i32>(max(cell - ring, 0), min(cell + ring, grid_size - 1));
+}
+
+// Visit rings of cells around the boid cell until the k nearest visible boids are known or the ring limit is reached
+fn collectTopologicalNeighbors(
+  index: u32,
+  currentPosition: vecN,
+  currentVelocity: vecN,
+  cell: vec3<i32>,
+  neighborList: ptr<function, NeighborList>,
+  ) {
+  let grid_size : i32 = i32(simulationParameters.grid_size);
+  let k = topologicalNeighborCount();
+
+  // Last ring still holding cells not visited yet, bounded so sparse regions don't scan the whole grid
+  var max_ring = grid_size - 1;
+  if (simulationParameters.boundary_mode == BOUNDARY_MODE_TOROIDAL) {
+    max_ring = grid_size / 2;
+  }
+  max_ring = min(max_ring, i32(max(simulationParameters.topological_max_ring, 1u)));
+
+  for (var ring : i32 = 0; ring <= max_ring; ring = ring + 1) {
+    let x_range = ringRange(cell.x, ring);
+    let y_range = ringRange(cell.y, ring);
+    var z_range = vec2<i32>(0, 0);
+    if (DIMENSION == 3u) {
+      z_range = ringRange(cell.z, ring);
+    }
+
+    for (var z : i32 = z_range.x; z <= z_range.y; z = z + 1) {
+      for (var y : i32 = y_range.x; y <= y_range.y; y = y + 1) {
+        let row = wrapCell(z) * grid_size + wrapCell(y);
+        if (abs(y - cell.y) == ring || abs(z - cell.z) == ring) {
+          // Rows on the faces of the ring are fully part of it
+          collectRow(index, currentPosition, currentVelocity, row, x_range, neighborList);
+        } else {
+          // Only the cells at both ends of the other rows
+          if (cell.x - ring >= x_range.x) {
+            collectRowRange(index, currentPosition, currentVelocity, row, wrapCell(cell.x - ring), wrapCell(cell.x - ring), neighborList);
+          }
+          if (ring > 0 && cell.x + ring <= x_range.y) {
+            collectRowRange(index, currentPosition, currentVelocity, row, wrapCell(cell.x + ring), wrapCell(cell.x + ring), neighborList);
+          }
+        }
+      }
+    }
+
+    // Boids of the next rings are at least ring cells away, stop once the k-th neighbor is closer than that
+    let reach = f32(ring) / f32(grid_size);
+    if ((*neighborList).count == k && (*neighborList).sqrtDistances[k - 1u] <= reach * reach) {
+      break;
+    }
+  }
+}
 
 @compute @workgroup_size(64)
 fn cs_main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
@@ -32,56 +239,41 @@ fn cs_main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
   let index = GlobalInvocationID.x;
   if (index >= total) { return; }
 
-  var currentPosition : vec2<f32> = boidsPositionSrc[sorting_id[index]];
-  var currentVelocity : vec2<f32> = boidsVelocitySrc[sorting_id[index]];
-  var currentCellId : u32 = boidsCellIdSrc[sorting_id[index]];
+  var currentPosition : vecN = boidsPositionSrc[sorting_id[index]];
+  var currentVelocity : vecN = boidsVelocitySrc[sorting_id[index]];
+  let currentSpecies : u32 = clampSpecies(boidsSpeciesIdSrc[sorting_id[index]]);
+  var randomState : u32 = boidsRandomStateSrc[sorting_id[index]];
+  // Same cell id as the one used by the counting sort, stored ones may come from a previous grid
+  let cell : vec3<i32> = grid_cell_coordinates(position_to_grid_cell_id(currentPosition, simulationParameters.grid_size), simulationParameters.grid_size);
 
   // Flocking
   var flockingParameters = flockingInit();
 
-  // Accumulate over neighbors using cell_count_partial_sum and neighbor cells (8)
-  // for loops for each ligne of the grid (2D case)
-  for (var i : u32 = 0u; i < 3u; i = i + 1u) {
-
-    // split cell id into x and y
-    var cell_id_x : u32 = currentCellId % simulationParameters.grid_size;
-    var cell_id_y : u32 = currentCellId / simulationParameters.grid_size;
-
-    //skip down and up lines
-    if (
-      (i == 0u && cell_id_y == 0u) ||
-      (i == 2u && cell_id_y == simulationParameters.grid_size - 1u)
-    ) { continue; }
-
-    var line_offset_cell_id : u32 = currentCellId + (i - 1u) * simulationParameters.grid_size;
-    // Take care of edge cases using min on x
-    var begin_range_cell_id : u32 = line_offset_cell_id - min(cell_id_x, 1u);
-    var end_range_cell_id : u32 = line_offset_cell_id  + min((simulationParameters.grid_size - 1u) - cell_id_x, 1u);
-
-    var begin_range_id : u32 = cell_count_partial_sum[begin_range_cell_id];
-    var end_range_id : u32 = cell_count_partial_sum[end_range_cell_id+1u] - 1u;
-
-    // Use cell_count_partial_sum to iterate over the neighbors range on the given line
-    for (var j : u32 = begin_range_id; j <= end_range_id; j = j + 1u) {
-      if (j == index) { continue; }
-      flockingAccumulate(currentPosition, currentVelocity, j, &flockingParameters);
-    }
+  if (simulationParameters.neighborhood_mode == NEIGHBORHOOD_MODE_TOPOLOGICAL) {
+    var neighborList = neighborListInit();
+    collectTopologicalNeighbors(index, currentPosition, currentVelocity, cell, &neighborList);
+    neighborListAccumulate(&neighborList, currentPosition, currentVelocity, currentSpecies, &flockingParameters);
+  } else {
+    accumulateMetricNeighbors(index, currentPosition, currentVelocity, currentSpecies, cell, &flockingParameters);
   }
 
   flockingPostAccumulation(&flockingParameters);
 
   // Update velocity
-  var newVelocity : vec2<f32> = computeNewVelocity(currentPosition, currentVelocity, flockingParameters, simulationParameters);
-  var newPosition : vec2<f32> = computeNewPosition(currentPosition, newVelocity);
+  var newVelocity : vecN = computeNewVelocity(currentPosition, currentVelocity, currentSpecies, flockingParameters, &randomState);
+  var newPosition : vecN = computeNewPosition(currentPosition, newVelocity);
+  applyBoundary(&newPosition, &newVelocity);
+  if (caughtByPredator(newPosition)) {
+    newPosition = respawnPosition(&randomState);
+  }
 
   // Write back to storage buffer
   // no mater if we use boid_sorting_id as this will be sorted again
   boidsPositionDst[index] = newPosition;
   boidsVelocityDst[index] = newVelocity;
   boidsCellIdDst[index] = position_to_grid_cell_id(newPosition, simulationParameters.grid_size);
-}
-
-fn position_to_grid_cell_id(position: vec2<f32>, grid_size: u32) -> u32 {
-  let position_id_f32: vec2<f32> = floor(position * f32(grid_size));
-  return (grid_size * u32(position_id_f32.y) + u32(position_id_f32.x)) % (grid_size * grid_size);
+  boidsSpeciesIdDst[index] = currentSpecies;
+  boidsRandomStateDst[index] = randomState;
+  // The id follows the boid to its sorted slot
+  boidsIdDst[index] = boidsIdSrc[sorting_id[index]];
 }
@@ -1,24 +1,33 @@
-struct SimulationParameters {
-  view_radius: f32,
-  separation_radius_factor: f32,
-  cohesion_scale: f32,
-  aligment_scale: f32,
-  separation_scale: f32,
-  repulsion_margin: f32,
-  repulsion_strength: f32,
-  boids_count: u32,
-  grid_size: u32,
-}
+//!include simulationParameters.wgsl
+//!include dimension.wgsl
 
 @group(0) @binding(0) var<uniform> simulationParameters : SimulationParameters;
 
-@group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vec2<f32>>;
-@group(1) @binding(1) var<storage, read> boidsVelocitySrc : array<vec2<f32>>;
+@group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vecN>;
+@group(1) @binding(1) var<storage, read> boidsVelocitySrc : array<vecN>;
+
+@group(1) @binding(3) var<storage, read_write> boidsPositionDst : array<vecN>;
+@group(1) @binding(4) var<storage, read_write> boidsVelocityDst : array<vecN>;
+@group(1) @binding(5) var<storage, read_write> boidsCellIdDst : array<u32>;
+@group(1) @binding(6) var<storage, read> boidsSpeciesIdSrc : array<u32>;
+@group(1) @binding(7) var<storage, read_write> boidsSpeciesIdDst : array<u32>;
+@group(1) @binding(8) var<storage, read> boidsRandomStateSrc : array<u32>;
+@group(1) @binding(9) var<storage, read_write> boidsRandomStateDst : array<u32>;
+@group(1) @binding(10) var<storage, read> boidsIdSrc : array<u32>;
+@group(1) @binding(11) var<storage, read_write> boidsIdDst : array<u32>;
 
-@group(1) @binding(3) var<storage, read_write> boidsPositionDst : array<vec2<f32>>;
-@group(1) @binding(4) var<storage, read_write> boidsVelocityDst : array<vec2<f32>>;
+// The sorting id and cell counts groups (2, 3) are only used by computeGrid.wgsl
+// static obstacles, species, predators and the pointer force
+@group(4) @binding(0) var<storage, read> obstacles : array<Obstacle>;
+@group(4) @binding(1) var<storage, read> obstacleVertices : array<vec2<f32>>;
+@group(4) @binding(2) var<storage, read> species : array<Species>;
+@group(4) @binding(3) var<storage, read> speciesInteractions : array<SpeciesInteraction>;
+@group(4) @binding(4) var<storage, read> predators : array<Predator>;
+@group(4) @binding(5) var<uniform> pointer : PointerForce;
 
-//!include flocking.wgsl
+// Rule set chosen at pipeline creation, flocking.wgsl, couzin.wgsl or vicsek.wgsl
+//!include rules.wgsl
+//!include topologicalNeighbors.wgsl
 
 @compute @workgroup_size(64)
 fn cs_main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
@@ -27,24 +36,43 @@ fn cs_main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
   let index = GlobalInvocationID.x;
   if (index >= total) { return; }
 
-  var currentPosition : vec2<f32> = boidsPositionSrc[index];
-  var currentVelocity : vec2<f32> = boidsVelocitySrc[index];
+  var currentPosition : vecN = boidsPositionSrc[index];
+  var currentVelocity : vecN = boidsVelocitySrc[index];
+  let currentSpecies : u32 = clampSpecies(boidsSpeciesIdSrc[index]);
+  var randomState : u32 = boidsRandomStateSrc[index];
 
   var flockingParameters = flockingInit();
 
-  for (var i : u32 = 0u; i < total; i = i + 1u) {
-    if (i == index) { continue; } // skip self
-    flockingAccumulate(currentPosition, currentVelocity, boidsPositionSrc[i], boidsVelocitySrc[i], &flockingParameters);
+  if (simulationParameters.neighborhood_mode == NEIGHBORHOOD_MODE_TOPOLOGICAL) {
+    var neighborList = neighborListInit();
+    for (var i : u32 = 0u; i < total; i = i + 1u) {
+      if (i == index) { continue; } // skip self
+      neighborListConsider(&neighborList, currentPosition, currentVelocity, i);
+    }
+    neighborListAccumulate(&neighborList, currentPosition, currentVelocity, currentSpecies, &flockingParameters);
+  } else {
+    for (var i : u32 = 0u; i < total; i = i + 1u) {
+      if (i == index) { continue; } // skip self
+      flockingAccumulate(currentPosition, currentVelocity, currentSpecies, boidsPositionSrc[i], boidsVelocitySrc[i], boidsSpeciesIdSrc[i], &flockingParameters);
+    }
   }
 
   flockingPostAccumulation(&flockingParameters);
 
   // Update velocity
-  var newVelocity : vec2<f32> = computeNewVelocity(currentPosition, currentVelocity, flockingParameters);
-  var newPosition : vec2<f32> = computeNewPosition(currentPosition, newVelocity);
+  var newVelocity : vecN = computeNewVelocity(currentPosition, currentVelocity, currentSpecies, flockingParameters, &randomState);
+  var newPosition : vecN = computeNewPosition(currentPosition, newVelocity);
+  applyBoundary(&newPosition, &newVelocity);
+  if (caughtByPredator(newPosition)) {
+    newPosition = respawnPosition(&randomState);
+  }
 
   // Write back to storage buffer
-  // no mater if we use boid_sorting_id as this will be sorted again
+  // cell id is kept up to date to be able to switch to the grid strategy at any time
   boidsPositionDst[index] = newPosition;
   boidsVelocityDst[index] = newVelocity;
+  boidsCellIdDst[index] = position_to_grid_cell_id(newPosition, simulationParameters.grid_size);
+  boidsSpeciesIdDst[index] = currentSpecies;
+  boidsRandomStateDst[index] = randomState;
+  boidsIdDst[index] = boidsIdSrc[index];
 }
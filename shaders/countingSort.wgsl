@@ -0,0 +1,36 @@
+// Counting sort of the boids by cell id, the exclusive prefix sum of the histogram is done in prefixSum.wgsl
+
+//!include simulationParameters.wgsl
+//!include dimension.wgsl
+
+// Cell ids are computed from positions with the current grid size,
+// stored ones may come from a previous grid when the grid size just changed
+@group(0) @binding(0) var<storage, read> boidsPositionSrc : array<vecN>;
+
+@group(1) @binding(0) var<storage, read_write> boidsPerCellCount : array<atomic<u32>>;
+@group(1) @binding(1) var<storage, read_write> cellScatterOffset : array<atomic<u32>>;
+@group(1) @binding(2) var<storage, read_write> sortingId : array<u32>;
+
+@group(2) @binding(0) var<uniform> simulationParameters : SimulationParameters;
+
+// Count the number of boids in each cell (boidsPerCellCount is cleared before this pass)
+@compute @workgroup_size(64)
+fn histogram(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let index = GlobalInvocationID.x;
+  if (index >= arrayLength(&boidsPositionSrc)) { return; }
+
+  let cellId = position_to_grid_cell_id(boidsPositionSrc[index], simulationParameters.grid_size);
+  atomicAdd(&boidsPerCellCount[cellId], 1u);
+}
+
+// Write each boid index in the range of its cell (cellScatterOffset starts as a copy of the prefix sum)
+// The order of boids inside a cell is not deterministic but it doesn't matter for neighbors search
+@compute @workgroup_size(64)
+fn scatter(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let index = GlobalInvocationID.x;
+  if (index >= arrayLength(&boidsPositionSrc)) { return; }
+
+  let cellId = position_to_grid_cell_id(boidsPositionSrc[index], simulationParameters.grid_size);
+  let sorted_index = atomicAdd(&cellScatterOffset[cellId], 1u);
+  sortingId[sorted_index] = index;
+}
@@ -0,0 +1,154 @@
+// Couzin et al. zone model, the other rule set the compute shaders can include as rules.wgsl
+// Same interface as flocking.wgsl: FlockingParameters, flockingInit, flockingAccumulate, flockingPostAccumulation and computeNewVelocity (that may draw from the random stream of the boid)
+//!include boidsCommon.wgsl
+
+const PI: f32 = 3.14159265;
+
+struct FlockingParameters {
+    // Sums of unit vectors for each zone, weighted by the species interactions
+    repulsion: vecN,
+    orientation: vecN,
+    attraction: vecN,
+    repulsionCount: u32,
+    orientationCount: u32,
+    attractionCount: u32,
+}
+
+fn flockingInit() -> FlockingParameters {
+    return FlockingParameters(
+        vecN(0.0),
+        vecN(0.0),
+        vecN(0.0),
+        0u,
+        0u,
+        0u
+    );
+}
+
+// Zones are nested: repulsion, then orientation, then attraction up to the view radius
+// Separation, alignment and cohesion interactions of the species weight repulsion, orientation and attraction
+fn flockingAccumulate(
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    currentSpecies: u32,
+    otherPosition: vecN,
+    otherVelocity: vecN,
+    otherSpecies: u32,
+    flockingParameters: ptr<function, FlockingParameters>,
+    ) {
+
+    let current_to_other = positionOffset(currentPosition, otherPosition);
+    let distance = length(current_to_other);
+
+    let view_radius = speciesViewRadius(currentSpecies);
+    // Skip if too far away, topological neighbors are already selected whatever their distance
+    if (simulationParameters.neighborhood_mode == NEIGHBORHOOD_MODE_METRIC && distance > view_radius) {
+        return;
+    }
+
+    // Boids at the same position give no direction
+    if (distance == 0.0 || !isVisible(current_to_other, currentVelocity)) {
+        return;
+    }
+
+    let interaction = speciesInteractions[currentSpecies * simulationParameters.species_count + clampSpecies(otherSpecies)];
+    let direction = current_to_other / distance;
+
+    if (distance < view_radius * simulationParameters.couzin_repulsion_radius_factor) {
+        if (interaction.separation > 0.0) {
+            (*flockingParameters).repulsion -= direction * interaction.separation;
+            (*flockingParameters).repulsionCount += 1u;
+        }
+    } else if (distance < view_radius * simulationParameters.couzin_orientation_radius_factor) {
+        if (interaction.alignment > 0.0 && any(otherVelocity != vecN(0.0))) {
+            (*flockingParameters).orientation += normalize(otherVelocity) * interaction.alignment;
+            (*flockingParameters).orientationCount += 1u;
+        }
+    } else if (interaction.cohesion > 0.0) {
+        (*flockingParameters).attraction += direction * interaction.cohesion;
+        (*flockingParameters).attractionCount += 1u;
+    }
+}
+
+// Zones are combined in computeNewVelocity
+fn flockingPostAccumulation(
+    flockingParameters: ptr<function, FlockingParameters>
+    ) {
+}
+
+// Desired direction of the zones, repulsion taking strict priority over orientation and attraction
+fn zonesDirection(currentDirection: vecN, flockingParameters: FlockingParameters) -> vecN {
+    if (flockingParameters.repulsionCount > 0u) {
+        return normalizeOr(flockingParameters.repulsion, currentDirection);
+    }
+
+    // The boid orients along itself too
+    let orientation = normalizeOr(flockingParameters.orientation + currentDirection, currentDirection);
+    let attraction = normalizeOr(flockingParameters.attraction, currentDirection);
+    if (flockingParameters.orientationCount > 0u && flockingParameters.attractionCount > 0u) {
+        return normalizeOr(orientation + attraction, currentDirection);
+    } else if (flockingParameters.orientationCount > 0u) {
+        return orientation;
+    } else if (flockingParameters.attractionCount > 0u) {
+        return attraction;
+    }
+    return currentDirection;
+}
+
+// Gaussian angular noise, the standard deviation being couzin_noise degrees after one second
+// so it grows with the square root of the delta time like a rotational diffusion
+fn angularNoise(direction: vecN, randomState: ptr<function, u32>) -> vecN {
+    // Box-Muller transform, u1 in (0, 1]
+    let u1 = 1.0 - randomFloat(randomState);
+    let u2 = randomFloat(randomState);
+    let standard_deviation = radians(simulationParameters.couzin_noise) * sqrt(simulationParameters.delta_time);
+    let angle = standard_deviation * sqrt(-2.0 * log(u1)) * cos(2.0 * PI * u2);
+
+    let perpendicular = randomPerpendicular(direction, randomState);
+    return normalizeOr(cos(angle) * direction + sin(angle) * perpendicular, direction);
+}
+
+// Rotate the current direction toward the desired one by at most max_angle radians
+fn turnTowards(currentDirection: vecN, desiredDirection: vecN, max_angle: f32, randomState: ptr<function, u32>) -> vecN {
+    let cos_angle = clamp(dot(currentDirection, desiredDirection), -1.0, 1.0);
+    if (acos(cos_angle) <= max_angle) {
+        return desiredDirection;
+    }
+
+    // Any side will do for a U-turn
+    var perpendicular = normalizeOr(desiredDirection - cos_angle * currentDirection, vecN(0.0));
+    if (all(perpendicular == vecN(0.0))) {
+        perpendicular = randomPerpendicular(currentDirection, randomState);
+    }
+    return normalizeOr(cos(max_angle) * currentDirection + sin(max_angle) * perpendicular, currentDirection);
+}
+
+// Boids move at max speed along their direction, turning by at most couzin_turning_rate degrees per second
+fn computeNewVelocity(
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    currentSpecies: u32,
+    flockingParameters: FlockingParameters,
+    randomState: ptr<function, u32>,
+    ) -> vecN {
+
+    let currentSpeciesParameters = species[currentSpecies];
+    let max_speed = simulationParameters.max_speed * currentSpeciesParameters.speed_factor;
+    let min_speed = simulationParameters.min_speed * currentSpeciesParameters.speed_factor;
+
+    let current_direction = boidHeading(currentVelocity);
+
+    var desired_direction = zonesDirection(current_direction, flockingParameters);
+    // Alarmed boids only flee
+    let flee = predatorFlee(currentPosition);
+    if (any(flee != vecN(0.0))) {
+        desired_direction = normalize(flee);
+    }
+
+    desired_direction = angularNoise(desired_direction, randomState);
+    let max_angle = radians(simulationParameters.couzin_turning_rate) * simulationParameters.delta_time;
+    let direction = turnTowards(current_direction, desired_direction, max_angle, randomState);
+
+    let velocity = direction * max_speed + environmentAcceleration(currentPosition, currentVelocity) * simulationParameters.delta_time;
+    return limitSpeed(velocity, min_speed, max_speed);
+}
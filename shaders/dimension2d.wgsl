@@ -0,0 +1,36 @@
+// 2D variant of the simulation shaders, included as dimension.wgsl
+
+alias vecN = vec2<f32>;
+const DIMENSION: u32 = 2u;
+
+//!include hash.wgsl
+
+// Consecutive hashes as a vector
+fn hashVecN(n: u32) -> vecN {
+  return vecN(hash1(n), hash1(n + 1u));
+}
+
+// Grid cell id from a position in [0, 1]², positions outside are clamped to the border cells
+fn position_to_grid_cell_id(position: vecN, grid_size: u32) -> u32 {
+  let cell = clamp(vec2<i32>(floor(position * f32(grid_size))), vec2<i32>(0), vec2<i32>(i32(grid_size) - 1));
+  return grid_size * u32(cell.y) + u32(cell.x);
+}
+
+// Cell coordinates from a cell id, z is always 0 in 2D
+fn grid_cell_coordinates(cell_id: u32, grid_size: u32) -> vec3<i32> {
+  return vec3<i32>(i32(cell_id % grid_size), i32(cell_id / grid_size), 0);
+}
+
+// Obstacles are shapes of the xy plane
+fn vecNFromPlane(v: vec2<f32>) -> vecN {
+  return v;
+}
+
+// Predators are stored as vec4 in both variants
+fn vecNFromVec4(v: vec4<f32>) -> vecN {
+  return v.xy;
+}
+
+fn vec4FromVecN(v: vecN) -> vec4<f32> {
+  return vec4<f32>(v, 0.0, 0.0);
+}
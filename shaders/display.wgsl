@@ -1,18 +1,14 @@
-struct SimulationParameters {
-  boids_count: u32,
-  delta_t: f32,
-  view_radius: f32,
-  cohesion_scale: f32,
-  aligment_scale: f32,
-  separation_scale: f32,
-  grid_size: u32,
-}
+//!include simulationParameters.wgsl
 
 @group(0) @binding(0) var<uniform> simulationParameters : SimulationParameters;
 
 @group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vec2<f32>>;
 @group(1) @binding(1) var<storage, read> boidsVelocitySrc : array<vec2<f32>>;
 @group(1) @binding(2) var<storage, read> boidsCellIdSrc : array<u32>;
+@group(1) @binding(3) var<storage, read> boidsSpeciesIdSrc : array<u32>;
+@group(1) @binding(4) var<storage, read> boidsIdSrc : array<u32>;
+
+@group(2) @binding(0) var<storage, read> species : array<Species>;
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
@@ -46,17 +42,15 @@ fn vs_main(
     let centered_boid = boid_position * 2.0 - 1.0;
 
     out.clip_position = vec4<f32>(pos.x + centered_boid.x, pos.y + centered_boid.y, 0.0, 1.0);
-    // let color_factor = cell_factor(boid_cell_id, simulationParameters.grid_size);
-    let color_factor = f32(instance_index) / f32(simulationParameters.boids_count);
-    out.color = palette(color_factor, vec3<f32>(0.2,0.2,0.2),vec3<f32>(0.8,0.8,0.8),vec3<f32>(1.0,1.0,1.0),vec3<f32>(0.0,0.33,0.67));
+    // Colored by species, ids above the species count fall back to the last species like in the simulation
+    let boid_species = min(boidsSpeciesIdSrc[boid_sorting_id], simulationParameters.species_count - 1u);
+    out.color = species[boid_species].color.rgb;
+    if (boidsIdSrc[boid_sorting_id] == simulationParameters.highlighted_boid_id) {
+        out.color = vec3<f32>(1.0);
+    }
     return out;
 }
 
-// from iq : https://iquilezles.org/articles/palettes/
-fn palette(t: f32, a: vec3<f32>, b: vec3<f32>, c: vec3<f32>, d: vec3<f32>) -> vec3<f32> {
-    return a + b * cos(6.28318 * (c * t + d));
-}
-
 fn cell_factor(cell_id: u32, grid_size: u32) -> f32 {
     let grid_size_f32 = f32(grid_size);
     return f32(cell_id) / (grid_size_f32*grid_size_f32);
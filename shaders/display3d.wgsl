@@ -0,0 +1,60 @@
+//!include simulationParameters.wgsl
+//!include camera.wgsl
+
+@group(0) @binding(0) var<uniform> simulationParameters : SimulationParameters;
+
+@group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vec3<f32>>;
+@group(1) @binding(1) var<storage, read> boidsVelocitySrc : array<vec3<f32>>;
+@group(1) @binding(2) var<storage, read> boidsCellIdSrc : array<u32>;
+@group(1) @binding(3) var<storage, read> boidsSpeciesIdSrc : array<u32>;
+@group(1) @binding(4) var<storage, read> boidsIdSrc : array<u32>;
+
+@group(2) @binding(0) var<uniform> camera : Camera;
+
+@group(3) @binding(0) var<storage, read> species : array<Species>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(1) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) boid_sorting_id: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let boid_position = boidsPositionSrc[boid_sorting_id];
+    let boid_velocity = boidsVelocitySrc[boid_sorting_id];
+
+    // Basis with the mesh +z axis along the velocity, any up vector not aligned with it will do
+    let forward = normalize(boid_velocity);
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(forward.y) > 0.99) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let right = normalize(cross(up, forward));
+    let rotation = mat3x3<f32>(right, cross(forward, right), forward);
+
+    // The 2d display maps [0, 1] to [-1, 1], scale the mesh accordingly
+    let scale = 0.15;
+    let world_position = boid_position + rotation * position * scale;
+    out.clip_position = camera.view_projection * vec4<f32>(world_position, 1.0);
+
+    // Lit on both sides as the mesh is drawn without culling
+    let light = abs(dot(rotation * normal, normalize(vec3<f32>(0.3, 1.0, 0.5))));
+    let boid_species = min(boidsSpeciesIdSrc[boid_sorting_id], simulationParameters.species_count - 1u);
+    out.color = species[boid_species].color.rgb * (0.3 + 0.7 * light);
+    if (boidsIdSrc[boid_sorting_id] == simulationParameters.highlighted_boid_id) {
+        out.color = vec3<f32>(0.3 + 0.7 * light);
+    }
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color.x, in.color.y, in.color.z, 1.0);
+}
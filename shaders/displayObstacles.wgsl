@@ -0,0 +1,53 @@
+//!include simulationParameters.wgsl
+
+@group(0) @binding(0) var<uniform> simulationParameters : SimulationParameters;
+
+@group(1) @binding(0) var<storage, read> obstacles : array<Obstacle>;
+@group(1) @binding(1) var<storage, read> obstacleVertices : array<vec2<f32>>;
+
+//!include obstacles.wgsl
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) position: vec2<f32>,
+    @location(1) @interpolate(flat) obstacle_index: u32,
+};
+
+// Quad covering the bounds of the obstacle, its shape is cut by the signed distance in the fragment shader
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let obstacle = obstacles[instance_index];
+    var half_extents = obstacle.half_extents;
+    if (obstacle.kind == OBSTACLE_KIND_CIRCLE) {
+        half_extents = vec2<f32>(obstacle.radius);
+    }
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+    );
+    let position = obstacle.center + corners[vertex_index] * half_extents;
+
+    // shift to display obstacles in [0, 1] in range of the screen [-1, 1]
+    out.clip_position = vec4<f32>(position * 2.0 - 1.0, 0.0, 1.0);
+    out.position = position;
+    out.obstacle_index = instance_index;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = obstacleDistance(obstacles[in.obstacle_index], in.position);
+    if (distance > 0.0) {
+        discard;
+    }
+
+    // Darker outline
+    let color = select(vec3<f32>(0.35, 0.35, 0.4), vec3<f32>(0.6, 0.6, 0.65), distance < -0.003);
+    return vec4<f32>(color, 1.0);
+}
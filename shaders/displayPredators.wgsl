@@ -0,0 +1,39 @@
+//!include simulationParameters.wgsl
+
+@group(0) @binding(0) var<uniform> simulationParameters : SimulationParameters;
+
+@group(1) @binding(0) var<storage, read> predators : array<Predator>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// Same orientation and placement as the boids, with the predator mesh
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let predator_position = predators[instance_index].position.xy;
+    let predator_velocity = predators[instance_index].velocity.xy;
+
+    let angle = -atan2(predator_velocity.x, predator_velocity.y);
+    let c = cos(angle);
+    let s = sin(angle);
+
+    let pos = vec2<f32>(
+        position.x * c - position.y * s,
+        position.x * s + position.y * c,
+    );
+
+    // shift to display predators in [0, 1] in range of the screen [-1, 1]
+    out.clip_position = vec4<f32>(pos + predator_position * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.9, 0.1, 0.1, 1.0);
+}
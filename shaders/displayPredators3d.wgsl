@@ -0,0 +1,47 @@
+//!include simulationParameters.wgsl
+//!include camera.wgsl
+
+@group(0) @binding(0) var<uniform> simulationParameters : SimulationParameters;
+
+@group(1) @binding(0) var<storage, read> predators : array<Predator>;
+
+@group(2) @binding(0) var<uniform> camera : Camera;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(1) color: vec3<f32>,
+};
+
+// Same orientation and lighting as the 3D boids, with a bigger dart
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let predator_position = predators[instance_index].position.xyz;
+    let predator_velocity = predators[instance_index].velocity.xyz;
+
+    let forward = normalize(predator_velocity);
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(forward.y) > 0.99) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let right = normalize(cross(up, forward));
+    let rotation = mat3x3<f32>(right, cross(forward, right), forward);
+
+    let scale = 0.4;
+    let world_position = predator_position + rotation * position * scale;
+    out.clip_position = camera.view_projection * vec4<f32>(world_position, 1.0);
+
+    let light = abs(dot(rotation * normal, normalize(vec3<f32>(0.3, 1.0, 0.5))));
+    out.color = vec3<f32>(0.9, 0.1, 0.1) * (0.3 + 0.7 * light);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color.x, in.color.y, in.color.z, 1.0);
+}
@@ -1,134 +1,151 @@
+// Reynolds rules, one of the rule sets the compute shaders include as rules.wgsl
+//!include boidsCommon.wgsl
+
 struct FlockingParameters {
-    avgPosition: vec2<f32>,
-    avoidance: vec2<f32>,
-    avgVelocity: vec2<f32>,
+    avgPosition: vecN,
+    avoidance: vecN,
+    avgVelocity: vecN,
+    // Sum of kernel weights to average positions and velocities
+    cohesionWeight: f32,
+    alignmentWeight: f32,
     neighborCount: u32,
     avoidCount: u32,
 }
 
-// detla time
-const detlaTime: f32 = 0.0166;
-
 fn flockingInit() -> FlockingParameters {
     return FlockingParameters(
-        vec2<f32>(0.0, 0.0),
-        vec2<f32>(0.0, 0.0),
-        vec2<f32>(0.0, 0.0),
+        vecN(0.0),
+        vecN(0.0),
+        vecN(0.0),
+        0.0,
+        0.0,
         0u,
         0u
     );
 }
 
+// Weight of a neighbor contribution, distance is normalized by the radius of the rule
+fn kernelWeight(kernel: u32, normalizedDistance: f32) -> f32 {
+    if (kernel == WEIGHTING_KERNEL_LINEAR) {
+        return 1.0 - normalizedDistance;
+    } else if (kernel == WEIGHTING_KERNEL_INVERSE_SQUARE) {
+        // Capped to keep very close neighbors from taking over
+        return 1.0 / max(normalizedDistance * normalizedDistance, 0.0001);
+    } else if (kernel == WEIGHTING_KERNEL_GAUSSIAN) {
+        // Standard deviation of half the radius
+        return exp(-2.0 * normalizedDistance * normalizedDistance);
+    }
+    return 1.0;
+}
+
 fn flockingAccumulate(
-    currentPosition: vec2<f32>,
-    currentVelocity: vec2<f32>,
-    otherPosition: vec2<f32>,
-    otherVelocity: vec2<f32>,
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    currentSpecies: u32,
+    otherPosition: vecN,
+    otherVelocity: vecN,
+    otherSpecies: u32,
     flockingParameters: ptr<function, FlockingParameters>,
     ) {
 
-    let current_to_other = otherPosition - currentPosition;
+    let current_to_other = positionOffset(currentPosition, otherPosition);
     let sqrt_distance = dot(current_to_other, current_to_other);
 
-    let sqrt_view_radius = simulationParameters.view_radius * simulationParameters.view_radius;    
-    // Skip if too far away
-    if (sqrt_distance > sqrt_view_radius) {
+    let view_radius = speciesViewRadius(currentSpecies);
+    let sqrt_view_radius = view_radius * view_radius;
+    // Skip if too far away, topological neighbors are already selected whatever their distance
+    if (simulationParameters.neighborhood_mode == NEIGHBORHOOD_MODE_METRIC && sqrt_distance > sqrt_view_radius) {
         return;
     }
 
     // Visiblity angle
-    if (dot(normalize(current_to_other), normalize(currentVelocity)) < -0.6) {
+    if (!isVisible(current_to_other, currentVelocity)) {
         return;
     }
+
+    let distance = sqrt(sqrt_distance);
+    // Kernels weight farther topological neighbors as if they were at the view radius
+    let normalized_distance = min(distance / view_radius, 1.0);
+
+    // How much the current species follows each rule with the other one
+    let interaction = speciesInteractions[currentSpecies * simulationParameters.species_count + clampSpecies(otherSpecies)];
     
     // Separation
-    if (sqrt_distance < sqrt_view_radius * simulationParameters.separation_radius_factor * simulationParameters.separation_radius_factor) {
-        (*flockingParameters).avoidance -= current_to_other;
+    let separation_radius = view_radius * simulationParameters.separation_radius_factor;
+    let separation_weight = kernelWeight(simulationParameters.separation_kernel, distance / separation_radius) * interaction.separation;
+    if (distance < separation_radius && separation_weight > 0.0) {
+        (*flockingParameters).avoidance -= current_to_other * separation_weight;
         (*flockingParameters).avoidCount += 1u;
     }
 
-    (*flockingParameters).avgVelocity += otherVelocity; // Aligment
-    (*flockingParameters).avgPosition += otherPosition; // Cohesion
+    // Aligment
+    let alignment_weight = kernelWeight(simulationParameters.alignment_kernel, normalized_distance) * interaction.alignment;
+    (*flockingParameters).avgVelocity += otherVelocity * alignment_weight;
+    (*flockingParameters).alignmentWeight += alignment_weight;
+
+    // Cohesion (other position seen from the current one in toroidal mode)
+    let cohesion_weight = kernelWeight(simulationParameters.cohesion_kernel, normalized_distance) * interaction.cohesion;
+    (*flockingParameters).avgPosition += (currentPosition + current_to_other) * cohesion_weight;
+    (*flockingParameters).cohesionWeight += cohesion_weight;
+
     (*flockingParameters).neighborCount += 1u;
 }
 
 fn flockingPostAccumulation(
     flockingParameters: ptr<function, FlockingParameters>
     ) {
-    if ((*flockingParameters).neighborCount > 0u) {
-        var f32_neighborCount = f32((*flockingParameters).neighborCount);
-        (*flockingParameters).avgPosition /= f32_neighborCount;
-        (*flockingParameters).avgVelocity /= f32_neighborCount;
+    if ((*flockingParameters).cohesionWeight > 0.0) {
+        (*flockingParameters).avgPosition /= (*flockingParameters).cohesionWeight;
     }
-}
-
-fn computeNewVelocity(
-    currentPosition: vec2<f32>,
-    currentVelocity: vec2<f32>,
-    flockingParameters: FlockingParameters,
-    ) -> vec2<f32> {
-
-    var acceleration : vec2<f32> = vec2<f32>(0.0, 0.0);
-
-    // Todo: make this a parameter
-    var max_speed : f32 = 0.1;
-    var min_speed : f32 = 0.01;
-    var max_steering_strength : f32 = max_speed * 2.0;
-
-    if (flockingParameters.neighborCount > 0u) {
-        acceleration += normalize(flockingParameters.avgVelocity)  * simulationParameters.aligment_scale;
-        acceleration += normalize(flockingParameters.avgPosition - currentPosition) * simulationParameters.cohesion_scale;
-
-        if(flockingParameters.avoidCount > 0u) {
-            acceleration += normalize(flockingParameters.avoidance) * simulationParameters.separation_scale;
-        }
+    if ((*flockingParameters).alignmentWeight > 0.0) {
+        (*flockingParameters).avgVelocity /= (*flockingParameters).alignmentWeight;
     }
-
-    acceleration += edge_repulsion(currentPosition, currentVelocity, simulationParameters.repulsion_margin/2.0, simulationParameters.repulsion_strength);
-    
-    var vel = currentVelocity + acceleration * detlaTime;
-
-    let vel_mag = length(currentVelocity);
-
-    // Limit speed
-    vel = (vel / vel_mag) * clamp(vel_mag, min_speed, max_speed);
-    return vel;
-}
-
-fn computeNewPosition(
-    currentPosition: vec2<f32>,
-    currentVelocity: vec2<f32>,
-    ) -> vec2<f32> {
-    return currentPosition + currentVelocity * detlaTime;
 }
 
-fn wrap_arroud(v : vec2<f32>) -> vec2<f32> {
-    var result : vec2<f32> = v;
-    if (v.x < 0.0) { result.x = 1.0; }
-    if (v.x > 1.0) { result.x = 0.0; }
-    if (v.y < 0.0) { result.y = 1.0; }
-    if (v.y > 1.0) { result.y = 0.0; }
-    return result;
+// Acceleration of a rule toward direction, before its scale, depending on the steering model
+fn ruleAcceleration(direction: vecN, currentVelocity: vecN, max_speed: f32) -> vecN {
+    if (simulationParameters.steering_model == STEERING_MODEL_REYNOLDS) {
+        return steerTowards(direction, currentVelocity, max_speed, simulationParameters.max_steering_strength);
+    }
+    return normalize(direction);
 }
 
-fn edge_repulsion(
-    currentPosition: vec2<f32>,
-    currentVelocity: vec2<f32>,
-    repulsion_margin: f32,
-    repulsion_strength: f32,
-    ) -> vec2<f32> {
-    var edgeRepulsionForce : vec2<f32> = vec2<f32>(0.0, 0.0);
-    if (currentPosition.x < repulsion_margin) {
-        edgeRepulsionForce.x += (repulsion_margin - currentPosition.x);
-    }else if (currentPosition.x > 1.0 - repulsion_margin) {
-        edgeRepulsionForce.x = ((1.0 - repulsion_margin) - currentPosition.x);
+fn computeNewVelocity(
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    currentSpecies: u32,
+    flockingParameters: FlockingParameters,
+    // Unused, these rules are deterministic
+    randomState: ptr<function, u32>,
+    ) -> vecN {
+
+    let currentSpeciesParameters = species[currentSpecies];
+    let max_speed = simulationParameters.max_speed * currentSpeciesParameters.speed_factor;
+    let min_speed = simulationParameters.min_speed * currentSpeciesParameters.speed_factor;
+
+    var acceleration : vecN = vecN(0.0);
+
+    let flee = predatorFlee(currentPosition);
+    if (any(flee != vecN(0.0))) {
+        // Alarmed boids only flee, the escape spreads to the others through alignment
+        acceleration += ruleAcceleration(flee, currentVelocity, max_speed) * simulationParameters.predator_flee_strength;
+    } else {
+        // A rule only applies with neighbors of non zero weight
+        if (flockingParameters.alignmentWeight > 0.0) {
+            acceleration += ruleAcceleration(flockingParameters.avgVelocity, currentVelocity, max_speed)
+                * simulationParameters.aligment_scale * currentSpeciesParameters.alignment_factor;
+        }
+        if (flockingParameters.cohesionWeight > 0.0) {
+            acceleration += ruleAcceleration(flockingParameters.avgPosition - currentPosition, currentVelocity, max_speed)
+                * simulationParameters.cohesion_scale * currentSpeciesParameters.cohesion_factor;
+        }
+        if (flockingParameters.avoidCount > 0u) {
+            acceleration += ruleAcceleration(flockingParameters.avoidance, currentVelocity, max_speed)
+                * simulationParameters.separation_scale * currentSpeciesParameters.separation_factor;
+        }
     }
 
-    if (currentPosition.y < repulsion_margin) {
-        edgeRepulsionForce.y = (repulsion_margin - currentPosition.y);
-    }else if (currentPosition.y > 1.0 - repulsion_margin) {
-        edgeRepulsionForce.y = ((1.0 - repulsion_margin) - currentPosition.y);
-    }
+    acceleration += environmentAcceleration(currentPosition, currentVelocity);
 
-  return repulsion_strength * edgeRepulsionForce;
+    return limitSpeed(currentVelocity + acceleration * simulationParameters.delta_time, min_speed, max_speed);
 }
@@ -0,0 +1,6 @@
+// from iq https://www.shadertoy.com/view/llGSzw
+fn hash1(n: u32) -> f32 {
+	var m = (n << 13u) ^ n;
+  m = m * (m * m * 15731u + 789221u) + 1376312589u;
+  return f32(m & u32(0x7fffffffu))/f32(0x7fffffff);
+}
@@ -1,38 +1,74 @@
-struct InitParameters {
-  seed: u32,
-}
-
-struct SimulationParameters {
-  boids_count: u32,
-  delta_t: f32,
-  view_radius: f32,
-  cohesion_scale: f32,
-  aligment_scale: f32,
-  separation_scale: f32,
-  grid_size: u32,
-}
+//!include initParameters.wgsl
+//!include simulationParameters.wgsl
+//!include dimension.wgsl
+//!include random.wgsl
 
 @group(0) @binding(0) var<uniform> initParameters : InitParameters;
 @group(1) @binding(0) var<uniform> simulationParameters : SimulationParameters;
 
-// @group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vec2<f32>>;
-// @group(1) @binding(1) var<storage, read> boidsVelocitySrc : array<vec2<f32>>;
+// @group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vecN>;
+// @group(1) @binding(1) var<storage, read> boidsVelocitySrc : array<vecN>;
 // @group(1) @binding(2) var<storage, read> boidsCellIdSrc : array<vec2<u32>>;
 
-@group(2) @binding(3) var<storage, read_write> boidsPositionDst : array<vec2<f32>>;
-@group(2) @binding(4) var<storage, read_write> boidsVelocityDst : array<vec2<f32>>;
+@group(2) @binding(3) var<storage, read_write> boidsPositionDst : array<vecN>;
+@group(2) @binding(4) var<storage, read_write> boidsVelocityDst : array<vecN>;
 @group(2) @binding(5) var<storage, read_write> boidsCellIdDst : array<u32>;
+@group(2) @binding(7) var<storage, read_write> boidsSpeciesIdDst : array<u32>;
+@group(2) @binding(9) var<storage, read_write> boidsRandomStateDst : array<u32>;
+@group(2) @binding(11) var<storage, read_write> boidsIdDst : array<u32>;
+
+// Bind group of the environment buffers, only the species are used
+@group(3) @binding(2) var<storage, read> species : array<Species>;
 
-// from iq https://www.shadertoy.com/view/llGSzw
-fn hash1(n: u32) -> f32 {
-	var m = (n << 13u) ^ n;
-  m = m * (m * m * 15731u + 789221u) + 1376312589u;
-  return f32(m & u32(0x7fffffffu))/f32(0x7fffffff);
+// Species whose share of the cumulated proportions contains r in [0, 1]
+fn pickSpecies(r: f32) -> u32 {
+  var total = 0.0;
+  for (var i = 0u; i < simulationParameters.species_count; i++) {
+    total += species[i].proportion;
+  }
+
+  var threshold = r * total;
+  for (var i = 0u; i < simulationParameters.species_count - 1u; i++) {
+    threshold -= species[i].proportion;
+    if (threshold < 0.0) {
+      return i;
+    }
+  }
+  return simulationParameters.species_count - 1u;
 }
 
-fn position_to_grid_cell_id(position: vec2<f32>, grid_size: u32) -> u32 {
-  let position_id_f32: vec2<f32> = floor(position * f32(grid_size));
-  return grid_size * u32(position_id_f32.y) + u32(position_id_f32.x);
+const PI: f32 = 3.14159265;
+
+// Standard normal components, Box-Muller transform with u1 in (0, 1]
+fn gaussianVecN(state: ptr<function, u32>) -> vecN {
+  var v = vecN(0.0);
+  for (var axis = 0u; axis < DIMENSION; axis++) {
+    let u1 = 1.0 - randomFloat(state);
+    let u2 = randomFloat(state);
+    v[axis] = sqrt(-2.0 * log(u1)) * cos(2.0 * PI * u2);
+  }
+  return v;
+}
+
+// Unit vector of the xy plane at the given angle in radians
+fn planeDirection(angle: f32) -> vecN {
+  var direction = vecN(0.0);
+  direction.x = cos(angle);
+  direction.y = sin(angle);
+  return direction;
+}
+
+// Smallest lattice side with side^DIMENSION >= total
+fn latticeSide(total: u32) -> u32 {
+  var side = max(u32(round(pow(f32(total), 1.0 / f32(DIMENSION)))), 1u);
+  var cells = 1u;
+  for (var axis = 0u; axis < DIMENSION; axis++) {
+    cells *= side;
+  }
+  if (cells < total) {
+    side += 1u;
+  }
+  return side;
 }
 
 @compute @workgroup_size(64)
@@ -43,8 +79,65 @@ fn cs_main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
 
   let alterated_index: u32 = index * 142857u + initParameters.seed;
 
-  // Init boid with random velocity and position
-  boidsPositionDst[index] = vec2<f32>(hash1(alterated_index), hash1(alterated_index + 1u));
-  boidsVelocityDst[index] = normalize(vec2<f32>(hash1(alterated_index + 2u), hash1(alterated_index + 3u)) * 2.0 - 1.0)* 0.04;
+  // Random velocity and position by default, uniform mode is the original init
+  var position = hashVecN(alterated_index);
+  var direction = normalize(hashVecN(alterated_index + DIMENSION) * 2.0 - 1.0);
+  // Extra randomness of the other modes
+  var random = pcgHash(alterated_index);
+
+  switch initParameters.init_mode {
+    case INIT_MODE_GAUSSIAN_CLUSTERS: {
+      // Centers only depend on the seed, away from the edges
+      let cluster_count = max(initParameters.cluster_count, 1u);
+      let cluster = min(u32(randomFloat(&random) * f32(cluster_count)), cluster_count - 1u);
+      let center = 0.2 + 0.6 * hashVecN(pcgHash(initParameters.seed) + cluster * DIMENSION);
+      position = center + gaussianVecN(&random) * initParameters.cluster_sigma;
+    }
+    case INIT_MODE_RING: {
+      let angle = 2.0 * PI * randomFloat(&random);
+      let radius = initParameters.ring_radius + (randomFloat(&random) - 0.5) * initParameters.ring_width;
+      position = 0.5 + planeDirection(angle) * radius;
+      // A torus in 3D
+      for (var axis = 2u; axis < DIMENSION; axis++) {
+        position[axis] = 0.5 + (randomFloat(&random) - 0.5) * initParameters.ring_width;
+      }
+      // Tangent, counterclockwise
+      direction = planeDirection(angle + 0.5 * PI);
+    }
+    case INIT_MODE_ALIGNED: {
+      direction = planeDirection(radians(initParameters.heading));
+    }
+    case INIT_MODE_LATTICE: {
+      let side = latticeSide(total);
+      var rest = index;
+      for (var axis = 0u; axis < DIMENSION; axis++) {
+        position[axis] = (f32(rest % side) + 0.5) / f32(side);
+        rest /= side;
+      }
+    }
+    case INIT_MODE_POLARIZED_DISC: {
+      // Uniform in the disc (or ball)
+      let gaussian = gaussianVecN(&random);
+      var offset_direction = planeDirection(0.0);
+      if (any(gaussian != vecN(0.0))) {
+        offset_direction = normalize(gaussian);
+      }
+      let radius = initParameters.disc_radius * pow(randomFloat(&random), 1.0 / f32(DIMENSION));
+      position = 0.5 + offset_direction * radius;
+      // Random headings average to zero so the expected polarization is the aligned share
+      if (randomFloat(&random) < initParameters.polarization) {
+        direction = planeDirection(radians(initParameters.heading));
+      }
+    }
+    default: {}
+  }
+
+  boidsPositionDst[index] = clamp(position, vecN(0.0), vecN(1.0));
+  boidsVelocityDst[index] = direction * initParameters.speed;
   boidsCellIdDst[index] = position_to_grid_cell_id(boidsPositionDst[index], simulationParameters.grid_size);
-}
\ No newline at end of file
+  boidsSpeciesIdDst[index] = pickSpecies(hash1(alterated_index + 2u * DIMENSION));
+  // A stream per boid, different for each seed
+  boidsRandomStateDst[index] = pcgHash(index ^ pcgHash(initParameters.seed));
+  // Boids keep their id whatever their slot in the buffers after sorting
+  boidsIdDst[index] = index;
+}
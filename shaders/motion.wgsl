@@ -0,0 +1,59 @@
+// Motion helpers shared by boids and predators, they only depend on the simulation parameters
+
+fn limitLength(v: vecN, max_length: f32) -> vecN {
+    let v_length = length(v);
+    if (v_length > max_length) {
+        return v / v_length * max_length;
+    }
+    return v;
+}
+
+// Reynolds steering: desired velocity at max speed toward direction minus current velocity, limited to the max steering strength
+fn steerTowards(direction: vecN, currentVelocity: vecN, max_speed: f32, max_steering_strength: f32) -> vecN {
+    let desiredVelocity = normalize(direction) * max_speed;
+    return limitLength(desiredVelocity - currentVelocity, max_steering_strength);
+}
+
+fn computeNewPosition(
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    ) -> vecN {
+    return currentPosition + currentVelocity * simulationParameters.delta_time;
+}
+
+// Offset from current to other position, using the minimum image in toroidal mode
+fn positionOffset(currentPosition: vecN, otherPosition: vecN) -> vecN {
+    let offset = otherPosition - currentPosition;
+    if (simulationParameters.boundary_mode == BOUNDARY_MODE_TOROIDAL) {
+        return offset - round(offset);
+    }
+    return offset;
+}
+
+// Keep boids in the unit square (or cube) for the reflect and toroidal modes
+fn applyBoundary(
+    position: ptr<function, vecN>,
+    velocity: ptr<function, vecN>,
+    ) {
+    if (simulationParameters.boundary_mode == BOUNDARY_MODE_TOROIDAL) {
+        *position = *position - floor(*position);
+    } else if (simulationParameters.boundary_mode == BOUNDARY_MODE_REFLECT) {
+        // Mirror the position on the crossed edge and point the velocity back inside
+        let below = *position < vecN(0.0);
+        let above = *position > vecN(1.0);
+        *position = select(select(*position, -*position, below), 2.0 - *position, above);
+        *velocity = select(select(*velocity, abs(*velocity), below), -abs(*velocity), above);
+    }
+}
+
+fn edge_repulsion(
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    repulsion_margin: f32,
+    repulsion_strength: f32,
+    ) -> vecN {
+    let edgeRepulsionForce = select(vecN(0.0), repulsion_margin - currentPosition, currentPosition < vecN(repulsion_margin))
+        + select(vecN(0.0), (1.0 - repulsion_margin) - currentPosition, currentPosition > vecN(1.0 - repulsion_margin));
+
+    return repulsion_strength * edgeRepulsionForce;
+}
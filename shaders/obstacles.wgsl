@@ -0,0 +1,41 @@
+// Signed distances to the obstacles of the xy plane, negative inside
+// Shaders including it declare obstacles : array<Obstacle> and obstacleVertices : array<vec2<f32>>
+
+fn circleDistance(p: vec2<f32>, center: vec2<f32>, radius: f32) -> f32 {
+    return length(p - center) - radius;
+}
+
+fn boxDistance(p: vec2<f32>, center: vec2<f32>, half_extents: vec2<f32>) -> f32 {
+    let q = abs(p - center) - half_extents;
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0);
+}
+
+// from iq : https://iquilezles.org/articles/distfunctions2d/
+fn polygonDistance(p: vec2<f32>, first_vertex: u32, vertex_count: u32) -> f32 {
+    var d = dot(p - obstacleVertices[first_vertex], p - obstacleVertices[first_vertex]);
+    var s = 1.0;
+    var j = vertex_count - 1u;
+    for (var i = 0u; i < vertex_count; i++) {
+        let vi = obstacleVertices[first_vertex + i];
+        let vj = obstacleVertices[first_vertex + j];
+        let e = vj - vi;
+        let w = p - vi;
+        let b = w - e * clamp(dot(w, e) / dot(e, e), 0.0, 1.0);
+        d = min(d, dot(b, b));
+        let c = vec3<bool>(p.y >= vi.y, p.y < vj.y, e.x * w.y > e.y * w.x);
+        if (all(c) || all(!c)) {
+            s = -s;
+        }
+        j = i;
+    }
+    return s * sqrt(d);
+}
+
+fn obstacleDistance(obstacle: Obstacle, p: vec2<f32>) -> f32 {
+    if (obstacle.kind == OBSTACLE_KIND_CIRCLE) {
+        return circleDistance(p, obstacle.center, obstacle.radius);
+    } else if (obstacle.kind == OBSTACLE_KIND_BOX) {
+        return boxDistance(p, obstacle.center, obstacle.half_extents);
+    }
+    return polygonDistance(p, obstacle.first_vertex, obstacle.vertex_count);
+}
@@ -0,0 +1,55 @@
+//!include simulationParameters.wgsl
+//!include dimension.wgsl
+
+@group(0) @binding(0) var<uniform> simulationParameters : SimulationParameters;
+
+// Boids of the source buffers, predators move before them in each step
+@group(1) @binding(0) var<storage, read> boidsPositionSrc : array<vecN>;
+
+@group(2) @binding(0) var<storage, read_write> predators : array<Predator>;
+
+//!include motion.wgsl
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let index = GlobalInvocationID.x;
+  if (index >= simulationParameters.predator_count) { return; }
+
+  let currentPosition = vecNFromVec4(predators[index].position);
+  let currentVelocity = vecNFromVec4(predators[index].velocity);
+  let max_speed = simulationParameters.predator_speed;
+
+  // Chase the nearest boid in view, first one on ties
+  var nearestOffset = vecN(0.0);
+  var nearest_sqrt_distance = simulationParameters.predator_view_radius * simulationParameters.predator_view_radius;
+  for (var i = 0u; i < arrayLength(&boidsPositionSrc); i++) {
+    let offset = positionOffset(currentPosition, boidsPositionSrc[i]);
+    let sqrt_distance = dot(offset, offset);
+    if (sqrt_distance < nearest_sqrt_distance) {
+      nearestOffset = offset;
+      nearest_sqrt_distance = sqrt_distance;
+    }
+  }
+
+  var acceleration = vecN(0.0);
+  if (any(nearestOffset != vecN(0.0))) {
+    acceleration += steerTowards(nearestOffset, currentVelocity, max_speed, simulationParameters.predator_steering_strength);
+  }
+
+  if (simulationParameters.boundary_mode == BOUNDARY_MODE_SOFT_REPULSION) {
+    acceleration += edge_repulsion(currentPosition, currentVelocity, simulationParameters.repulsion_margin/2.0, simulationParameters.repulsion_strength);
+  }
+
+  // Predators always cruise at their speed
+  var newVelocity = currentVelocity + acceleration * simulationParameters.delta_time;
+  let speed = length(newVelocity);
+  if (speed > 0.0) {
+    newVelocity = newVelocity / speed * max_speed;
+  }
+
+  var newPosition = computeNewPosition(currentPosition, newVelocity);
+  applyBoundary(&newPosition, &newVelocity);
+
+  predators[index].position = vec4FromVecN(newPosition);
+  predators[index].velocity = vec4FromVecN(newVelocity);
+}
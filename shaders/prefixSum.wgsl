@@ -0,0 +1,52 @@
+// Exclusive prefix sum (scan) in place over an array of u32 of any length
+// Each workgroup scans a block of SCAN_WORKGROUP_SIZE elements and writes the block total in blockSums.
+// blockSums is then scanned the same way and added back with add_block_offsets.
+
+const SCAN_WORKGROUP_SIZE: u32 = 256u;
+
+@group(0) @binding(0) var<storage, read_write> data : array<u32>;
+@group(0) @binding(1) var<storage, read_write> blockSums : array<u32>;
+
+var<workgroup> sharedData : array<u32, SCAN_WORKGROUP_SIZE>;
+
+@compute @workgroup_size(256)
+fn scan_blocks(
+  @builtin(global_invocation_id) GlobalInvocationID : vec3<u32>,
+  @builtin(local_invocation_id) LocalInvocationID : vec3<u32>,
+  @builtin(workgroup_id) WorkgroupID : vec3<u32>,
+) {
+  let index = GlobalInvocationID.x;
+  let local_index = LocalInvocationID.x;
+
+  var value : u32 = 0u;
+  if (index < arrayLength(&data)) { value = data[index]; }
+  sharedData[local_index] = value;
+  workgroupBarrier();
+
+  // Hillis-Steele inclusive scan in workgroup memory
+  for (var offset : u32 = 1u; offset < SCAN_WORKGROUP_SIZE; offset = offset << 1u) {
+    var addend : u32 = 0u;
+    if (local_index >= offset) { addend = sharedData[local_index - offset]; }
+    workgroupBarrier();
+    sharedData[local_index] += addend;
+    workgroupBarrier();
+  }
+
+  // Inclusive to exclusive
+  if (index < arrayLength(&data)) { data[index] = sharedData[local_index] - value; }
+
+  if (local_index == SCAN_WORKGROUP_SIZE - 1u) {
+    blockSums[WorkgroupID.x] = sharedData[local_index];
+  }
+}
+
+@compute @workgroup_size(256)
+fn add_block_offsets(
+  @builtin(global_invocation_id) GlobalInvocationID : vec3<u32>,
+  @builtin(workgroup_id) WorkgroupID : vec3<u32>,
+) {
+  let index = GlobalInvocationID.x;
+  if (index >= arrayLength(&data)) { return; }
+
+  data[index] += blockSums[WorkgroupID.x];
+}
@@ -0,0 +1,23 @@
+// Random streams, each boid has its own state stored in the boids random state buffers
+// PCG from "Hash Functions for GPU Rendering" (Jarzynski and Olano), a bijection of u32 so distinct inputs give distinct states
+
+fn pcgHash(n: u32) -> u32 {
+  let state = n * 747796405u + 2891336453u;
+  let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+  return (word >> 22u) ^ word;
+}
+
+// Advance the state and return a float in [0, 1)
+fn randomFloat(state: ptr<function, u32>) -> f32 {
+  *state = pcgHash(*state);
+  return f32(*state >> 8u) / 16777216.0;
+}
+
+// Components in [-1, 1)
+fn randomVecN(state: ptr<function, u32>) -> vecN {
+  var v = vecN(0.0);
+  for (var axis = 0u; axis < DIMENSION; axis++) {
+    v[axis] = randomFloat(state) * 2.0 - 1.0;
+  }
+  return v;
+}
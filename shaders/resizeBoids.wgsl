@@ -0,0 +1,33 @@
+//!include dimension.wgsl
+
+// Previous boids buffers as the source and the resized ones as the destination, with the ping pong bindings
+@group(0) @binding(0) var<storage, read> boidsPositionSrc : array<vecN>;
+@group(0) @binding(1) var<storage, read> boidsVelocitySrc : array<vecN>;
+@group(0) @binding(2) var<storage, read> boidsCellIdSrc : array<u32>;
+@group(0) @binding(3) var<storage, read_write> boidsPositionDst : array<vecN>;
+@group(0) @binding(4) var<storage, read_write> boidsVelocityDst : array<vecN>;
+@group(0) @binding(5) var<storage, read_write> boidsCellIdDst : array<u32>;
+@group(0) @binding(6) var<storage, read> boidsSpeciesIdSrc : array<u32>;
+@group(0) @binding(7) var<storage, read_write> boidsSpeciesIdDst : array<u32>;
+@group(0) @binding(8) var<storage, read> boidsRandomStateSrc : array<u32>;
+@group(0) @binding(9) var<storage, read_write> boidsRandomStateDst : array<u32>;
+@group(0) @binding(10) var<storage, read> boidsIdSrc : array<u32>;
+@group(0) @binding(11) var<storage, read_write> boidsIdDst : array<u32>;
+
+// Previous ids are 0..previous count, each boid whose id is below the new count moves to the slot of its id
+// When growing, slots from the previous count up keep the boids seeded by the init shader along with their ids
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let index = GlobalInvocationID.x;
+  if (index >= arrayLength(&boidsIdSrc)) { return; }
+
+  let id = boidsIdSrc[index];
+  if (id >= arrayLength(&boidsIdDst)) { return; }
+
+  boidsPositionDst[id] = boidsPositionSrc[index];
+  boidsVelocityDst[id] = boidsVelocitySrc[index];
+  boidsCellIdDst[id] = boidsCellIdSrc[index];
+  boidsSpeciesIdDst[id] = boidsSpeciesIdSrc[index];
+  boidsRandomStateDst[id] = boidsRandomStateSrc[index];
+  boidsIdDst[id] = id;
+}
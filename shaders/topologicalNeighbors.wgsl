@@ -0,0 +1,60 @@
+// Topological neighborhood: each boid interacts with its k nearest visible boids
+// Needs the boids source buffers of the compute shader including it
+
+// Per thread list of the nearest neighbors found so far, sorted by distance
+struct NeighborList {
+    ids: array<u32, MAX_TOPOLOGICAL_NEIGHBORS>,
+    sqrtDistances: array<f32, MAX_TOPOLOGICAL_NEIGHBORS>,
+    count: u32,
+}
+
+fn topologicalNeighborCount() -> u32 {
+    return clamp(simulationParameters.topological_neighbor_count, 1u, MAX_TOPOLOGICAL_NEIGHBORS);
+}
+
+fn neighborListInit() -> NeighborList {
+    var list: NeighborList;
+    list.count = 0u;
+    return list;
+}
+
+// Insertion keeping the list sorted, a candidate as far as the last one of a full list is dropped so the first found wins on ties
+fn neighborListInsert(list: ptr<function, NeighborList>, id: u32, sqrt_distance: f32) {
+    let k = topologicalNeighborCount();
+    if ((*list).count == k && sqrt_distance >= (*list).sqrtDistances[k - 1u]) {
+        return;
+    }
+
+    // Shift farther neighbors by one, the last one falls off a full list
+    var i = min((*list).count, k - 1u);
+    while (i > 0u && (*list).sqrtDistances[i - 1u] > sqrt_distance) {
+        (*list).ids[i] = (*list).ids[i - 1u];
+        (*list).sqrtDistances[i] = (*list).sqrtDistances[i - 1u];
+        i--;
+    }
+    (*list).ids[i] = id;
+    (*list).sqrtDistances[i] = sqrt_distance;
+    (*list).count = min((*list).count + 1u, k);
+}
+
+// Add the boid at index id (in the source buffers) if visible
+fn neighborListConsider(list: ptr<function, NeighborList>, currentPosition: vecN, currentVelocity: vecN, id: u32) {
+    let current_to_other = positionOffset(currentPosition, boidsPositionSrc[id]);
+    if (isVisible(current_to_other, currentVelocity)) {
+        neighborListInsert(list, id, dot(current_to_other, current_to_other));
+    }
+}
+
+// Accumulate the neighbors of the list, nearest first
+fn neighborListAccumulate(
+    list: ptr<function, NeighborList>,
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    currentSpecies: u32,
+    flockingParameters: ptr<function, FlockingParameters>,
+    ) {
+    for (var n = 0u; n < (*list).count; n++) {
+        let id = (*list).ids[n];
+        flockingAccumulate(currentPosition, currentVelocity, currentSpecies, boidsPositionSrc[id], boidsVelocitySrc[id], boidsSpeciesIdSrc[id], flockingParameters);
+    }
+}
@@ -0,0 +1,81 @@
+// Vicsek model: constant speed, heading along the average heading of the neighbors plus uniform angular noise
+// Same interface as flocking.wgsl, the compute shaders can include it as rules.wgsl
+//!include boidsCommon.wgsl
+
+struct FlockingParameters {
+    // Sum of the neighbors headings, weighted by the alignment interactions of the species
+    heading: vecN,
+    neighborCount: u32,
+}
+
+fn flockingInit() -> FlockingParameters {
+    return FlockingParameters(vecN(0.0), 0u);
+}
+
+fn flockingAccumulate(
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    currentSpecies: u32,
+    otherPosition: vecN,
+    otherVelocity: vecN,
+    otherSpecies: u32,
+    flockingParameters: ptr<function, FlockingParameters>,
+    ) {
+
+    let current_to_other = positionOffset(currentPosition, otherPosition);
+    let sqrt_distance = dot(current_to_other, current_to_other);
+
+    let view_radius = speciesViewRadius(currentSpecies);
+    // Skip if too far away, topological neighbors are already selected whatever their distance
+    if (simulationParameters.neighborhood_mode == NEIGHBORHOOD_MODE_METRIC && sqrt_distance > view_radius * view_radius) {
+        return;
+    }
+
+    if (!isVisible(current_to_other, currentVelocity)) {
+        return;
+    }
+
+    let interaction = speciesInteractions[currentSpecies * simulationParameters.species_count + clampSpecies(otherSpecies)];
+    if (interaction.alignment > 0.0 && any(otherVelocity != vecN(0.0))) {
+        (*flockingParameters).heading += normalize(otherVelocity) * interaction.alignment;
+        (*flockingParameters).neighborCount += 1u;
+    }
+}
+
+// The average heading only needs a normalization, done in computeNewVelocity
+fn flockingPostAccumulation(
+    flockingParameters: ptr<function, FlockingParameters>
+    ) {
+}
+
+// Boids move at max speed, the noise angle is uniform in [-η/2, η/2] with η = vicsek_noise
+fn computeNewVelocity(
+    currentPosition: vecN,
+    currentVelocity: vecN,
+    currentSpecies: u32,
+    flockingParameters: FlockingParameters,
+    randomState: ptr<function, u32>,
+    ) -> vecN {
+
+    let currentSpeciesParameters = species[currentSpecies];
+    let max_speed = simulationParameters.max_speed * currentSpeciesParameters.speed_factor;
+
+    let current_direction = boidHeading(currentVelocity);
+
+    // The boid is one of its own neighbors
+    var direction = normalizeOr(flockingParameters.heading + current_direction, current_direction);
+    // Alarmed boids only flee
+    let flee = predatorFlee(currentPosition);
+    if (any(flee != vecN(0.0))) {
+        direction = normalize(flee);
+    }
+
+    // Rotation around a random axis in 3D
+    let angle = (randomFloat(randomState) - 0.5) * simulationParameters.vicsek_noise;
+    let perpendicular = randomPerpendicular(direction, randomState);
+    direction = normalizeOr(cos(angle) * direction + sin(angle) * perpendicular, direction);
+
+    // Constant speed, the environment only turns the boid
+    let velocity = direction * max_speed + environmentAcceleration(currentPosition, currentVelocity) * simulationParameters.delta_time;
+    return normalizeOr(velocity, direction) * max_speed;
+}
@@ -10,17 +10,61 @@ use wgpu_profiler::{GpuProfiler, GpuProfilerSettings};
 
 use crate::{
     simulation::{
-        gpu_spatial_partitioning_strategy::create_gpu_spatial_partitioning_strategy, parameters::InitParametersUniformBufferContent, SimulationParametersUniformBufferContent, SimulationStrategy
+        create_simulation_strategy, device_limits, image_mask::{ImageMask, ImageMaskSettings}, obstacles::Obstacles, parameters::{GridSettings, InitParametersUniformBufferContent, RuleSet, TimeStepSettings}, pointer_force::PointerForce, snapshot::{Snapshot, SnapshotAction, SnapshotSettings}, species::SpeciesSettings, trajectory::TrajectoryRecorder, Dimension, SimulationParametersUniformBufferContent, SimulationStrategy, SimulationStrategyKind
 }   ,
     utils::setup_ui_profiler,
 };
 
+// Initial settings of the windowed simulation, from the command line
+pub struct RunSettings {
+    pub simulation_strategy_kind: SimulationStrategyKind,
+    pub rule_set: RuleSet,
+    pub use_spatial_partitioning: bool,
+    pub init_parameters: InitParametersUniformBufferContent,
+    pub simulation_parameters: SimulationParametersUniformBufferContent,
+    // Physical pixels, the default window size otherwise
+    pub window_size: Option<(u32, u32)>,
+}
+
+impl Default for RunSettings {
+    fn default() -> Self {
+        Self {
+            simulation_strategy_kind: SimulationStrategyKind::Gpu,
+            rule_set: RuleSet::Reynolds,
+            use_spatial_partitioning: false,
+            init_parameters: InitParametersUniformBufferContent::default(),
+            simulation_parameters: SimulationParametersUniformBufferContent::default(),
+            window_size: None,
+        }
+    }
+}
+
+// Set before running the application as oxyde creates it without arguments
+pub static RUN_SETTINGS: std::sync::OnceLock<RunSettings> = std::sync::OnceLock::new();
+
 pub struct RustyBoids {
     pub simulation_profiler: GpuProfiler,
 
     pub vertices_buffer: wgpu::Buffer,
 
     simulation_strategy: Box<dyn SimulationStrategy>,
+    simulation_strategy_kind: SimulationStrategyKind,
+    // Without the limits of the compute pipelines only the CPU reference runs
+    gpu_strategies_available: bool,
+    use_spatial_partitioning: bool,
+    rule_set: RuleSet,
+    grid_settings: GridSettings,
+    obstacles: Obstacles,
+    species_settings: SpeciesSettings,
+    pointer_force: PointerForce,
+    image_mask_settings: ImageMaskSettings,
+    snapshot_settings: SnapshotSettings,
+    trajectory_recorder: TrajectoryRecorder,
+
+    time_step_settings: TimeStepSettings,
+    last_update_instant: std::time::Instant,
+    // Simulation steps to run in the next render, computed in update
+    simulation_steps: u32,
 
     pub init_parameters_uniform_buffer: UniformBufferWrapper<InitParametersUniformBufferContent>,
     pub simulation_parameters_uniform_buffer: UniformBufferWrapper<SimulationParametersUniformBufferContent>,
@@ -30,6 +74,11 @@ pub struct RustyBoids {
 
 impl oxyde::App for RustyBoids {
     fn create(_app_state: &mut AppState) -> Self {
+        let run_settings = RUN_SETTINGS.get_or_init(RunSettings::default);
+        if let Some((width, height)) = run_settings.window_size {
+            let _ = _app_state.window.request_inner_size(oxyde::winit::dpi::PhysicalSize::new(width, height));
+        }
+
         // buffer for the three 2d triangle vertices of each boid
         let vertex_buffer_data = [-0.01f32, -0.02, 0.01, -0.02, 0.00, 0.02];
         let vertices_buffer = wgpu::util::DeviceExt::create_buffer_init(
@@ -43,20 +92,23 @@ impl oxyde::App for RustyBoids {
 
 
         let init_parameters_uniform_buffer =
-            UniformBufferWrapper::new(&_app_state.device, InitParametersUniformBufferContent::default(), wgpu::ShaderStages::COMPUTE);
+            UniformBufferWrapper::new(&_app_state.device, run_settings.init_parameters, wgpu::ShaderStages::COMPUTE);
 
         let simulation_parameters_uniform_buffer = UniformBufferWrapper::new(
             &_app_state.device,
-            SimulationParametersUniformBufferContent::default(),
+            run_settings.simulation_parameters,
             wgpu::ShaderStages::all(),
         );
 
-        let simulation_strategy = create_gpu_spatial_partitioning_strategy(
+        let simulation_strategy_kind = run_settings.simulation_strategy_kind;
+        let rule_set = run_settings.rule_set;
+        let simulation_strategy = create_simulation_strategy(
+            simulation_strategy_kind,
+            rule_set,
             &_app_state.device,
             &_app_state.config,
             &init_parameters_uniform_buffer,
             &simulation_parameters_uniform_buffer,
-            false,
         );
 
         let simulation_profiler = GpuProfiler::new(GpuProfilerSettings::default()).unwrap();
@@ -64,6 +116,20 @@ impl oxyde::App for RustyBoids {
         Self {
             vertices_buffer,
             simulation_strategy,
+            simulation_strategy_kind,
+            gpu_strategies_available: device_limits().check_limits(&_app_state.device.limits()),
+            use_spatial_partitioning: run_settings.use_spatial_partitioning,
+            rule_set,
+            grid_settings: GridSettings::default(),
+            obstacles: Obstacles::default(),
+            species_settings: SpeciesSettings::default(),
+            pointer_force: PointerForce::default(),
+            image_mask_settings: ImageMaskSettings::default(),
+            snapshot_settings: SnapshotSettings::default(),
+            trajectory_recorder: TrajectoryRecorder::default(),
+            time_step_settings: TimeStepSettings::default(),
+            last_update_instant: std::time::Instant::now(),
+            simulation_steps: 0,
             simulation_profiler,
             init_parameters_uniform_buffer,
             simulation_parameters_uniform_buffer,
@@ -71,23 +137,159 @@ impl oxyde::App for RustyBoids {
         }
     }
 
-    fn handle_event<T: 'static>(&mut self, _app_state: &mut AppState, _event: &Event<T>) -> Result<()> { Ok(()) }
+    fn handle_event<T: 'static>(&mut self, _app_state: &mut AppState, _event: &Event<T>) -> Result<()> {
+        // Dragging in 3D orbits the camera
+        if let Event::WindowEvent { event, .. } = _event {
+            if self.simulation_strategy_kind != SimulationStrategyKind::Gpu3d {
+                self.pointer_force.handle_window_event(event, _app_state.egui_renderer.context());
+            }
+        }
+        Ok(())
+    }
 
     fn render_gui(&mut self, _app_state: &mut AppState) -> Result<()> {
         egui::SidePanel::right("right panel").resizable(true).show(_app_state.egui_renderer.context(), |ui| {
+            egui::CollapsingHeader::new("Simulation strategy").default_open(true).show(ui, |ui| {
+                let previous_simulation_strategy_kind = self.simulation_strategy_kind;
+                egui::ComboBox::from_label("Strategy")
+                    .selected_text(self.simulation_strategy_kind.label())
+                    .show_ui(ui, |ui| {
+                        for simulation_strategy_kind in SimulationStrategyKind::ALL {
+                            if simulation_strategy_kind != SimulationStrategyKind::CpuReference && !self.gpu_strategies_available {
+                                continue;
+                            }
+                            ui.selectable_value(&mut self.simulation_strategy_kind, simulation_strategy_kind, simulation_strategy_kind.label());
+                        }
+                    });
+
+                if self.simulation_strategy_kind != previous_simulation_strategy_kind {
+                    self.simulation_strategy = create_simulation_strategy(
+                        self.simulation_strategy_kind,
+                        self.rule_set,
+                        &_app_state.device,
+                        &_app_state.config,
+                        &self.init_parameters_uniform_buffer,
+                        &self.simulation_parameters_uniform_buffer,
+                    );
+                    self.need_init = true;
+                }
+
+                // Switching keeps the current boids as both use the same buffers
+                ui.add_enabled(
+                    self.simulation_strategy_kind != SimulationStrategyKind::CpuReference,
+                    egui::Checkbox::new(&mut self.use_spatial_partitioning, "Spatial partitioning (grid)"),
+                );
+
+                // Compute pipelines are created again with the other rules, boids are kept to compare both from the same state
+                let previous_rule_set = self.rule_set;
+                egui::ComboBox::from_label("Rule set").selected_text(self.rule_set.label()).show_ui(ui, |ui| {
+                    for rule_set in RuleSet::ALL {
+                        ui.selectable_value(&mut self.rule_set, rule_set, rule_set.label());
+                    }
+                });
+                if self.rule_set != previous_rule_set {
+                    self.simulation_strategy.set_rule_set(&_app_state.device, self.rule_set);
+                }
+
+                self.simulation_strategy.display_ui(ui);
+            });
+
             self.simulation_parameters_uniform_buffer.content_mut().display_ui(ui);
+            match self.rule_set {
+                RuleSet::Reynolds => {},
+                RuleSet::Couzin => self.simulation_parameters_uniform_buffer.content_mut().display_couzin_ui(ui),
+                RuleSet::Vicsek => self.simulation_parameters_uniform_buffer.content_mut().display_vicsek_ui(ui),
+            }
+            self.grid_settings.display_ui(ui, self.simulation_parameters_uniform_buffer.content().grid_size, self.simulation_strategy_kind.dimension());
+            self.time_step_settings.display_ui(ui);
+            self.species_settings.display_ui(ui);
+            self.simulation_parameters_uniform_buffer.content_mut().display_predators_ui(ui);
+            self.obstacles.display_ui(ui, self.simulation_strategy_kind.dimension());
+            self.pointer_force.display_ui(ui);
 
             egui::CollapsingHeader::new("Init settings").default_open(true).show(ui, |ui| {
-                ui.add(
-                    egui::DragValue::new(&mut self.init_parameters_uniform_buffer.content_mut().seed)
-                        .speed(1)
-                        .prefix("Seed: "),
-                );
+                self.init_parameters_uniform_buffer.content_mut().display_ui(ui);
                 if ui.button("Init boids").clicked() {
                     self.need_init = true;
                 }
+
+                ui.separator();
+                if self.image_mask_settings.display_ui(ui) {
+                    let dimension = self.simulation_strategy_kind.dimension();
+                    let state = ImageMask::load(&self.image_mask_settings.path).and_then(|image_mask| {
+                        image_mask.sample(
+                            self.simulation_parameters_uniform_buffer.content().boids_count,
+                            self.init_parameters_uniform_buffer.content(),
+                            self.image_mask_settings.hue_sets_heading,
+                            &self.species_settings.species,
+                            dimension,
+                        )
+                    });
+                    match state {
+                        Ok(state) => {
+                            self.simulation_strategy.set_boids_state(&_app_state.device, &_app_state.queue, &state);
+                            self.need_init = false;
+                        },
+                        Err(error) => log::error!("Could not init boids from {}: {}", self.image_mask_settings.path, error),
+                    }
+                }
             });
 
+            match self.snapshot_settings.display_ui(ui) {
+                Some(SnapshotAction::Save) => {
+                    let snapshot = Snapshot {
+                        dimension: self.simulation_strategy_kind.dimension(),
+                        init_parameters: *self.init_parameters_uniform_buffer.content(),
+                        simulation_parameters: *self.simulation_parameters_uniform_buffer.content(),
+                        state: self.simulation_strategy.boids_state(&_app_state.device, &_app_state.queue),
+                    };
+                    if let Err(error) = snapshot.save(&self.snapshot_settings.path) {
+                        log::error!("Could not save snapshot {}: {}", self.snapshot_settings.path, error);
+                    }
+                },
+                Some(SnapshotAction::Load) => match Snapshot::load(&self.snapshot_settings.path) {
+                    Ok(snapshot) if snapshot.dimension == Dimension::Three && !self.gpu_strategies_available => {
+                        log::error!("Could not load snapshot {}: 3D needs the GPU strategies", self.snapshot_settings.path);
+                    },
+                    Ok(snapshot) => {
+                        // A 3D snapshot needs the 3D strategy and the other way around
+                        if snapshot.dimension != self.simulation_strategy_kind.dimension() {
+                            self.simulation_strategy_kind = match snapshot.dimension {
+                                Dimension::Two => SimulationStrategyKind::Gpu,
+                                Dimension::Three => SimulationStrategyKind::Gpu3d,
+                            };
+                            self.simulation_strategy = create_simulation_strategy(
+                                self.simulation_strategy_kind,
+                                self.rule_set,
+                                &_app_state.device,
+                                &_app_state.config,
+                                &self.init_parameters_uniform_buffer,
+                                &self.simulation_parameters_uniform_buffer,
+                            );
+                        }
+                        *self.init_parameters_uniform_buffer.content_mut() = snapshot.init_parameters;
+                        *self.simulation_parameters_uniform_buffer.content_mut() = snapshot.simulation_parameters;
+                        self.simulation_strategy.set_boids_state(&_app_state.device, &_app_state.queue, &snapshot.state);
+                        self.need_init = false;
+                    },
+                    Err(error) => log::error!("Could not load snapshot {}: {}", self.snapshot_settings.path, error),
+                },
+                None => {},
+            }
+
+            if self.trajectory_recorder.display_ui(ui) {
+                if self.trajectory_recorder.is_recording() {
+                    self.trajectory_recorder.stop(&_app_state.device);
+                } else if let Err(error) = self.trajectory_recorder.start(
+                    &_app_state.device,
+                    &_app_state.queue,
+                    self.simulation_strategy.current_buffers(),
+                    self.simulation_strategy_kind.dimension(),
+                ) {
+                    log::error!("Could not record trajectories to {}: {}", self.trajectory_recorder.path, error);
+                }
+            }
+
             if let Some(latest_profiler_results) = self.simulation_profiler.process_finished_frame(_app_state.queue.get_timestamp_period()) {
                 egui::CollapsingHeader::new("Wgpu Profiler")
                     .default_open(true)
@@ -101,6 +303,25 @@ impl oxyde::App for RustyBoids {
     }
 
     fn update(&mut self, _app_state: &mut AppState) -> Result<()> {
+        // Grid buffers are reallocated by the simulation strategy when the grid size changes
+        let view_radius = self.simulation_parameters_uniform_buffer.content().view_radius;
+        self.simulation_parameters_uniform_buffer.content_mut().grid_size = self.grid_settings.grid_size(view_radius, self.simulation_strategy_kind.dimension());
+
+        // Obstacles and species buffers are written by the simulation strategy, obstacles only exist in 2D
+        self.simulation_parameters_uniform_buffer.content_mut().obstacle_count = match self.simulation_strategy_kind.dimension() {
+            Dimension::Two => self.obstacles.count(),
+            Dimension::Three => 0,
+        };
+        self.simulation_parameters_uniform_buffer.content_mut().species_count = self.species_settings.count();
+
+        let now = std::time::Instant::now();
+        let frame_time = now.duration_since(self.last_update_instant).as_secs_f32();
+        self.last_update_instant = now;
+
+        let (simulation_steps, delta_time) = self.time_step_settings.advance(frame_time);
+        self.simulation_steps = simulation_steps;
+        self.simulation_parameters_uniform_buffer.content_mut().delta_time = delta_time;
+
         self.simulation_parameters_uniform_buffer.update_content(&_app_state.queue);
         self.init_parameters_uniform_buffer.update_content(&_app_state.queue);
 
@@ -108,14 +329,33 @@ impl oxyde::App for RustyBoids {
     }
 
     fn render(&mut self, _app_state: &mut AppState, _output_view: &wgpu::TextureView) -> Result<()> {
+        // Mapped from the cursor with the gui layout of this frame
+        let pointer_force = self.pointer_force.buffer_content(_app_state.egui_renderer.context());
+        self.simulation_strategy.simulate(
+            &_app_state.device,
+            &_app_state.queue,
+            &self.init_parameters_uniform_buffer,
+            &self.simulation_parameters_uniform_buffer,
+            &mut self.simulation_profiler,
+            &mut self.need_init,
+            self.simulation_steps,
+            self.use_spatial_partitioning,
+            &self.obstacles,
+            &self.species_settings,
+            &pointer_force,
+        );
+        self.trajectory_recorder.record(
+            &_app_state.device,
+            &_app_state.queue,
+            self.simulation_strategy.current_buffers(),
+            self.simulation_steps,
+        );
         self.simulation_strategy.render(
             _app_state,
             _output_view,
-            &self.init_parameters_uniform_buffer,
             &self.simulation_parameters_uniform_buffer,
             &self.vertices_buffer,
             &mut self.simulation_profiler,
-            &mut self.need_init,
         )?;
         Ok(())
     }
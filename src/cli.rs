@@ -0,0 +1,243 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use oxyde::wgpu;
+
+use crate::{
+    app::RunSettings,
+    headless::{HeadlessSettings, DEFAULT_STEPS},
+    simulation::{
+        parameter_file::load_parameter_file, parameters::{InitParametersUniformBufferContent, RuleSet}, SimulationParametersUniformBufferContent, SimulationStrategyKind,
+    },
+};
+
+#[derive(Parser)]
+#[command(name = "rusty_boids", version, about = "Boids simulation on the GPU")]
+pub struct Cli {
+    /// The windowed simulation when no command is given
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Most verbose level logged: off, error, warn, info, debug or trace
+    #[arg(long, global = true, default_value = "debug")]
+    pub log_level: log::LevelFilter,
+
+    /// Also write the log to this file, without colors
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Simulation in a window with the settings panel
+    Run(RunArgs),
+    /// Run a number of steps without window then exit, writing snapshots, trajectories or metrics
+    Headless(HeadlessArgs),
+    /// Time the steps without window for several boids counts
+    Bench(BenchArgs),
+}
+
+// Shared by every command, what is not given keeps the value of the parameter file or the default one
+#[derive(Args, Default)]
+pub struct SimulationArgs {
+    /// Seed of the boids init
+    #[arg(long)]
+    pub seed: Option<u32>,
+
+    /// Simulation strategy, the GPU in 2D when not given
+    #[arg(long, value_enum)]
+    pub strategy: Option<SimulationStrategyKind>,
+
+    /// Rules computing the new velocities, Reynolds when not given
+    #[arg(long, value_enum)]
+    pub rule_set: Option<RuleSet>,
+
+    /// Neighbors search using the uniform grid instead of checking every boid
+    #[arg(long)]
+    pub spatial_partitioning: bool,
+
+    /// Parameters overriding the defaults, one `field = value` per line under an [init] or a [simulation] section
+    #[arg(long)]
+    pub parameters: Option<PathBuf>,
+}
+
+impl SimulationArgs {
+    fn parameters(&self, boids_count: Option<u32>) -> Result<(InitParametersUniformBufferContent, SimulationParametersUniformBufferContent)> {
+        let mut init_parameters = InitParametersUniformBufferContent::default();
+        let mut simulation_parameters = SimulationParametersUniformBufferContent::default();
+        if let Some(path) = &self.parameters {
+            load_parameter_file(&path.to_string_lossy(), &mut init_parameters, &mut simulation_parameters)
+                .with_context(|| format!("could not load parameters {}", path.display()))?;
+        }
+
+        if let Some(seed) = self.seed {
+            init_parameters.seed = seed;
+        }
+        if let Some(boids_count) = boids_count {
+            simulation_parameters.boids_count = boids_count;
+        }
+        if simulation_parameters.boids_count == 0 {
+            anyhow::bail!("the boids count must be positive");
+        }
+        Ok((init_parameters, simulation_parameters))
+    }
+
+    fn headless_settings(&self, boids_count: Option<u32>, steps: u32, force_fallback_adapter: bool) -> Result<HeadlessSettings> {
+        let (init_parameters, simulation_parameters) = self.parameters(boids_count)?;
+        Ok(HeadlessSettings {
+            steps,
+            simulation_strategy_kind: self.strategy.unwrap_or(SimulationStrategyKind::Gpu),
+            rule_set: self.rule_set.unwrap_or(RuleSet::Reynolds),
+            use_spatial_partitioning: self.spatial_partitioning,
+            init_parameters,
+            simulation_parameters,
+            snapshot_path: None,
+            trajectory_path: None,
+            trajectory_interval: 1,
+            metrics_path: None,
+            metrics_interval: 1,
+            force_fallback_adapter,
+        })
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PresentMode {
+    AutoVsync,
+    AutoNoVsync,
+    Fifo,
+    Immediate,
+    Mailbox,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(present_mode: PresentMode) -> Self {
+        match present_mode {
+            PresentMode::AutoVsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::AutoNoVsync => wgpu::PresentMode::AutoNoVsync,
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
+#[derive(Args, Default)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub simulation: SimulationArgs,
+
+    /// Boids count, overrides the parameter file
+    #[arg(long)]
+    pub boids: Option<u32>,
+
+    /// Window width in physical pixels
+    #[arg(long, requires = "height")]
+    pub width: Option<u32>,
+
+    /// Window height in physical pixels
+    #[arg(long, requires = "width")]
+    pub height: Option<u32>,
+
+    /// Presentation mode of the window surface, vsync or not
+    #[arg(long, value_enum)]
+    pub present_mode: Option<PresentMode>,
+}
+
+impl RunArgs {
+    pub fn settings(&self) -> Result<RunSettings> {
+        let (init_parameters, simulation_parameters) = self.simulation.parameters(self.boids)?;
+        Ok(RunSettings {
+            simulation_strategy_kind: self.simulation.strategy.unwrap_or(SimulationStrategyKind::Gpu),
+            rule_set: self.simulation.rule_set.unwrap_or(RuleSet::Reynolds),
+            use_spatial_partitioning: self.simulation.spatial_partitioning,
+            init_parameters,
+            simulation_parameters,
+            window_size: self.width.zip(self.height),
+        })
+    }
+}
+
+#[derive(Args)]
+pub struct HeadlessArgs {
+    #[command(flatten)]
+    pub simulation: SimulationArgs,
+
+    /// Boids count, overrides the parameter file
+    #[arg(long)]
+    pub boids: Option<u32>,
+
+    /// Simulation steps to run
+    #[arg(long, default_value_t = DEFAULT_STEPS)]
+    pub steps: u32,
+
+    /// Snapshot of the boids once the steps are done
+    #[arg(long)]
+    pub snapshot: Option<String>,
+
+    /// Trajectories file, NumPy array when it ends with .npy and CSV otherwise
+    #[arg(long)]
+    pub trajectory: Option<String>,
+
+    /// Steps between two trajectory samples
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub trajectory_interval: u32,
+
+    /// CSV file of the polarization, milling and mean speed of the flock
+    #[arg(long)]
+    pub metrics: Option<String>,
+
+    /// Steps between two rows of metrics
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub metrics_interval: u32,
+
+    /// Use a software adapter such as lavapipe or llvmpipe, when there is no GPU
+    #[arg(long)]
+    pub software: bool,
+}
+
+impl HeadlessArgs {
+    pub fn settings(&self) -> Result<HeadlessSettings> {
+        Ok(HeadlessSettings {
+            snapshot_path: self.snapshot.clone(),
+            trajectory_path: self.trajectory.clone(),
+            trajectory_interval: self.trajectory_interval,
+            metrics_path: self.metrics.clone(),
+            metrics_interval: self.metrics_interval,
+            ..self.simulation.headless_settings(self.boids, self.steps, self.software)?
+        })
+    }
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    #[command(flatten)]
+    pub simulation: SimulationArgs,
+
+    /// Boids counts to time, one run each
+    #[arg(long, value_delimiter = ',', default_value = "1024,4096,16384,65536")]
+    pub boids: Vec<u32>,
+
+    /// Simulation steps timed for each boids count
+    #[arg(long, default_value_t = 500)]
+    pub steps: u32,
+
+    /// CSV file of the timings
+    #[arg(long)]
+    pub output: Option<String>,
+
+    /// Use a software adapter such as lavapipe or llvmpipe, when there is no GPU
+    #[arg(long)]
+    pub software: bool,
+}
+
+impl BenchArgs {
+    // One headless run per boids count, without any output file
+    pub fn settings(&self) -> Result<Vec<HeadlessSettings>> {
+        self.boids
+            .iter()
+            .map(|boids_count| self.simulation.headless_settings(Some(*boids_count), self.steps, self.software))
+            .collect()
+    }
+}
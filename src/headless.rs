@@ -0,0 +1,505 @@
+use std::io::Write;
+
+use anyhow::{bail, Context, Result};
+use oxyde::{wgpu, wgpu_utils::uniform_buffer::UniformBufferWrapper};
+use wgpu_profiler::{GpuProfiler, GpuProfilerSettings};
+
+use crate::simulation::{
+    boids_state::BoidsState,
+    cpu_reference_strategy::CpuFlock,
+    create_simulation_strategy, device_limits,
+    obstacles::Obstacles,
+    parameters::{GridSettings, InitParametersUniformBufferContent, RuleSet},
+    pointer_force::PointerForceUniformBufferContent,
+    snapshot::Snapshot,
+    species::SpeciesSettings,
+    trajectory::{TrajectoryFormat, TrajectoryRecorder},
+    Dimension, SimulationParametersUniformBufferContent, SimulationStrategy, SimulationStrategyKind,
+};
+
+// Steps of a headless run when not given
+pub const DEFAULT_STEPS: u32 = 1000;
+
+// Longest run of steps encoded in a single submission, keeps command buffers small on software adapters
+const MAX_STEPS_PER_SUBMISSION: u32 = 64;
+
+// Simulation without window nor surface, for batch jobs and CI
+pub struct HeadlessSettings {
+    pub steps: u32,
+    pub simulation_strategy_kind: SimulationStrategyKind,
+    pub rule_set: RuleSet,
+    pub use_spatial_partitioning: bool,
+    pub init_parameters: InitParametersUniformBufferContent,
+    pub simulation_parameters: SimulationParametersUniformBufferContent,
+    // Saved once the steps are done
+    pub snapshot_path: Option<String>,
+    // CSV or npy depending on the extension
+    pub trajectory_path: Option<String>,
+    pub trajectory_interval: u32,
+    pub metrics_path: Option<String>,
+    pub metrics_interval: u32,
+    // Software adapter such as lavapipe or llvmpipe, when there is no GPU
+    pub force_fallback_adapter: bool,
+}
+
+// Order parameters of the flock
+struct FlockMetrics {
+    // Norm of the mean heading, 1 when every boid goes the same way
+    polarization: f32,
+    // Norm of the mean angular momentum of the headings around the center of mass, 1 for a perfect mill
+    milling: f32,
+    // Simulation space units per second
+    mean_speed: f32,
+}
+
+impl FlockMetrics {
+    fn new(state: &BoidsState) -> Self {
+        let count = state.boids_count().max(1) as f32;
+        let center = state.positions.iter().fold(nalgebra_glm::Vec3::zeros(), |sum, position| sum + position.xyz()) / count;
+
+        let mut heading_sum = nalgebra_glm::Vec3::zeros();
+        let mut angular_momentum_sum = nalgebra_glm::Vec3::zeros();
+        let mut speed_sum = 0.0;
+        for (position, velocity) in state.positions.iter().zip(&state.velocities) {
+            let speed = velocity.xyz().norm();
+            speed_sum += speed;
+            if speed <= 0.0 {
+                continue;
+            }
+            let heading = velocity.xyz() / speed;
+            heading_sum += heading;
+            let offset = position.xyz() - center;
+            let distance = offset.norm();
+            if distance > 0.0 {
+                angular_momentum_sum += (offset / distance).cross(&heading);
+            }
+        }
+
+        Self {
+            polarization: heading_sum.norm() / count,
+            milling: angular_momentum_sum.norm() / count,
+            mean_speed: speed_sum / count,
+        }
+    }
+}
+
+// Preferred kind of adapter first, software ones last unless asked for
+fn adapter_rank(device_type: wgpu::DeviceType, force_fallback_adapter: bool) -> Option<u32> {
+    match device_type {
+        wgpu::DeviceType::Cpu => Some(if force_fallback_adapter { 0 } else { 3 }),
+        _ if force_fallback_adapter => None,
+        wgpu::DeviceType::DiscreteGpu => Some(0),
+        wgpu::DeviceType::IntegratedGpu => Some(1),
+        wgpu::DeviceType::VirtualGpu | wgpu::DeviceType::Other => Some(2),
+    }
+}
+
+// Best adapter having the given limits, the GPU strategies need `device_limits`
+pub fn find_adapter(force_fallback_adapter: bool, required_limits: &wgpu::Limits) -> Result<wgpu::Adapter> {
+    let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor { backends, ..Default::default() });
+
+    let mut adapters: Vec<(u32, wgpu::Adapter)> = instance
+        .enumerate_adapters(backends)
+        .into_iter()
+        .filter_map(|adapter| Some((adapter_rank(adapter.get_info().device_type, force_fallback_adapter)?, adapter)))
+        .collect();
+    if adapters.is_empty() {
+        bail!("no {}adapter found", if force_fallback_adapter { "software " } else { "" });
+    }
+
+    // The same software rasterizer can be exposed by several backends with different limits,
+    // e.g. llvmpipe through GL only has 16 storage buffers per stage while lavapipe through Vulkan has enough
+    adapters.retain(|(_, adapter)| {
+        let has_limits = required_limits.check_limits(&adapter.limits());
+        if !has_limits {
+            let info = adapter.get_info();
+            log::warn!("Adapter {} ({:?}) skipped, its limits are too low", info.name, info.backend);
+        }
+        has_limits
+    });
+    adapters.sort_by_key(|(rank, _)| *rank);
+    let Some((_, adapter)) = adapters.into_iter().next() else {
+        bail!("no {}adapter with the limits needed by the simulation", if force_fallback_adapter { "software " } else { "" });
+    };
+    Ok(adapter)
+}
+
+fn create_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
+    let adapter_info = adapter.get_info();
+    log::info!("Headless run on {} ({:?}, {:?})", adapter_info.name, adapter_info.device_type, adapter_info.backend);
+
+    // Software adapters rarely have timestamp queries, the profiler then records nothing
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("Headless Device"),
+            required_features: adapter.features() & GpuProfiler::ALL_WGPU_TIMER_FEATURES,
+            required_limits: device_limits(),
+        },
+        None,
+    ))?;
+    Ok((device, queue))
+}
+
+// Same as the app update
+fn effective_simulation_parameters(
+    settings: &HeadlessSettings,
+    obstacles: &Obstacles,
+    species_settings: &SpeciesSettings,
+) -> SimulationParametersUniformBufferContent {
+    let mut simulation_parameters = settings.simulation_parameters;
+    simulation_parameters.grid_size = GridSettings::default().grid_size(simulation_parameters.view_radius, settings.simulation_strategy_kind.dimension());
+    simulation_parameters.obstacle_count = obstacles.count();
+    simulation_parameters.species_count = species_settings.count();
+    simulation_parameters
+}
+
+fn create_metrics_writer(settings: &HeadlessSettings) -> Result<Option<std::io::BufWriter<std::fs::File>>> {
+    let Some(path) = &settings.metrics_path else {
+        return Ok(None);
+    };
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path).with_context(|| format!("could not create {}", path))?);
+    writeln!(writer, "step,polarization,milling,mean_speed")?;
+    Ok(Some(writer))
+}
+
+fn create_trajectory_recorder(settings: &HeadlessSettings) -> TrajectoryRecorder {
+    let mut trajectory_recorder = TrajectoryRecorder::default();
+    if let Some(path) = &settings.trajectory_path {
+        trajectory_recorder.path = path.clone();
+        trajectory_recorder.format = if path.ends_with(".npy") { TrajectoryFormat::Npy } else { TrajectoryFormat::Csv };
+        trajectory_recorder.interval = settings.trajectory_interval;
+    }
+    trajectory_recorder
+}
+
+fn save_snapshot(path: &str, snapshot: Snapshot) -> Result<()> {
+    snapshot.save(path).with_context(|| format!("could not save snapshot {}", path))?;
+    log::info!("Snapshot saved to {}", path);
+    Ok(())
+}
+
+// Returns the time taken by the steps, from the end of the init
+// Without any adapter, 2D runs fall back to the CPU reference
+pub fn run_headless(settings: &HeadlessSettings) -> Result<std::time::Duration> {
+    let dimension = settings.simulation_strategy_kind.dimension();
+    let adapter = match find_adapter(settings.force_fallback_adapter, &device_limits()) {
+        Ok(adapter) => adapter,
+        Err(error) if dimension == Dimension::Two => {
+            log::warn!("{}, running the CPU reference instead", error);
+            return run_headless_cpu(settings);
+        },
+        Err(error) => return Err(error),
+    };
+    let (device, queue) = create_device(&adapter)?;
+
+    // Render pipelines are still created by the strategies, they are just never used
+    let config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        width: 1,
+        height: 1,
+        present_mode: wgpu::PresentMode::Fifo,
+        desired_maximum_frame_latency: 2,
+        alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        view_formats: vec![],
+    };
+
+    let mut init_parameters_uniform_buffer = UniformBufferWrapper::new(&device, settings.init_parameters, wgpu::ShaderStages::COMPUTE);
+    let mut simulation_parameters_uniform_buffer = UniformBufferWrapper::new(&device, settings.simulation_parameters, wgpu::ShaderStages::all());
+
+    // Same as the app update, with a fixed delta time
+    let obstacles = Obstacles::default();
+    let species_settings = SpeciesSettings::default();
+    *simulation_parameters_uniform_buffer.content_mut() = effective_simulation_parameters(settings, &obstacles, &species_settings);
+    simulation_parameters_uniform_buffer.update_content(&queue);
+    init_parameters_uniform_buffer.update_content(&queue);
+    let pointer_force: PointerForceUniformBufferContent = bytemuck::Zeroable::zeroed();
+
+    let mut simulation_strategy = create_simulation_strategy(
+        settings.simulation_strategy_kind,
+        settings.rule_set,
+        &device,
+        &config,
+        &init_parameters_uniform_buffer,
+        &simulation_parameters_uniform_buffer,
+    );
+    let mut simulation_profiler = GpuProfiler::new(GpuProfilerSettings::default()).unwrap();
+
+    let mut simulate = |simulation_strategy: &mut dyn SimulationStrategy, mut need_init: bool, steps: u32| {
+        simulation_strategy.simulate(
+            &device,
+            &queue,
+            &init_parameters_uniform_buffer,
+            &simulation_parameters_uniform_buffer,
+            &mut simulation_profiler,
+            &mut need_init,
+            steps,
+            settings.use_spatial_partitioning,
+            &obstacles,
+            &species_settings,
+            &pointer_force,
+        );
+        simulation_profiler.end_frame().unwrap();
+        simulation_profiler.process_finished_frame(queue.get_timestamp_period());
+    };
+    simulate(simulation_strategy.as_mut(), true, 0);
+
+    let mut trajectory_recorder = create_trajectory_recorder(settings);
+    if settings.trajectory_path.is_some() {
+        trajectory_recorder.start(&device, &queue, simulation_strategy.current_buffers(), dimension)?;
+    }
+    let mut metrics_writer = create_metrics_writer(settings)?;
+
+    device.poll(wgpu::Maintain::Wait);
+    let start_instant = std::time::Instant::now();
+    let mut step = 0;
+    let mut last_steps = 0;
+    loop {
+        // Batch runs can wait for the previous sample instead of dropping one
+        if trajectory_recorder.is_recording() {
+            device.poll(wgpu::Maintain::Wait);
+        }
+        trajectory_recorder.record(&device, &queue, simulation_strategy.current_buffers(), last_steps);
+
+        if let Some(writer) = &mut metrics_writer {
+            if step % settings.metrics_interval == 0 || step == settings.steps {
+                let metrics = FlockMetrics::new(&simulation_strategy.boids_state(&device, &queue));
+                writeln!(writer, "{},{},{},{}", step, metrics.polarization, metrics.milling, metrics.mean_speed)?;
+            }
+        }
+
+        if step == settings.steps {
+            break;
+        }
+
+        // Stop on the next sample of the trajectories or of the metrics
+        let steps_to_sample = |enabled: bool, interval: u32| if enabled { interval - step % interval } else { u32::MAX };
+        let steps = [
+            settings.steps - step,
+            MAX_STEPS_PER_SUBMISSION,
+            steps_to_sample(trajectory_recorder.is_recording(), settings.trajectory_interval),
+            steps_to_sample(metrics_writer.is_some(), settings.metrics_interval),
+        ]
+        .into_iter()
+        .min()
+        .unwrap();
+        simulate(simulation_strategy.as_mut(), false, steps);
+        step += steps;
+        last_steps = steps;
+    }
+    device.poll(wgpu::Maintain::Wait);
+    let elapsed = start_instant.elapsed();
+    log::info!("{} steps of {} boids in {:.2}s", settings.steps, settings.simulation_parameters.boids_count, elapsed.as_secs_f32());
+
+    trajectory_recorder.stop(&device);
+    if let Some(writer) = &mut metrics_writer {
+        writer.flush()?;
+    }
+
+    if let Some(path) = &settings.snapshot_path {
+        save_snapshot(
+            path,
+            Snapshot {
+                dimension,
+                init_parameters: *init_parameters_uniform_buffer.content(),
+                simulation_parameters: *simulation_parameters_uniform_buffer.content(),
+                state: simulation_strategy.boids_state(&device, &queue),
+            },
+        )?;
+    }
+
+    Ok(elapsed)
+}
+
+// Same outputs as run_headless, one step at a time without any device
+fn run_headless_cpu(settings: &HeadlessSettings) -> Result<std::time::Duration> {
+    let obstacles = Obstacles::default();
+    let obstacles_buffer_contents = obstacles.buffer_contents();
+    let species_settings = SpeciesSettings::default();
+    let simulation_parameters = effective_simulation_parameters(settings, &obstacles, &species_settings);
+    let pointer_force: PointerForceUniformBufferContent = bytemuck::Zeroable::zeroed();
+
+    let mut flock = CpuFlock::new(simulation_parameters.boids_count, &settings.init_parameters, &species_settings.species);
+
+    let mut trajectory_recorder = create_trajectory_recorder(settings);
+    if settings.trajectory_path.is_some() {
+        trajectory_recorder.start_host(&flock.boids_state(), Dimension::Two)?;
+    }
+    let mut metrics_writer = create_metrics_writer(settings)?;
+
+    let start_instant = std::time::Instant::now();
+    for step in 0..=settings.steps {
+        // The initial state is sampled by start_host
+        if step > 0 {
+            flock.step(settings.rule_set, &simulation_parameters, &obstacles_buffer_contents, &species_settings, &pointer_force);
+            if trajectory_recorder.is_recording() {
+                trajectory_recorder.record_host(&flock.boids_state(), 1);
+            }
+        }
+
+        if let Some(writer) = &mut metrics_writer {
+            if step % settings.metrics_interval == 0 || step == settings.steps {
+                let metrics = FlockMetrics::new(&flock.boids_state());
+                writeln!(writer, "{},{},{},{}", step, metrics.polarization, metrics.milling, metrics.mean_speed)?;
+            }
+        }
+    }
+    let elapsed = start_instant.elapsed();
+    log::info!("{} steps of {} boids on the CPU in {:.2}s", settings.steps, simulation_parameters.boids_count, elapsed.as_secs_f32());
+
+    trajectory_recorder.stop_host();
+    if let Some(writer) = &mut metrics_writer {
+        writer.flush()?;
+    }
+
+    if let Some(path) = &settings.snapshot_path {
+        save_snapshot(
+            path,
+            Snapshot {
+                dimension: Dimension::Two,
+                init_parameters: settings.init_parameters,
+                simulation_parameters,
+                state: flock.boids_state(),
+            },
+        )?;
+    }
+
+    Ok(elapsed)
+}
+
+// Headless runs without output files, the timings are logged and written as CSV to the output file if any
+pub fn run_bench(settings: &[HeadlessSettings], output_path: Option<&str>) -> Result<()> {
+    let mut writer = match output_path {
+        Some(path) => {
+            let mut writer = std::io::BufWriter::new(std::fs::File::create(path).with_context(|| format!("could not create {}", path))?);
+            writeln!(writer, "strategy,rule_set,spatial_partitioning,boids,steps,ms_per_step")?;
+            Some(writer)
+        },
+        None => None,
+    };
+
+    for settings in settings {
+        let elapsed = run_headless(settings)?;
+        let ms_per_step = elapsed.as_secs_f64() * 1000.0 / settings.steps.max(1) as f64;
+        log::info!(
+            "{}, {}{}, {} boids: {:.4} ms per step",
+            settings.simulation_strategy_kind.label(),
+            settings.rule_set.label(),
+            if settings.use_spatial_partitioning { ", spatial partitioning" } else { "" },
+            settings.simulation_parameters.boids_count,
+            ms_per_step,
+        );
+        if let Some(writer) = &mut writer {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                settings.simulation_strategy_kind.label(),
+                settings.rule_set.label(),
+                settings.use_spatial_partitioning,
+                settings.simulation_parameters.boids_count,
+                settings.steps,
+                ms_per_step,
+            )?;
+        }
+    }
+
+    if let Some(writer) = &mut writer {
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::parameters::BoundaryMode;
+
+    // Set on machines that must have a software adapter, e.g. CI with lavapipe, so the test can't silently skip
+    const REQUIRE_ADAPTER_VARIABLE: &str = "RUSTY_BOIDS_REQUIRE_ADAPTER";
+
+    const BOIDS_COUNT: u32 = 256;
+    const STEPS: u32 = 20;
+    const TOLERANCE: f32 = 1e-3;
+
+    fn gpu_state(simulation_parameters: SimulationParametersUniformBufferContent, use_spatial_partitioning: bool) -> Snapshot {
+        let path = std::env::temp_dir().join(format!("rusty_boids_{}_{}.snapshot", std::process::id(), use_spatial_partitioning));
+        let path = path.to_string_lossy().into_owned();
+        let settings = HeadlessSettings {
+            steps: STEPS,
+            simulation_strategy_kind: SimulationStrategyKind::Gpu,
+            rule_set: RuleSet::Reynolds,
+            use_spatial_partitioning,
+            init_parameters: InitParametersUniformBufferContent::default(),
+            simulation_parameters,
+            snapshot_path: Some(path.clone()),
+            trajectory_path: None,
+            trajectory_interval: 1,
+            metrics_path: None,
+            metrics_interval: 1,
+            force_fallback_adapter: true,
+        };
+        run_headless(&settings).unwrap();
+        let snapshot = Snapshot::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        snapshot
+    }
+
+    // Same init and steps on the CPU, with the parameters the GPU run ended with
+    fn cpu_state(snapshot: &Snapshot) -> BoidsState {
+        let species_settings = SpeciesSettings::default();
+        let obstacles_buffer_contents = Obstacles::default().buffer_contents();
+        let pointer_force: PointerForceUniformBufferContent = bytemuck::Zeroable::zeroed();
+        let mut flock = CpuFlock::new(BOIDS_COUNT, &snapshot.init_parameters, &species_settings.species);
+        for _ in 0..STEPS {
+            flock.step(RuleSet::Reynolds, &snapshot.simulation_parameters, &obstacles_buffer_contents, &species_settings, &pointer_force);
+        }
+        flock.boids_state()
+    }
+
+    #[test]
+    fn gpu_strategy_matches_cpu_reference() {
+        if let Err(error) = find_adapter(true, &device_limits()) {
+            if std::env::var_os(REQUIRE_ADAPTER_VARIABLE).is_some() {
+                panic!("{}", error);
+            }
+            eprintln!("Skipped, {} (set {} to fail instead)", error, REQUIRE_ADAPTER_VARIABLE);
+            return;
+        }
+
+        let default_parameters = SimulationParametersUniformBufferContent { boids_count: BOIDS_COUNT, ..Default::default() };
+        // A 3x3 grid whose neighbor cells range covers every cell, wrapped neighbors included
+        let coarse_grid_parameters = SimulationParametersUniformBufferContent {
+            view_radius: 0.4,
+            boundary_mode: BoundaryMode::Toroidal as u32,
+            ..default_parameters
+        };
+        for (simulation_parameters, use_spatial_partitioning) in
+            [(default_parameters, false), (default_parameters, true), (coarse_grid_parameters, false), (coarse_grid_parameters, true)]
+        {
+            let snapshot = gpu_state(simulation_parameters, use_spatial_partitioning);
+            let expected = cpu_state(&snapshot);
+            assert_eq!(snapshot.state.boids_count(), BOIDS_COUNT);
+
+            // The GPU sorts the boids by cell, they are matched by id
+            let mut expected_indices = vec![usize::MAX; BOIDS_COUNT as usize];
+            for (index, id) in expected.ids.iter().enumerate() {
+                expected_indices[*id as usize] = index;
+            }
+            for (index, id) in snapshot.state.ids.iter().enumerate() {
+                let expected_index = expected_indices[*id as usize];
+                let position_error = (snapshot.state.positions[index].xy() - expected.positions[expected_index].xy()).norm();
+                let velocity_error = (snapshot.state.velocities[index].xy() - expected.velocities[expected_index].xy()).norm();
+                assert!(
+                    position_error < TOLERANCE && velocity_error < TOLERANCE,
+                    "boid {} differs (spatial partitioning: {}, grid size: {}): position error {}, velocity error {}",
+                    id,
+                    use_spatial_partitioning,
+                    snapshot.simulation_parameters.grid_size,
+                    position_error,
+                    velocity_error
+                );
+            }
+        }
+    }
+}
@@ -1,12 +1,17 @@
 mod app;
+mod cli;
+mod headless;
 mod simulation;
 mod utils;
 
+use anyhow::Context;
+use clap::Parser;
 use fern::colors::ColoredLevelConfig;
-fn main() {
+
+fn setup_logger(level: log::LevelFilter, log_file: Option<&std::path::Path>) -> anyhow::Result<()> {
     let color_config = ColoredLevelConfig::new();
-    fern::Dispatch::new()
-        .level(log::LevelFilter::Debug)
+    let mut dispatch = fern::Dispatch::new()
+        .level(level)
         .level_for("wgpu_hal", log::LevelFilter::Warn)
         .level_for("naga", log::LevelFilter::Warn)
         .level_for("wgpu_core", log::LevelFilter::Warn)
@@ -26,9 +31,38 @@ fn main() {
                 color = format_args!("\x1B[{color_number}m", color_number = color_config.get_color(&record.level()).to_fg_str()),
                 color_reset = "\x1B[0m",
             ))
-        }))
-        .apply()
-        .unwrap();
+        }));
+    if let Some(log_file) = log_file {
+        dispatch = dispatch.chain(fern::log_file(log_file)?);
+    }
+    dispatch.apply()?;
+    Ok(())
+}
+
+fn run_window(run_args: &cli::RunArgs) -> anyhow::Result<()> {
+    let mut run_settings = run_args.settings()?;
+
+    // Adapters without the limits of the compute pipelines can still draw the CPU reference
+    let device_limits = match headless::find_adapter(false, &simulation::device_limits()) {
+        Ok(_) => simulation::device_limits(),
+        Err(error) => {
+            headless::find_adapter(false, &simulation::cpu_reference_device_limits()).context("could not open the window")?;
+            log::warn!("{}, the window runs the CPU reference instead", error);
+            run_settings.simulation_strategy_kind = simulation::SimulationStrategyKind::CpuReference;
+            simulation::cpu_reference_device_limits()
+        },
+    };
+    let _ = app::RUN_SETTINGS.set(run_settings);
+
+    let mut rendering_config = oxyde::RenderingConfig {
+        power_preference: oxyde::wgpu::PowerPreference::HighPerformance,
+        device_features: wgpu_profiler::GpuProfiler::ALL_WGPU_TIMER_FEATURES | oxyde::wgpu::Features::default(),
+        device_limits,
+        ..oxyde::RenderingConfig::default()
+    };
+    if let Some(present_mode) = run_args.present_mode {
+        rendering_config.window_surface_present_mode = present_mode.into();
+    }
 
     oxyde::run_application::<app::RustyBoids>(
         oxyde::AppConfig {
@@ -36,18 +70,24 @@ fn main() {
             title: "Rusty Boids",
             control_flow: oxyde::winit::event_loop::ControlFlow::Poll,
         },
-        oxyde::RenderingConfig {
-            power_preference: oxyde::wgpu::PowerPreference::HighPerformance,
-            // window_surface_present_mode: oxyde::wgpu::PresentMode::Immediate,
-            ..oxyde::RenderingConfig {
-                device_features: wgpu_profiler::GpuProfiler::ALL_WGPU_TIMER_FEATURES | oxyde::wgpu::Features::default(),
-                device_limits: oxyde::wgpu::Limits {
-                    max_bind_groups: 6,
-                    ..oxyde::wgpu::Limits::default()
-                },
-                ..oxyde::RenderingConfig::default()
-            }
-        },
+        rendering_config,
     )
-    .unwrap();
+}
+
+fn main() {
+    let cli = cli::Cli::parse();
+    if let Err(error) = setup_logger(cli.log_level, cli.log_file.as_deref()) {
+        eprintln!("Could not set up the logger: {:#}", error);
+        std::process::exit(1);
+    }
+
+    let result = match cli.command.unwrap_or(cli::Command::Run(cli::RunArgs::default())) {
+        cli::Command::Run(run_args) => run_window(&run_args),
+        cli::Command::Headless(headless_args) => headless_args.settings().and_then(|settings| headless::run_headless(&settings).map(|_| ())),
+        cli::Command::Bench(bench_args) => bench_args.settings().and_then(|settings| headless::run_bench(&settings, bench_args.output.as_deref())),
+    };
+    if let Err(error) = result {
+        log::error!("{:#}", error);
+        std::process::exit(1);
+    }
 }
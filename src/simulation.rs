@@ -1,21 +1,175 @@
 pub mod parameters;
 pub mod types;
+pub mod wgsl_struct;
+pub mod shader_builder;
+pub mod boids_buffers;
+pub mod boids_state;
 pub mod gpu_spatial_partitioning_strategy;
+pub mod gpu_counting_sort;
+pub mod cpu_reference_strategy;
+pub mod camera;
+pub mod perspective_boids_renderer;
+pub mod obstacles;
+pub mod species;
+pub mod predators;
+pub mod pointer_force;
+pub mod image_mask;
+pub mod snapshot;
+pub mod trajectory;
+pub mod parameter_file;
 
-use oxyde::{wgpu, wgpu_utils::uniform_buffer::UniformBufferWrapper, AppState};
+use oxyde::{egui, wgpu, wgpu_utils::uniform_buffer::UniformBufferWrapper, AppState};
 pub use parameters::SimulationParametersUniformBufferContent;
 
-use self::parameters::InitParametersUniformBufferContent;
+use self::{
+    boids_state::BoidsState, cpu_reference_strategy::create_cpu_reference_strategy, gpu_spatial_partitioning_strategy::create_gpu_spatial_partitioning_strategy,
+    obstacles::Obstacles, parameters::{InitParametersUniformBufferContent, RuleSet}, pointer_force::PointerForceUniformBufferContent, species::SpeciesSettings,
+};
+
+pub const WORKGROUP_SIZE: u32 = 64;
+
+// Limits the device needs for the simulation pipelines, with or without a window
+pub fn device_limits() -> wgpu::Limits {
+    wgpu::Limits {
+        // Parameters, boids, sorting id, boids per cell count and environment groups of the compute pipelines
+        max_bind_groups: 5,
+        // The compute pipelines bind the 12 ping pong boids buffers, the sorting id, the boids per cell count
+        // and 5 environment buffers (obstacles, obstacle vertices, species, species interactions and predators)
+        max_storage_buffers_per_shader_stage: 19,
+        ..wgpu::Limits::default()
+    }
+}
+
+// Limits of the CPU reference, it only draws and its boids display reads 6 storage buffers from the vertex stage
+pub fn cpu_reference_device_limits() -> wgpu::Limits {
+    wgpu::Limits {
+        max_storage_buffers_per_shader_stage: 6,
+        ..wgpu::Limits::downlevel_defaults()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dimension {
+    Two,
+    Three,
+}
+
+impl Dimension {
+    pub fn count(&self) -> u32 {
+        match self {
+            Dimension::Two => 2,
+            Dimension::Three => 3,
+        }
+    }
+
+    // Variant of the simulation shaders, resolved as the dimension.wgsl include
+    pub fn shader_include(&self) -> &'static str {
+        match self {
+            Dimension::Two => include_str!("../shaders/dimension2d.wgsl"),
+            Dimension::Three => include_str!("../shaders/dimension3d.wgsl"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum SimulationStrategyKind {
+    Gpu,
+    Gpu3d,
+    // Pure rust mirror of the shaders, slow but does not need GPU compute
+    CpuReference,
+}
+
+impl SimulationStrategyKind {
+    pub const ALL: [SimulationStrategyKind; 3] = [SimulationStrategyKind::Gpu, SimulationStrategyKind::Gpu3d, SimulationStrategyKind::CpuReference];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SimulationStrategyKind::Gpu => "GPU",
+            SimulationStrategyKind::Gpu3d => "GPU 3D",
+            SimulationStrategyKind::CpuReference => "CPU reference",
+        }
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            SimulationStrategyKind::Gpu3d => Dimension::Three,
+            SimulationStrategyKind::Gpu | SimulationStrategyKind::CpuReference => Dimension::Two,
+        }
+    }
+}
 
 pub trait SimulationStrategy {
+    // Run the init or the steps and submit them, without display so it works without a window too
+    fn simulate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+        need_init: &mut bool,
+        // Number of simulation steps to run before display, each of them integrating the delta time of the simulation parameters
+        steps: u32,
+        // Neighbors search using the uniform grid instead of checking every boid
+        use_spatial_partitioning: bool,
+        obstacles: &Obstacles,
+        species_settings: &SpeciesSettings,
+        pointer_force: &PointerForceUniformBufferContent,
+    );
+
+    // Draw the boids written by the last simulation
     fn render(
         &mut self,
         _app_state: &mut AppState,
         _output_view: &wgpu::TextureView,
-        init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
         simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
         vertices_buffer: &wgpu::Buffer,
         simulation_profiler: &mut wgpu_profiler::GpuProfiler,
-        need_init: &mut bool,
     ) -> Result<(), wgpu::SurfaceError>;
-}
\ No newline at end of file
+
+    // Recreate the pipelines depending on the rule set, boids are kept
+    fn set_rule_set(&mut self, device: &wgpu::Device, rule_set: RuleSet);
+
+    // Replace the boids with a state built on the host, the boids count of the simulation parameters must follow
+    fn set_boids_state(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, state: &BoidsState);
+
+    // Current boids, read back from the buffers written by the last step
+    fn boids_state(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> BoidsState;
+
+    // Buffers written by the last step, for read backs that should not stall the frame
+    fn current_buffers(&self) -> &[wgpu::Buffer; 6];
+
+    // Settings specific to the strategy, shown under the strategy selection
+    fn display_ui(&mut self, _ui: &mut egui::Ui) {}
+}
+
+pub fn create_simulation_strategy(
+    simulation_strategy_kind: SimulationStrategyKind,
+    rule_set: RuleSet,
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
+    simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+) -> Box<dyn SimulationStrategy> {
+    match simulation_strategy_kind {
+        SimulationStrategyKind::Gpu => create_gpu_spatial_partitioning_strategy(
+            Dimension::Two,
+            rule_set,
+            device,
+            config,
+            init_parameters_uniform_buffer,
+            simulation_parameters_uniform_buffer,
+        ),
+        SimulationStrategyKind::Gpu3d => create_gpu_spatial_partitioning_strategy(
+            Dimension::Three,
+            rule_set,
+            device,
+            config,
+            init_parameters_uniform_buffer,
+            simulation_parameters_uniform_buffer,
+        ),
+        SimulationStrategyKind::CpuReference => {
+            create_cpu_reference_strategy(rule_set, device, config, init_parameters_uniform_buffer, simulation_parameters_uniform_buffer)
+        },
+    }
+}
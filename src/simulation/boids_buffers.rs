@@ -0,0 +1,221 @@
+use oxyde::{wgpu, wgpu_utils::binding_builder};
+
+use super::{shader_builder::create_shader_module, types::*, Dimension, WORKGROUP_SIZE};
+
+// Size of one boid in each of the boids data buffers (position, velocity, cell id, species id, random state, id)
+fn boids_data_element_sizes(dimension: Dimension) -> [u64; 6] {
+    match dimension {
+        Dimension::Two => [
+            std::mem::size_of::<BoidsPosition>() as u64,
+            std::mem::size_of::<BoidsVelocity>() as u64,
+            std::mem::size_of::<BoidsCellId>() as u64,
+            std::mem::size_of::<BoidsSpeciesId>() as u64,
+            std::mem::size_of::<BoidsRandomState>() as u64,
+            std::mem::size_of::<BoidId>() as u64,
+        ],
+        Dimension::Three => [
+            std::mem::size_of::<Boids3dPosition>() as u64,
+            std::mem::size_of::<Boids3dVelocity>() as u64,
+            std::mem::size_of::<BoidsCellId>() as u64,
+            std::mem::size_of::<BoidsSpeciesId>() as u64,
+            std::mem::size_of::<BoidsRandomState>() as u64,
+            std::mem::size_of::<BoidId>() as u64,
+        ],
+    }
+}
+
+// Layouts only depend on the size of one boid so buffers can be reallocated without recreating pipelines
+pub struct BoidsBindGroupLayouts {
+    pub ping_pong: binding_builder::BindGroupLayoutWithDesc,
+    pub read_only: binding_builder::BindGroupLayoutWithDesc,
+    pub element_sizes: [u64; 6],
+}
+
+impl BoidsBindGroupLayouts {
+    pub fn new(
+        device: &wgpu::Device,
+        dimension: Dimension,
+        ping_pong_buffer_visibility: wgpu::ShaderStages,
+        read_only_buffer_visibility: wgpu::ShaderStages,
+    ) -> Self {
+        let storage_binding = |read_only: bool, element_size: u64| wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(element_size),
+        };
+
+        let element_sizes = boids_data_element_sizes(dimension);
+        let [position_size, velocity_size, cell_id_size, species_id_size, random_state_size, id_size] = element_sizes;
+
+        // Source then destination of position, velocity and cell id at 0 to 5,
+        // then source and destination pairs of species id (6, 7), random state (8, 9) and id (10, 11)
+        let ping_pong = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding(ping_pong_buffer_visibility, storage_binding(true, position_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(true, velocity_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(true, cell_id_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(false, position_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(false, velocity_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(false, cell_id_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(true, species_id_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(false, species_id_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(true, random_state_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(false, random_state_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(true, id_size))
+            .add_binding(ping_pong_buffer_visibility, storage_binding(false, id_size))
+            .create(device, Some("Boids data (ping <-> pong)"));
+
+        // read only bind group for final display: position 0, velocity 1, cell id 2, species id 3, id 4 (random states are compute only)
+        let read_only = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding(read_only_buffer_visibility, storage_binding(true, position_size))
+            .add_binding(read_only_buffer_visibility, storage_binding(true, velocity_size))
+            .add_binding(read_only_buffer_visibility, storage_binding(true, cell_id_size))
+            .add_binding(read_only_buffer_visibility, storage_binding(true, species_id_size))
+            .add_binding(read_only_buffer_visibility, storage_binding(true, id_size))
+            .create(device, Some("Boids data (read only)"));
+
+        Self { ping_pong, read_only, element_sizes }
+    }
+}
+
+fn create_ping_pong_bind_group(
+    device: &wgpu::Device,
+    layout: &binding_builder::BindGroupLayoutWithDesc,
+    src: &[wgpu::Buffer; 6],
+    dst: &[wgpu::Buffer; 6],
+    label: &str,
+) -> wgpu::BindGroup {
+    binding_builder::BindGroupBuilder::new(layout)
+        .resource(src[0].as_entire_binding())
+        .resource(src[1].as_entire_binding())
+        .resource(src[2].as_entire_binding())
+        .resource(dst[0].as_entire_binding())
+        .resource(dst[1].as_entire_binding())
+        .resource(dst[2].as_entire_binding())
+        .resource(src[3].as_entire_binding())
+        .resource(dst[3].as_entire_binding())
+        .resource(src[4].as_entire_binding())
+        .resource(dst[4].as_entire_binding())
+        .resource(src[5].as_entire_binding())
+        .resource(dst[5].as_entire_binding())
+        .create(device, Some(label))
+}
+
+// Ping and pong buffers of boids data (position, velocity, cell id, species id, random state, id) and the bind groups using them
+pub struct BoidsBuffers {
+    pub boids_count: u32,
+
+    pub ping_buffers: [wgpu::Buffer; 6],
+    pub pong_buffers: [wgpu::Buffer; 6],
+
+    pub ping_pong_bind_group: wgpu::BindGroup,
+    pub pong_ping_bind_group: wgpu::BindGroup,
+    pub ping_bind_group: wgpu::BindGroup,
+    pub pong_bind_group: wgpu::BindGroup,
+}
+
+impl BoidsBuffers {
+    pub fn new(device: &wgpu::Device, boids_count: u32, layouts: &BoidsBindGroupLayouts) -> Self {
+        let usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
+
+        let create_buffers = |suffix: &str| {
+            let create_buffer = |label: &str, element_size: u64| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("{} {}", label, suffix)),
+                    size: boids_count as u64 * element_size,
+                    usage,
+                    mapped_at_creation: false,
+                })
+            };
+            let [position_size, velocity_size, cell_id_size, species_id_size, random_state_size, id_size] = layouts.element_sizes;
+            [
+                create_buffer("Position", position_size),
+                create_buffer("Velocity", velocity_size),
+                create_buffer("Cell Id", cell_id_size),
+                create_buffer("Species Id", species_id_size),
+                create_buffer("Random State", random_state_size),
+                create_buffer("Boid Id", id_size),
+            ]
+        };
+
+        let ping_buffers = create_buffers("ping");
+        let pong_buffers = create_buffers("pong");
+
+        let create_read_only_bind_group = |buffers: &[wgpu::Buffer; 6], label: &str| {
+            binding_builder::BindGroupBuilder::new(&layouts.read_only)
+                .resource(buffers[0].as_entire_binding())
+                .resource(buffers[1].as_entire_binding())
+                .resource(buffers[2].as_entire_binding())
+                .resource(buffers[3].as_entire_binding())
+                .resource(buffers[5].as_entire_binding())
+                .create(device, Some(label))
+        };
+
+        Self {
+            boids_count,
+            ping_pong_bind_group: create_ping_pong_bind_group(device, &layouts.ping_pong, &ping_buffers, &pong_buffers, "Boids data (ping -> pong)"),
+            pong_ping_bind_group: create_ping_pong_bind_group(device, &layouts.ping_pong, &pong_buffers, &ping_buffers, "Boids data (pong -> ping)"),
+            ping_bind_group: create_read_only_bind_group(&ping_buffers, "Boids data (ping read only)"),
+            pong_bind_group: create_read_only_bind_group(&pong_buffers, "Boids data (pong read only)"),
+            ping_buffers,
+            pong_buffers,
+        }
+    }
+
+    // Buffers written by the last step (the pong buffers when ping_pong_state is true)
+    pub fn current_buffers(&self, ping_pong_state: bool) -> &[wgpu::Buffer; 6] {
+        if ping_pong_state {
+            &self.pong_buffers
+        } else {
+            &self.ping_buffers
+        }
+    }
+}
+
+// Moves the boids kept by a resize from the previous buffers to the slot of their id in the new ones (resizeBoids.wgsl)
+// so ids stay 0..count and survivors keep theirs, whether boids are added or removed
+pub struct BoidsResizePass {
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl BoidsResizePass {
+    pub fn new(device: &wgpu::Device, dimension: Dimension, layouts: &BoidsBindGroupLayouts) -> Self {
+        let shader = create_shader_module(device, "Resize Boids Shader", include_str!("../../shaders/resizeBoids.wgsl"), dimension);
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Resize boids pipeline"),
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Resize Boids Pipeline"),
+                bind_group_layouts: &[&layouts.ping_pong.layout],
+                push_constant_ranges: &[],
+            })),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        Self { pipeline }
+    }
+
+    // To encode once the new buffers are seeded by the init shader, both sets of buffers have the same ping pong state
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        layouts: &BoidsBindGroupLayouts,
+        previous: &BoidsBuffers,
+        current: &BoidsBuffers,
+        ping_pong_state: bool,
+    ) {
+        let bind_group = create_ping_pong_bind_group(
+            device,
+            &layouts.ping_pong,
+            previous.current_buffers(ping_pong_state),
+            current.current_buffers(ping_pong_state),
+            "Boids data (resize)",
+        );
+
+        let compute_pass = &mut encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Resize Boids Pass"), timestamp_writes: None });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(previous.boids_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+}
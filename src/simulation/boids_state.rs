@@ -0,0 +1,90 @@
+use oxyde::wgpu;
+
+use super::{types::*, Dimension};
+
+// Mirror of position_to_grid_cell_id in dimension2d.wgsl and dimension3d.wgsl
+fn position_to_grid_cell_id(position: &nalgebra_glm::Vec4, dimension: Dimension, grid_size: u32) -> BoidsCellId {
+    let cell = |x: f32| ((x * grid_size as f32).floor() as i32).clamp(0, grid_size as i32 - 1) as u32;
+    match dimension {
+        Dimension::Two => grid_size * cell(position.y) + cell(position.x),
+        Dimension::Three => (grid_size * cell(position.z) + cell(position.y)) * grid_size + cell(position.x),
+    }
+}
+
+// Boids data on the host, built instead of running init.wgsl (image masks, snapshots) or read back from the buffers
+// Positions and velocities are vec4 whatever the dimension, 2D boids only use xy
+#[derive(Clone, Default)]
+pub struct BoidsState {
+    pub positions: Vec<nalgebra_glm::Vec4>,
+    pub velocities: Vec<nalgebra_glm::Vec4>,
+    pub species: Vec<BoidsSpeciesId>,
+    pub random_states: Vec<BoidsRandomState>,
+    pub ids: Vec<BoidId>,
+}
+
+impl BoidsState {
+    pub fn boids_count(&self) -> u32 { self.positions.len() as u32 }
+
+    // Blocking read back of the given buffers, fine for occasional snapshots but it stalls the frame
+    pub fn read_buffers(device: &wgpu::Device, queue: &wgpu::Queue, buffers: &[wgpu::Buffer; 6], dimension: Dimension) -> Self {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Boids Read Back Encoder") });
+        let staging_buffers = buffers.each_ref().map(|buffer| {
+            let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Boids read back"),
+                size: buffer.size(),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, buffer.size());
+            staging_buffer
+        });
+        queue.submit(Some(encoder.finish()));
+
+        for staging_buffer in &staging_buffers {
+            staging_buffer.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        }
+        device.poll(wgpu::Maintain::Wait);
+
+        let [positions, velocities, _, species, random_states, ids] = staging_buffers.each_ref().map(|staging_buffer| staging_buffer.slice(..).get_mapped_range());
+        let (positions, velocities) = match dimension {
+            Dimension::Two => (
+                bytemuck::cast_slice::<u8, BoidsPosition>(&positions).iter().map(|position| nalgebra_glm::vec4(position.x, position.y, 0.0, 0.0)).collect(),
+                bytemuck::cast_slice::<u8, BoidsVelocity>(&velocities).iter().map(|velocity| nalgebra_glm::vec4(velocity.x, velocity.y, 0.0, 0.0)).collect(),
+            ),
+            Dimension::Three => (bytemuck::cast_slice::<u8, Boids3dPosition>(&positions).to_vec(), bytemuck::cast_slice::<u8, Boids3dVelocity>(&velocities).to_vec()),
+        };
+
+        Self {
+            positions,
+            velocities,
+            species: bytemuck::cast_slice(&species).to_vec(),
+            random_states: bytemuck::cast_slice(&random_states).to_vec(),
+            ids: bytemuck::cast_slice(&ids).to_vec(),
+        }
+    }
+
+    // Write every boid in the given buffers, sized for the same boids count
+    // Cell ids are only used for display, they are computed with the current grid size
+    pub fn write_buffers(&self, queue: &wgpu::Queue, buffers: &[wgpu::Buffer; 6], dimension: Dimension, grid_size: u32) {
+        let [position_buffer, velocity_buffer, cell_id_buffer, species_id_buffer, random_state_buffer, id_buffer] = buffers;
+
+        match dimension {
+            Dimension::Two => {
+                let positions: Vec<BoidsPosition> = self.positions.iter().map(|position| position.xy()).collect();
+                let velocities: Vec<BoidsVelocity> = self.velocities.iter().map(|velocity| velocity.xy()).collect();
+                queue.write_buffer(position_buffer, 0, bytemuck::cast_slice(&positions));
+                queue.write_buffer(velocity_buffer, 0, bytemuck::cast_slice(&velocities));
+            },
+            Dimension::Three => {
+                queue.write_buffer(position_buffer, 0, bytemuck::cast_slice(&self.positions));
+                queue.write_buffer(velocity_buffer, 0, bytemuck::cast_slice(&self.velocities));
+            },
+        }
+
+        let cell_ids: Vec<BoidsCellId> = self.positions.iter().map(|position| position_to_grid_cell_id(position, dimension, grid_size)).collect();
+        queue.write_buffer(cell_id_buffer, 0, bytemuck::cast_slice(&cell_ids));
+        queue.write_buffer(species_id_buffer, 0, bytemuck::cast_slice(&self.species));
+        queue.write_buffer(random_state_buffer, 0, bytemuck::cast_slice(&self.random_states));
+        queue.write_buffer(id_buffer, 0, bytemuck::cast_slice(&self.ids));
+    }
+}
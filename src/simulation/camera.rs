@@ -0,0 +1,97 @@
+use oxyde::egui;
+
+use super::wgsl_struct::wgsl_struct;
+
+wgsl_struct! {
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct CameraUniformBufferContent as "Camera" {
+        pub view_projection: nalgebra_glm::Mat4,
+    }
+}
+
+impl Default for CameraUniformBufferContent {
+    fn default() -> Self { Self { view_projection: nalgebra_glm::identity() } }
+}
+
+// Keep the camera away from the poles where the up vector would be aligned with the view direction
+const MAX_PITCH: f32 = 1.5;
+
+// Camera orbiting around the center of the [0, 1]³ simulation cube
+pub struct OrbitCamera {
+    // Angles in radians
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    // Vertical field of view in degrees
+    pub fov_y: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            yaw: 0.6,
+            pitch: 0.4,
+            distance: 2.2,
+            fov_y: 50.0,
+        }
+    }
+}
+
+impl OrbitCamera {
+    fn target() -> nalgebra_glm::Vec3 { nalgebra_glm::vec3(0.5, 0.5, 0.5) }
+
+    pub fn eye(&self) -> nalgebra_glm::Vec3 {
+        Self::target()
+            + self.distance
+                * nalgebra_glm::vec3(self.pitch.cos() * self.yaw.sin(), self.pitch.sin(), self.pitch.cos() * self.yaw.cos())
+    }
+
+    pub fn view_projection(&self, aspect_ratio: f32) -> nalgebra_glm::Mat4 {
+        let projection = nalgebra_glm::perspective_rh_zo(aspect_ratio, self.fov_y.to_radians(), 0.01, 100.0);
+        let view = nalgebra_glm::look_at_rh(&self.eye(), &Self::target(), &nalgebra_glm::vec3(0.0, 1.0, 0.0));
+        projection * view
+    }
+
+    // Drag to orbit and scroll to zoom, only when the pointer is over the simulation and not the GUI
+    pub fn handle_input(&mut self, ctx: &egui::Context) {
+        if ctx.is_pointer_over_area() || ctx.is_using_pointer() {
+            return;
+        }
+
+        let (drag, scroll) = ctx.input(|input| {
+            (
+                if input.pointer.primary_down() {
+                    input.pointer.delta()
+                } else {
+                    egui::Vec2::ZERO
+                },
+                input.smooth_scroll_delta.y,
+            )
+        });
+
+        self.yaw -= drag.x * 0.01;
+        self.pitch = (self.pitch + drag.y * 0.01).clamp(-MAX_PITCH, MAX_PITCH);
+        self.distance = (self.distance * (-scroll * 0.002).exp()).clamp(0.5, 10.0);
+    }
+
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Camera").default_open(true).show(ui, |ui| {
+            ui.label("Drag to orbit, scroll to zoom");
+
+            ui.horizontal(|ui| {
+                ui.label("Yaw");
+                ui.drag_angle(&mut self.yaw);
+                ui.label("Pitch");
+                ui.drag_angle(&mut self.pitch);
+            });
+            self.pitch = self.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+
+            ui.add(egui::Slider::new(&mut self.distance, 0.5..=10.0).prefix("Distance"));
+            ui.add(egui::Slider::new(&mut self.fov_y, 10.0..=120.0).prefix("Field of view (degrees)"));
+
+            if ui.button("Reset camera").clicked() {
+                *self = Self::default();
+            }
+        });
+    }
+}
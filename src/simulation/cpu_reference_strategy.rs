@@ -0,0 +1,1387 @@
+use oxyde::{wgpu, wgpu_utils::uniform_buffer::UniformBufferWrapper};
+
+use super::{
+    boids_buffers::{BoidsBindGroupLayouts, BoidsBuffers},
+    boids_state::BoidsState,
+    gpu_spatial_partitioning_strategy::{create_render_pipeline, create_sorting_id_buffer},
+    obstacles::{ObstacleBufferContent, ObstacleKind, Obstacles, ObstaclesBufferContents, ObstaclesBuffers, ObstaclesRenderer, OBSTACLE_GRADIENT_STEP},
+    parameters::{BoundaryMode, InitMode, InitParametersUniformBufferContent, NeighborhoodMode, RuleSet, SteeringModel, WeightingKernel, MAX_TOPOLOGICAL_NEIGHBORS},
+    pointer_force::{PointerForceMode, PointerForceUniformBufferContent},
+    predators::{init_predators, PredatorBufferContent, PredatorsBuffers, PredatorsRenderer},
+    shader_builder::create_shader_module,
+    species::{SpeciesBufferContent, SpeciesBuffers, SpeciesSettings},
+    types::*,
+    SimulationParametersUniformBufferContent,
+    Dimension,
+    SimulationStrategy,
+};
+
+// Pure rust mirror of the 2D variant of init.wgsl (all init modes) and of the rule sets (flocking.wgsl, couzin.wgsl and vicsek.wgsl), used as a ground truth for the shaders and as a fallback without GPU compute
+
+// Mirror of hash1 in hash.wgsl (u32 arithmetic wraps in WGSL)
+pub fn hash1(n: u32) -> f32 {
+    let m = (n << 13) ^ n;
+    let m = m.wrapping_mul(m.wrapping_mul(m).wrapping_mul(15731).wrapping_add(789221)).wrapping_add(1376312589);
+    (m & 0x7fffffff) as f32 / 0x7fffffff as f32
+}
+
+// Mirror of random.wgsl
+pub fn pcg_hash(n: u32) -> u32 {
+    let state = n.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+pub fn random_float(state: &mut BoidsRandomState) -> f32 {
+    *state = pcg_hash(*state);
+    (*state >> 8) as f32 / 16777216.0
+}
+
+fn random_vec(state: &mut BoidsRandomState) -> BoidsVelocity {
+    let x = random_float(state) * 2.0 - 1.0;
+    let y = random_float(state) * 2.0 - 1.0;
+    BoidsVelocity::new(x, y)
+}
+
+pub struct FlockingParameters {
+    pub avg_position: BoidsPosition,
+    pub avoidance: BoidsVelocity,
+    pub avg_velocity: BoidsVelocity,
+    pub cohesion_weight: f32,
+    pub alignment_weight: f32,
+    pub neighbor_count: u32,
+    pub avoid_count: u32,
+}
+
+pub fn flocking_init() -> FlockingParameters {
+    FlockingParameters {
+        avg_position: BoidsPosition::zeros(),
+        avoidance: BoidsVelocity::zeros(),
+        avg_velocity: BoidsVelocity::zeros(),
+        cohesion_weight: 0.0,
+        alignment_weight: 0.0,
+        neighbor_count: 0,
+        avoid_count: 0,
+    }
+}
+
+// Mirror of clampSpecies in boidsCommon.wgsl
+pub fn clamp_species(simulation_parameters: &SimulationParametersUniformBufferContent, species_id: BoidsSpeciesId) -> BoidsSpeciesId {
+    species_id.min(simulation_parameters.species_count - 1)
+}
+
+// Mirror of speciesViewRadius in boidsCommon.wgsl
+pub fn species_view_radius(simulation_parameters: &SimulationParametersUniformBufferContent, species_settings: &SpeciesSettings, species_id: BoidsSpeciesId) -> f32 {
+    simulation_parameters.view_radius * species_settings.species[species_id as usize].view_radius_factor
+}
+
+// Mirror of kernelWeight in flocking.wgsl
+pub fn kernel_weight(kernel: u32, normalized_distance: f32) -> f32 {
+    match WeightingKernel::from_u32(kernel) {
+        WeightingKernel::Uniform => 1.0,
+        WeightingKernel::Linear => 1.0 - normalized_distance,
+        WeightingKernel::InverseSquare => 1.0 / (normalized_distance * normalized_distance).max(0.0001),
+        WeightingKernel::Gaussian => (-2.0 * normalized_distance * normalized_distance).exp(),
+    }
+}
+
+// Mirror of isVisible in boidsCommon.wgsl, the NaN of a null offset counts as visible
+pub fn is_visible(simulation_parameters: &SimulationParametersUniformBufferContent, current_to_other: &BoidsPosition, current_velocity: &BoidsVelocity) -> bool {
+    let cos_angle = current_to_other.normalize().dot(&current_velocity.normalize());
+    cos_angle.is_nan() || cos_angle >= (simulation_parameters.field_of_view * 0.5).to_radians().cos()
+}
+
+pub fn flocking_accumulate(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    species_settings: &SpeciesSettings,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+    current_species: BoidsSpeciesId,
+    other_position: &BoidsPosition,
+    other_velocity: &BoidsVelocity,
+    other_species: BoidsSpeciesId,
+    flocking_parameters: &mut FlockingParameters,
+) {
+    let current_to_other = position_offset(simulation_parameters, current_position, other_position);
+    let sqrt_distance = current_to_other.dot(&current_to_other);
+
+    let view_radius = species_view_radius(simulation_parameters, species_settings, current_species);
+    let sqrt_view_radius = view_radius * view_radius;
+    // Skip if too far away, topological neighbors are already selected whatever their distance
+    if NeighborhoodMode::from_u32(simulation_parameters.neighborhood_mode) == NeighborhoodMode::Metric && sqrt_distance > sqrt_view_radius {
+        return;
+    }
+
+    // Visiblity angle
+    if !is_visible(simulation_parameters, &current_to_other, current_velocity) {
+        return;
+    }
+
+    let distance = sqrt_distance.sqrt();
+    // Kernels weight farther topological neighbors as if they were at the view radius
+    let normalized_distance = (distance / view_radius).min(1.0);
+
+    let interaction = &species_settings.interactions
+        [(current_species * simulation_parameters.species_count + clamp_species(simulation_parameters, other_species)) as usize];
+
+    // Separation
+    let separation_radius = view_radius * simulation_parameters.separation_radius_factor;
+    let separation_weight = kernel_weight(simulation_parameters.separation_kernel, distance / separation_radius) * interaction.separation;
+    if distance < separation_radius && separation_weight > 0.0 {
+        flocking_parameters.avoidance -= current_to_other * separation_weight;
+        flocking_parameters.avoid_count += 1;
+    }
+
+    // Aligment
+    let alignment_weight = kernel_weight(simulation_parameters.alignment_kernel, normalized_distance) * interaction.alignment;
+    flocking_parameters.avg_velocity += other_velocity * alignment_weight;
+    flocking_parameters.alignment_weight += alignment_weight;
+
+    // Cohesion
+    let cohesion_weight = kernel_weight(simulation_parameters.cohesion_kernel, normalized_distance) * interaction.cohesion;
+    flocking_parameters.avg_position += (current_position + current_to_other) * cohesion_weight;
+    flocking_parameters.cohesion_weight += cohesion_weight;
+
+    flocking_parameters.neighbor_count += 1;
+}
+
+// Mirror of the neighbor list of topologicalNeighbors.wgsl, indices of the k nearest visible boids sorted by distance
+pub fn topological_neighbors(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    positions: &[BoidsPosition],
+    index: usize,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+) -> Vec<usize> {
+    let k = simulation_parameters.topological_neighbor_count.clamp(1, MAX_TOPOLOGICAL_NEIGHBORS) as usize;
+    let mut neighbors: Vec<(usize, f32)> = Vec::with_capacity(k + 1);
+
+    for (other_index, other_position) in positions.iter().enumerate() {
+        // skip self
+        if other_index == index {
+            continue;
+        }
+        let current_to_other = position_offset(simulation_parameters, current_position, other_position);
+        if !is_visible(simulation_parameters, &current_to_other, current_velocity) {
+            continue;
+        }
+
+        let sqrt_distance = current_to_other.dot(&current_to_other);
+        if neighbors.len() == k && sqrt_distance >= neighbors[k - 1].1 {
+            continue;
+        }
+        // After the ones as close so the first found wins on ties
+        let insert_at = neighbors.partition_point(|(_, neighbor_sqrt_distance)| *neighbor_sqrt_distance <= sqrt_distance);
+        neighbors.insert(insert_at, (other_index, sqrt_distance));
+        neighbors.truncate(k);
+    }
+
+    neighbors.into_iter().map(|(other_index, _)| other_index).collect()
+}
+
+pub fn flocking_post_accumulation(flocking_parameters: &mut FlockingParameters) {
+    if flocking_parameters.cohesion_weight > 0.0 {
+        flocking_parameters.avg_position /= flocking_parameters.cohesion_weight;
+    }
+    if flocking_parameters.alignment_weight > 0.0 {
+        flocking_parameters.avg_velocity /= flocking_parameters.alignment_weight;
+    }
+}
+
+pub fn limit_length(v: BoidsVelocity, max_length: f32) -> BoidsVelocity {
+    let v_length = v.norm();
+    if v_length > max_length {
+        return v / v_length * max_length;
+    }
+    v
+}
+
+// Mirror of steerTowards in motion.wgsl
+pub fn steer_towards(direction: &BoidsVelocity, current_velocity: &BoidsVelocity, max_speed: f32, max_steering_strength: f32) -> BoidsVelocity {
+    let desired_velocity = direction.normalize() * max_speed;
+    limit_length(desired_velocity - current_velocity, max_steering_strength)
+}
+
+// Mirror of ruleAcceleration in flocking.wgsl
+pub fn rule_acceleration(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    direction: &BoidsVelocity,
+    current_velocity: &BoidsVelocity,
+    max_speed: f32,
+) -> BoidsVelocity {
+    match SteeringModel::from_u32(simulation_parameters.steering_model) {
+        SteeringModel::Reynolds => steer_towards(direction, current_velocity, max_speed, simulation_parameters.max_steering_strength),
+        SteeringModel::Additive => direction.normalize(),
+    }
+}
+
+pub fn compute_new_velocity(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    obstacles: &ObstaclesBufferContents,
+    species_settings: &SpeciesSettings,
+    predators: &[PredatorBufferContent],
+    pointer_force: &PointerForceUniformBufferContent,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+    current_species: BoidsSpeciesId,
+    flocking_parameters: &FlockingParameters,
+) -> BoidsVelocity {
+    let current_species_parameters = &species_settings.species[current_species as usize];
+    let max_speed = simulation_parameters.max_speed * current_species_parameters.speed_factor;
+    let min_speed = simulation_parameters.min_speed * current_species_parameters.speed_factor;
+
+    let mut acceleration = BoidsVelocity::zeros();
+
+    let flee = predator_flee(simulation_parameters, predators, current_position);
+    if flee != BoidsVelocity::zeros() {
+        // Alarmed boids only flee
+        acceleration += rule_acceleration(simulation_parameters, &flee, current_velocity, max_speed) * simulation_parameters.predator_flee_strength;
+    } else {
+        // A rule only applies with neighbors of non zero weight
+        if flocking_parameters.alignment_weight > 0.0 {
+            acceleration += rule_acceleration(simulation_parameters, &flocking_parameters.avg_velocity, current_velocity, max_speed)
+                * simulation_parameters.aligment_scale
+                * current_species_parameters.alignment_factor;
+        }
+        if flocking_parameters.cohesion_weight > 0.0 {
+            let cohesion_direction = flocking_parameters.avg_position - current_position;
+            acceleration += rule_acceleration(simulation_parameters, &cohesion_direction, current_velocity, max_speed)
+                * simulation_parameters.cohesion_scale
+                * current_species_parameters.cohesion_factor;
+        }
+        if flocking_parameters.avoid_count > 0 {
+            acceleration += rule_acceleration(simulation_parameters, &flocking_parameters.avoidance, current_velocity, max_speed)
+                * simulation_parameters.separation_scale
+                * current_species_parameters.separation_factor;
+        }
+    }
+
+    acceleration += environment_acceleration(simulation_parameters, obstacles, pointer_force, current_position, current_velocity);
+
+    limit_speed(&(current_velocity + acceleration * simulation_parameters.delta_time), min_speed, max_speed)
+}
+
+// Mirror of environmentAcceleration in boidsCommon.wgsl
+pub fn environment_acceleration(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    obstacles: &ObstaclesBufferContents,
+    pointer_force: &PointerForceUniformBufferContent,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+) -> BoidsVelocity {
+    let mut acceleration = BoidsVelocity::zeros();
+
+    if BoundaryMode::from_u32(simulation_parameters.boundary_mode) == BoundaryMode::SoftRepulsion {
+        acceleration += edge_repulsion(
+            current_position,
+            current_velocity,
+            simulation_parameters.repulsion_margin / 2.0,
+            simulation_parameters.repulsion_strength,
+        );
+    }
+
+    acceleration += obstacle_avoidance(simulation_parameters, obstacles, current_position);
+    acceleration += pointer_force_acceleration(pointer_force, current_position);
+    acceleration
+}
+
+pub fn limit_speed(velocity: &BoidsVelocity, min_speed: f32, max_speed: f32) -> BoidsVelocity {
+    let speed = velocity.norm();
+    if speed > 0.0 {
+        // WGSL clamp is min(max(e, low), high), f32::clamp would panic if min_speed > max_speed
+        return (velocity / speed) * speed.max(min_speed).min(max_speed);
+    }
+    *velocity
+}
+
+fn normalize_or(v: &BoidsVelocity, fallback: &BoidsVelocity) -> BoidsVelocity {
+    let length = v.norm();
+    if length > 0.0 {
+        return v / length;
+    }
+    *fallback
+}
+
+fn random_perpendicular(direction: &BoidsVelocity, random_state: &mut BoidsRandomState) -> BoidsVelocity {
+    let random = random_vec(random_state);
+    normalize_or(&(random - direction * random.dot(direction)), &BoidsVelocity::zeros())
+}
+
+fn boid_heading(current_velocity: &BoidsVelocity) -> BoidsVelocity { normalize_or(current_velocity, &BoidsVelocity::new(1.0, 0.0)) }
+
+// Mirror of couzin.wgsl
+#[derive(Default)]
+pub struct CouzinZones {
+    pub repulsion: BoidsVelocity,
+    pub orientation: BoidsVelocity,
+    pub attraction: BoidsVelocity,
+    pub repulsion_count: u32,
+    pub orientation_count: u32,
+    pub attraction_count: u32,
+}
+
+pub fn couzin_accumulate(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    species_settings: &SpeciesSettings,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+    current_species: BoidsSpeciesId,
+    other_position: &BoidsPosition,
+    other_velocity: &BoidsVelocity,
+    other_species: BoidsSpeciesId,
+    zones: &mut CouzinZones,
+) {
+    let current_to_other = position_offset(simulation_parameters, current_position, other_position);
+    let distance = current_to_other.norm();
+
+    let view_radius = species_view_radius(simulation_parameters, species_settings, current_species);
+    if NeighborhoodMode::from_u32(simulation_parameters.neighborhood_mode) == NeighborhoodMode::Metric && distance > view_radius {
+        return;
+    }
+
+    if distance == 0.0 || !is_visible(simulation_parameters, &current_to_other, current_velocity) {
+        return;
+    }
+
+    let interaction = &species_settings.interactions
+        [(current_species * simulation_parameters.species_count + clamp_species(simulation_parameters, other_species)) as usize];
+    let direction = current_to_other / distance;
+
+    if distance < view_radius * simulation_parameters.couzin_repulsion_radius_factor {
+        if interaction.separation > 0.0 {
+            zones.repulsion -= direction * interaction.separation;
+            zones.repulsion_count += 1;
+        }
+    } else if distance < view_radius * simulation_parameters.couzin_orientation_radius_factor {
+        if interaction.alignment > 0.0 && *other_velocity != BoidsVelocity::zeros() {
+            zones.orientation += other_velocity.normalize() * interaction.alignment;
+            zones.orientation_count += 1;
+        }
+    } else if interaction.cohesion > 0.0 {
+        zones.attraction += direction * interaction.cohesion;
+        zones.attraction_count += 1;
+    }
+}
+
+fn zones_direction(current_direction: &BoidsVelocity, zones: &CouzinZones) -> BoidsVelocity {
+    if zones.repulsion_count > 0 {
+        return normalize_or(&zones.repulsion, current_direction);
+    }
+
+    let orientation = normalize_or(&(zones.orientation + current_direction), current_direction);
+    let attraction = normalize_or(&zones.attraction, current_direction);
+    match (zones.orientation_count > 0, zones.attraction_count > 0) {
+        (true, true) => normalize_or(&(orientation + attraction), current_direction),
+        (true, false) => orientation,
+        (false, true) => attraction,
+        (false, false) => *current_direction,
+    }
+}
+
+fn angular_noise(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    direction: &BoidsVelocity,
+    random_state: &mut BoidsRandomState,
+) -> BoidsVelocity {
+    let u1 = 1.0 - random_float(random_state);
+    let u2 = random_float(random_state);
+    let standard_deviation = simulation_parameters.couzin_noise.to_radians() * simulation_parameters.delta_time.sqrt();
+    let angle = standard_deviation * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos();
+
+    let perpendicular = random_perpendicular(direction, random_state);
+    normalize_or(&(direction * angle.cos() + perpendicular * angle.sin()), direction)
+}
+
+fn turn_towards(
+    current_direction: &BoidsVelocity,
+    desired_direction: &BoidsVelocity,
+    max_angle: f32,
+    random_state: &mut BoidsRandomState,
+) -> BoidsVelocity {
+    let cos_angle = current_direction.dot(desired_direction).clamp(-1.0, 1.0);
+    if cos_angle.acos() <= max_angle {
+        return *desired_direction;
+    }
+
+    let mut perpendicular = normalize_or(&(desired_direction - current_direction * cos_angle), &BoidsVelocity::zeros());
+    if perpendicular == BoidsVelocity::zeros() {
+        perpendicular = random_perpendicular(current_direction, random_state);
+    }
+    normalize_or(&(current_direction * max_angle.cos() + perpendicular * max_angle.sin()), current_direction)
+}
+
+pub fn couzin_new_velocity(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    obstacles: &ObstaclesBufferContents,
+    species_settings: &SpeciesSettings,
+    predators: &[PredatorBufferContent],
+    pointer_force: &PointerForceUniformBufferContent,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+    current_species: BoidsSpeciesId,
+    zones: &CouzinZones,
+    random_state: &mut BoidsRandomState,
+) -> BoidsVelocity {
+    let current_species_parameters = &species_settings.species[current_species as usize];
+    let max_speed = simulation_parameters.max_speed * current_species_parameters.speed_factor;
+    let min_speed = simulation_parameters.min_speed * current_species_parameters.speed_factor;
+
+    let current_direction = boid_heading(current_velocity);
+
+    let mut desired_direction = zones_direction(&current_direction, zones);
+    let flee = predator_flee(simulation_parameters, predators, current_position);
+    if flee != BoidsVelocity::zeros() {
+        desired_direction = flee.normalize();
+    }
+
+    desired_direction = angular_noise(simulation_parameters, &desired_direction, random_state);
+    let max_angle = simulation_parameters.couzin_turning_rate.to_radians() * simulation_parameters.delta_time;
+    let direction = turn_towards(&current_direction, &desired_direction, max_angle, random_state);
+
+    let velocity = direction * max_speed
+        + environment_acceleration(simulation_parameters, obstacles, pointer_force, current_position, current_velocity) * simulation_parameters.delta_time;
+    limit_speed(&velocity, min_speed, max_speed)
+}
+
+// Mirror of vicsek.wgsl, the sum of the neighbors headings
+pub fn vicsek_accumulate(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    species_settings: &SpeciesSettings,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+    current_species: BoidsSpeciesId,
+    other_position: &BoidsPosition,
+    other_velocity: &BoidsVelocity,
+    other_species: BoidsSpeciesId,
+    heading: &mut BoidsVelocity,
+) {
+    let current_to_other = position_offset(simulation_parameters, current_position, other_position);
+    let sqrt_distance = current_to_other.dot(&current_to_other);
+
+    let view_radius = species_view_radius(simulation_parameters, species_settings, current_species);
+    if NeighborhoodMode::from_u32(simulation_parameters.neighborhood_mode) == NeighborhoodMode::Metric && sqrt_distance > view_radius * view_radius {
+        return;
+    }
+
+    if !is_visible(simulation_parameters, &current_to_other, current_velocity) {
+        return;
+    }
+
+    let interaction = &species_settings.interactions
+        [(current_species * simulation_parameters.species_count + clamp_species(simulation_parameters, other_species)) as usize];
+    if interaction.alignment > 0.0 && *other_velocity != BoidsVelocity::zeros() {
+        *heading += other_velocity.normalize() * interaction.alignment;
+    }
+}
+
+pub fn vicsek_new_velocity(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    obstacles: &ObstaclesBufferContents,
+    species_settings: &SpeciesSettings,
+    predators: &[PredatorBufferContent],
+    pointer_force: &PointerForceUniformBufferContent,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+    current_species: BoidsSpeciesId,
+    heading: &BoidsVelocity,
+    random_state: &mut BoidsRandomState,
+) -> BoidsVelocity {
+    let current_species_parameters = &species_settings.species[current_species as usize];
+    let max_speed = simulation_parameters.max_speed * current_species_parameters.speed_factor;
+
+    let current_direction = boid_heading(current_velocity);
+
+    let mut direction = normalize_or(&(heading + current_direction), &current_direction);
+    let flee = predator_flee(simulation_parameters, predators, current_position);
+    if flee != BoidsVelocity::zeros() {
+        direction = flee.normalize();
+    }
+
+    let angle = (random_float(random_state) - 0.5) * simulation_parameters.vicsek_noise;
+    let perpendicular = random_perpendicular(&direction, random_state);
+    direction = normalize_or(&(direction * angle.cos() + perpendicular * angle.sin()), &direction);
+
+    // Constant speed, the environment only turns the boid
+    let velocity = direction * max_speed
+        + environment_acceleration(simulation_parameters, obstacles, pointer_force, current_position, current_velocity) * simulation_parameters.delta_time;
+    normalize_or(&velocity, &direction) * max_speed
+}
+
+pub fn compute_new_position(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    current_position: &BoidsPosition,
+    current_velocity: &BoidsVelocity,
+) -> BoidsPosition {
+    current_position + current_velocity * simulation_parameters.delta_time
+}
+
+// Mirror of positionOffset in motion.wgsl (WGSL round is half to even)
+pub fn position_offset(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    current_position: &BoidsPosition,
+    other_position: &BoidsPosition,
+) -> BoidsVelocity {
+    let offset = other_position - current_position;
+    if BoundaryMode::from_u32(simulation_parameters.boundary_mode) == BoundaryMode::Toroidal {
+        return offset - offset.map(f32::round_ties_even);
+    }
+    offset
+}
+
+// Mirror of applyBoundary in motion.wgsl
+pub fn apply_boundary(simulation_parameters: &SimulationParametersUniformBufferContent, position: &mut BoidsPosition, velocity: &mut BoidsVelocity) {
+    match BoundaryMode::from_u32(simulation_parameters.boundary_mode) {
+        BoundaryMode::SoftRepulsion => {},
+        BoundaryMode::Toroidal => *position -= position.map(f32::floor),
+        BoundaryMode::Reflect => {
+            for axis in 0..2 {
+                if position[axis] < 0.0 {
+                    position[axis] = -position[axis];
+                    velocity[axis] = velocity[axis].abs();
+                } else if position[axis] > 1.0 {
+                    position[axis] = 2.0 - position[axis];
+                    velocity[axis] = -velocity[axis].abs();
+                }
+            }
+        },
+    }
+}
+
+pub fn edge_repulsion(current_position: &BoidsPosition, _current_velocity: &BoidsVelocity, repulsion_margin: f32, repulsion_strength: f32) -> BoidsVelocity {
+    let mut edge_repulsion_force = BoidsVelocity::zeros();
+    if current_position.x < repulsion_margin {
+        edge_repulsion_force.x += repulsion_margin - current_position.x;
+    } else if current_position.x > 1.0 - repulsion_margin {
+        edge_repulsion_force.x = (1.0 - repulsion_margin) - current_position.x;
+    }
+
+    if current_position.y < repulsion_margin {
+        edge_repulsion_force.y = repulsion_margin - current_position.y;
+    } else if current_position.y > 1.0 - repulsion_margin {
+        edge_repulsion_force.y = (1.0 - repulsion_margin) - current_position.y;
+    }
+
+    repulsion_strength * edge_repulsion_force
+}
+
+// Mirror of polygonDistance in obstacles.wgsl
+fn polygon_distance(p: &nalgebra_glm::Vec2, vertices: &[nalgebra_glm::Vec2]) -> f32 {
+    let mut d = (p - vertices[0]).norm_squared();
+    let mut s = 1.0;
+    let mut j = vertices.len() - 1;
+    for i in 0..vertices.len() {
+        let e = vertices[j] - vertices[i];
+        let w = p - vertices[i];
+        let b = w - e * (w.dot(&e) / e.dot(&e)).clamp(0.0, 1.0);
+        d = d.min(b.norm_squared());
+        let c = [p.y >= vertices[i].y, p.y < vertices[j].y, e.x * w.y > e.y * w.x];
+        if c.iter().all(|c| *c) || c.iter().all(|c| !*c) {
+            s = -s;
+        }
+        j = i;
+    }
+    s * d.sqrt()
+}
+
+// Mirror of obstacleDistance in obstacles.wgsl
+pub fn obstacle_distance(obstacle: &ObstacleBufferContent, vertices: &[nalgebra_glm::Vec2], p: &nalgebra_glm::Vec2) -> f32 {
+    match ObstacleKind::from_u32(obstacle.kind) {
+        ObstacleKind::Circle => (p - obstacle.center).norm() - obstacle.radius,
+        ObstacleKind::Box => {
+            let q = (p - obstacle.center).abs() - obstacle.half_extents;
+            q.sup(&nalgebra_glm::Vec2::zeros()).norm() + q.x.max(q.y).min(0.0)
+        },
+        ObstacleKind::Polygon => {
+            let first_vertex = obstacle.first_vertex as usize;
+            polygon_distance(p, &vertices[first_vertex..first_vertex + obstacle.vertex_count as usize])
+        },
+    }
+}
+
+// Mirror of obstacleAvoidance in boidsCommon.wgsl
+pub fn obstacle_avoidance(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    obstacles: &ObstaclesBufferContents,
+    current_position: &BoidsPosition,
+) -> BoidsVelocity {
+    let distance = |obstacle: &ObstacleBufferContent, p: &nalgebra_glm::Vec2| obstacle_distance(obstacle, &obstacles.vertices, p);
+
+    let Some((nearest, nearest_distance)) = obstacles.obstacles[..simulation_parameters.obstacle_count as usize]
+        .iter()
+        .map(|obstacle| (obstacle, distance(obstacle, current_position)))
+        // First nearest obstacle on ties like the shader
+        .reduce(|nearest, other| if other.1 < nearest.1 { other } else { nearest })
+    else {
+        return BoidsVelocity::zeros();
+    };
+
+    let margin = simulation_parameters.obstacle_margin;
+    if nearest_distance > margin {
+        return BoidsVelocity::zeros();
+    }
+
+    let e_x = nalgebra_glm::vec2(OBSTACLE_GRADIENT_STEP, 0.0);
+    let e_y = nalgebra_glm::vec2(0.0, OBSTACLE_GRADIENT_STEP);
+    let gradient = nalgebra_glm::vec2(
+        distance(nearest, &(current_position + e_x)) - distance(nearest, &(current_position - e_x)),
+        distance(nearest, &(current_position + e_y)) - distance(nearest, &(current_position - e_y)),
+    );
+    if gradient == nalgebra_glm::Vec2::zeros() {
+        return BoidsVelocity::zeros();
+    }
+
+    gradient.normalize() * simulation_parameters.obstacle_strength * (1.0 - nearest_distance / margin.max(0.0001))
+}
+
+// Mirror of pointerForce in boidsCommon.wgsl
+pub fn pointer_force_acceleration(pointer_force: &PointerForceUniformBufferContent, current_position: &BoidsPosition) -> BoidsVelocity {
+    let mode = PointerForceMode::from_u32(pointer_force.mode);
+    if mode == PointerForceMode::None {
+        return BoidsVelocity::zeros();
+    }
+
+    let offset = pointer_force.position - current_position;
+    let distance = offset.norm();
+    if distance > pointer_force.radius || distance == 0.0 {
+        return BoidsVelocity::zeros();
+    }
+
+    let force = offset / distance * pointer_force.strength * (1.0 - distance / pointer_force.radius);
+    if mode == PointerForceMode::Repulse {
+        return -force;
+    }
+    force
+}
+
+// Mirror of predatorFlee in boidsCommon.wgsl
+pub fn predator_flee(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    predators: &[PredatorBufferContent],
+    current_position: &BoidsPosition,
+) -> BoidsVelocity {
+    let alarm_radius = simulation_parameters.predator_alarm_radius;
+    let mut flee = BoidsVelocity::zeros();
+    for predator in &predators[..simulation_parameters.predator_count as usize] {
+        let offset = position_offset(simulation_parameters, current_position, &predator.position.xy());
+        let distance = offset.norm();
+        if distance < alarm_radius && distance > 0.0 {
+            flee -= offset / distance * (1.0 - distance / alarm_radius);
+        }
+    }
+    flee
+}
+
+// Mirror of caughtByPredator in boidsCommon.wgsl
+pub fn caught_by_predator(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    predators: &[PredatorBufferContent],
+    position: &BoidsPosition,
+) -> bool {
+    if simulation_parameters.predator_catch == 0 {
+        return false;
+    }
+
+    let sqrt_catch_radius = simulation_parameters.predator_catch_radius * simulation_parameters.predator_catch_radius;
+    predators[..simulation_parameters.predator_count as usize].iter().any(|predator| {
+        let offset = position_offset(simulation_parameters, position, &predator.position.xy());
+        offset.dot(&offset) < sqrt_catch_radius
+    })
+}
+
+// Mirror of respawnPosition in boidsCommon.wgsl
+pub fn respawn_position(random_state: &mut BoidsRandomState) -> BoidsPosition { (random_vec(random_state) * 0.5).add_scalar(0.5) }
+
+// Mirror of predators.wgsl
+pub fn predator_step(
+    simulation_parameters: &SimulationParametersUniformBufferContent,
+    positions: &[BoidsPosition],
+    predator: &PredatorBufferContent,
+) -> PredatorBufferContent {
+    let current_position = predator.position.xy();
+    let current_velocity = predator.velocity.xy();
+    let max_speed = simulation_parameters.predator_speed;
+
+    // Chase the nearest boid in view, first one on ties
+    let mut nearest_offset = BoidsVelocity::zeros();
+    let mut nearest_sqrt_distance = simulation_parameters.predator_view_radius * simulation_parameters.predator_view_radius;
+    for position in positions {
+        let offset = position_offset(simulation_parameters, &current_position, position);
+        let sqrt_distance = offset.dot(&offset);
+        if sqrt_distance < nearest_sqrt_distance {
+            nearest_offset = offset;
+            nearest_sqrt_distance = sqrt_distance;
+        }
+    }
+
+    let mut acceleration = BoidsVelocity::zeros();
+    if nearest_offset != BoidsVelocity::zeros() {
+        acceleration += steer_towards(&nearest_offset, &current_velocity, max_speed, simulation_parameters.predator_steering_strength);
+    }
+
+    if BoundaryMode::from_u32(simulation_parameters.boundary_mode) == BoundaryMode::SoftRepulsion {
+        acceleration += edge_repulsion(
+            &current_position,
+            &current_velocity,
+            simulation_parameters.repulsion_margin / 2.0,
+            simulation_parameters.repulsion_strength,
+        );
+    }
+
+    // Predators always cruise at their speed
+    let mut new_velocity = current_velocity + acceleration * simulation_parameters.delta_time;
+    let speed = new_velocity.norm();
+    if speed > 0.0 {
+        new_velocity = new_velocity / speed * max_speed;
+    }
+
+    let mut new_position = compute_new_position(simulation_parameters, &current_position, &new_velocity);
+    apply_boundary(simulation_parameters, &mut new_position, &mut new_velocity);
+
+    PredatorBufferContent {
+        position: nalgebra_glm::vec4(new_position.x, new_position.y, 0.0, 0.0),
+        velocity: nalgebra_glm::vec4(new_velocity.x, new_velocity.y, 0.0, 0.0),
+    }
+}
+
+pub struct CpuFlock {
+    pub positions: Vec<BoidsPosition>,
+    pub velocities: Vec<BoidsVelocity>,
+    pub species: Vec<BoidsSpeciesId>,
+    pub random_states: Vec<BoidsRandomState>,
+    // Boids never move between slots here but restored states may come from sorted GPU buffers
+    pub ids: Vec<BoidId>,
+    // Every predator of the GPU buffer, only the first predator_count ones move
+    pub predators: Vec<PredatorBufferContent>,
+}
+
+// Mirror of pickSpecies in init.wgsl
+pub fn pick_species(species: &[SpeciesBufferContent], r: f32) -> BoidsSpeciesId {
+    let total: f32 = species.iter().map(|species| species.proportion).sum();
+
+    let mut threshold = r * total;
+    for (index, species) in species[..species.len() - 1].iter().enumerate() {
+        threshold -= species.proportion;
+        if threshold < 0.0 {
+            return index as BoidsSpeciesId;
+        }
+    }
+    (species.len() - 1) as BoidsSpeciesId
+}
+
+// Mirror of gaussianVecN in init.wgsl
+fn gaussian_vec(state: &mut BoidsRandomState) -> BoidsPosition {
+    let mut gaussian = || {
+        let u1 = 1.0 - random_float(state);
+        let u2 = random_float(state);
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+    };
+    let x = gaussian();
+    let y = gaussian();
+    BoidsPosition::new(x, y)
+}
+
+fn plane_direction(angle: f32) -> BoidsVelocity { BoidsVelocity::new(angle.cos(), angle.sin()) }
+
+// Smallest lattice side with side² >= total
+fn lattice_side(total: u32) -> u32 {
+    let side = ((total as f32).sqrt().round() as u32).max(1);
+    if side * side < total {
+        side + 1
+    } else {
+        side
+    }
+}
+
+// Same position, velocity, species and random state as init.wgsl for the boid at the given index among total boids
+fn init_boid(
+    index: u32,
+    total: u32,
+    init_parameters: &InitParametersUniformBufferContent,
+    species: &[SpeciesBufferContent],
+) -> (BoidsPosition, BoidsVelocity, BoidsSpeciesId, BoidsRandomState) {
+    let alterated_index = index.wrapping_mul(142857).wrapping_add(init_parameters.seed);
+    let mut position = BoidsPosition::new(hash1(alterated_index), hash1(alterated_index.wrapping_add(1)));
+    let mut direction = (BoidsVelocity::new(hash1(alterated_index.wrapping_add(2)), hash1(alterated_index.wrapping_add(3))) * 2.0)
+        .add_scalar(-1.0)
+        .normalize();
+    let mut random = pcg_hash(alterated_index);
+
+    match InitMode::from_u32(init_parameters.init_mode) {
+        InitMode::Uniform => {},
+        InitMode::GaussianClusters => {
+            let cluster_count = init_parameters.cluster_count.max(1);
+            let cluster = ((random_float(&mut random) * cluster_count as f32) as u32).min(cluster_count - 1);
+            let center_index = pcg_hash(init_parameters.seed).wrapping_add(cluster * 2);
+            let center = BoidsPosition::new(hash1(center_index), hash1(center_index.wrapping_add(1))) * 0.6;
+            position = center.add_scalar(0.2) + gaussian_vec(&mut random) * init_parameters.cluster_sigma;
+        },
+        InitMode::Ring => {
+            let angle = 2.0 * std::f32::consts::PI * random_float(&mut random);
+            let radius = init_parameters.ring_radius + (random_float(&mut random) - 0.5) * init_parameters.ring_width;
+            position = (plane_direction(angle) * radius).add_scalar(0.5);
+            direction = plane_direction(angle + 0.5 * std::f32::consts::PI);
+        },
+        InitMode::Aligned => direction = plane_direction(init_parameters.heading.to_radians()),
+        InitMode::Lattice => {
+            let side = lattice_side(total);
+            position = BoidsPosition::new(((index % side) as f32 + 0.5) / side as f32, ((index / side % side) as f32 + 0.5) / side as f32);
+        },
+        InitMode::PolarizedDisc => {
+            let offset_direction = normalize_or(&gaussian_vec(&mut random), &plane_direction(0.0));
+            let radius = init_parameters.disc_radius * random_float(&mut random).powf(0.5);
+            position = (offset_direction * radius).add_scalar(0.5);
+            if random_float(&mut random) < init_parameters.polarization {
+                direction = plane_direction(init_parameters.heading.to_radians());
+            }
+        },
+    }
+
+    (
+        position.map(|x| x.clamp(0.0, 1.0)),
+        direction * init_parameters.speed,
+        pick_species(species, hash1(alterated_index.wrapping_add(4))),
+        pcg_hash(index ^ pcg_hash(init_parameters.seed)),
+    )
+}
+
+fn retain_kept<T>(values: &mut Vec<T>, kept: &[bool]) {
+    let mut kept = kept.iter();
+    values.retain(|_| *kept.next().unwrap());
+}
+
+impl CpuFlock {
+    pub fn new(boids_count: u32, init_parameters: &InitParametersUniformBufferContent, species: &[SpeciesBufferContent]) -> Self {
+        let mut flock = Self {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            species: Vec::new(),
+            random_states: Vec::new(),
+            ids: Vec::new(),
+            predators: init_predators(Dimension::Two, init_parameters.seed),
+        };
+        flock.resize(boids_count, init_parameters, species);
+        flock
+    }
+
+    // Keep the boids whose id is below the new count and seed the new ones like the GPU strategy does, so ids stay 0..count
+    pub fn resize(&mut self, total: u32, init_parameters: &InitParametersUniformBufferContent, species: &[SpeciesBufferContent]) {
+        let kept: Vec<bool> = self.ids.iter().map(|id| *id < total).collect();
+        retain_kept(&mut self.positions, &kept);
+        retain_kept(&mut self.velocities, &kept);
+        retain_kept(&mut self.species, &kept);
+        retain_kept(&mut self.random_states, &kept);
+        retain_kept(&mut self.ids, &kept);
+
+        for index in self.positions.len() as u32..total {
+            let (position, velocity, species, random_state) = init_boid(index, total, init_parameters, species);
+            self.positions.push(position);
+            self.velocities.push(velocity);
+            self.species.push(species);
+            self.random_states.push(random_state);
+            self.ids.push(index);
+        }
+    }
+
+    pub fn boids_state(&self) -> BoidsState {
+        BoidsState {
+            positions: self.positions.iter().map(|position| nalgebra_glm::vec4(position.x, position.y, 0.0, 0.0)).collect(),
+            velocities: self.velocities.iter().map(|velocity| nalgebra_glm::vec4(velocity.x, velocity.y, 0.0, 0.0)).collect(),
+            species: self.species.clone(),
+            random_states: self.random_states.clone(),
+            ids: self.ids.clone(),
+        }
+    }
+
+    // Predators are kept
+    pub fn set_boids_state(&mut self, state: &BoidsState) {
+        self.positions = state.positions.iter().map(|position| position.xy()).collect();
+        self.velocities = state.velocities.iter().map(|velocity| velocity.xy()).collect();
+        self.species = state.species.clone();
+        self.random_states = state.random_states.clone();
+        self.ids = state.ids.clone();
+    }
+
+    // One step of computeNative.wgsl, computeGrid.wgsl gives the same result as it only skips boids out of view radius
+    // or, for topological neighbors, boids farther than the k nearest ones (up to ties between equally distant boids)
+    // and boids beyond topological_max_ring rings of cells, which only matters when fewer than k boids are within them
+    pub fn step(
+        &mut self,
+        rule_set: RuleSet,
+        simulation_parameters: &SimulationParametersUniformBufferContent,
+        obstacles: &ObstaclesBufferContents,
+        species_settings: &SpeciesSettings,
+        pointer_force: &PointerForceUniformBufferContent,
+    ) {
+        // Predators move first, looking at the boids before the step
+        let predator_count = simulation_parameters.predator_count as usize;
+        for predator in self.predators[..predator_count].iter_mut() {
+            *predator = predator_step(simulation_parameters, &self.positions, predator);
+        }
+
+        let ((positions, velocities), random_states): ((Vec<_>, Vec<_>), Vec<_>) = self
+            .positions
+            .iter()
+            .zip(self.velocities.iter())
+            .zip(self.species.iter())
+            .zip(self.random_states.iter())
+            .enumerate()
+            .map(|(index, (((current_position, current_velocity), current_species), random_state))| {
+                let current_species = clamp_species(simulation_parameters, *current_species);
+                let mut random_state = *random_state;
+
+                let neighbors = if NeighborhoodMode::from_u32(simulation_parameters.neighborhood_mode) == NeighborhoodMode::Topological {
+                    topological_neighbors(simulation_parameters, &self.positions, index, current_position, current_velocity)
+                } else {
+                    // skip self
+                    (0..self.positions.len()).filter(|other_index| *other_index != index).collect()
+                };
+
+                let mut new_velocity = match rule_set {
+                    RuleSet::Reynolds => {
+                        let mut flocking_parameters = flocking_init();
+                        for other_index in neighbors {
+                            flocking_accumulate(
+                                simulation_parameters,
+                                species_settings,
+                                current_position,
+                                current_velocity,
+                                current_species,
+                                &self.positions[other_index],
+                                &self.velocities[other_index],
+                                self.species[other_index],
+                                &mut flocking_parameters,
+                            );
+                        }
+                        flocking_post_accumulation(&mut flocking_parameters);
+
+                        compute_new_velocity(
+                            simulation_parameters,
+                            obstacles,
+                            species_settings,
+                            &self.predators,
+                            pointer_force,
+                            current_position,
+                            current_velocity,
+                            current_species,
+                            &flocking_parameters,
+                        )
+                    },
+                    RuleSet::Couzin => {
+                        let mut zones = CouzinZones::default();
+                        for other_index in neighbors {
+                            couzin_accumulate(
+                                simulation_parameters,
+                                species_settings,
+                                current_position,
+                                current_velocity,
+                                current_species,
+                                &self.positions[other_index],
+                                &self.velocities[other_index],
+                                self.species[other_index],
+                                &mut zones,
+                            );
+                        }
+
+                        couzin_new_velocity(
+                            simulation_parameters,
+                            obstacles,
+                            species_settings,
+                            &self.predators,
+                            pointer_force,
+                            current_position,
+                            current_velocity,
+                            current_species,
+                            &zones,
+                            &mut random_state,
+                        )
+                    },
+                    RuleSet::Vicsek => {
+                        let mut heading = BoidsVelocity::zeros();
+                        for other_index in neighbors {
+                            vicsek_accumulate(
+                                simulation_parameters,
+                                species_settings,
+                                current_position,
+                                current_velocity,
+                                current_species,
+                                &self.positions[other_index],
+                                &self.velocities[other_index],
+                                self.species[other_index],
+                                &mut heading,
+                            );
+                        }
+
+                        vicsek_new_velocity(
+                            simulation_parameters,
+                            obstacles,
+                            species_settings,
+                            &self.predators,
+                            pointer_force,
+                            current_position,
+                            current_velocity,
+                            current_species,
+                            &heading,
+                            &mut random_state,
+                        )
+                    },
+                };
+                let mut new_position = compute_new_position(simulation_parameters, current_position, &new_velocity);
+                apply_boundary(simulation_parameters, &mut new_position, &mut new_velocity);
+                if caught_by_predator(simulation_parameters, &self.predators, &new_position) {
+                    new_position = respawn_position(&mut random_state);
+                }
+                ((new_position, new_velocity), random_state)
+            })
+            .unzip();
+
+        self.positions = positions;
+        self.velocities = velocities;
+        self.random_states = random_states;
+        // Species are written back clamped like the shaders do
+        for species in self.species.iter_mut() {
+            *species = clamp_species(simulation_parameters, *species);
+        }
+    }
+}
+
+struct CpuReferenceStrategy {
+    render_pipeline: wgpu::RenderPipeline,
+    obstacles_buffers: ObstaclesBuffers,
+    obstacles_renderer: ObstaclesRenderer,
+    species_buffers: SpeciesBuffers,
+    predators_buffers: PredatorsBuffers,
+    predators_renderer: PredatorsRenderer,
+
+    flock: CpuFlock,
+    rule_set: RuleSet,
+
+    // Only the ping buffers are used to upload the CPU state for display
+    boids_bind_group_layouts: BoidsBindGroupLayouts,
+    boids_buffers: BoidsBuffers,
+    sorting_id_buffer: wgpu::Buffer,
+}
+
+impl SimulationStrategy for CpuReferenceStrategy {
+    fn simulate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        _simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+        need_init: &mut bool,
+        steps: u32,
+        _use_spatial_partitioning: bool,
+        obstacles: &Obstacles,
+        species_settings: &SpeciesSettings,
+        pointer_force: &PointerForceUniformBufferContent,
+    ) {
+        let boids_count = simulation_parameters_uniform_buffer.content().boids_count;
+        if boids_count != self.boids_buffers.boids_count {
+            self.flock.resize(boids_count, init_parameters_uniform_buffer.content(), &species_settings.species);
+            self.boids_buffers = BoidsBuffers::new(device, boids_count, &self.boids_bind_group_layouts);
+            self.sorting_id_buffer = create_sorting_id_buffer(device, boids_count);
+        }
+
+        let obstacles_buffer_contents = obstacles.buffer_contents();
+        self.obstacles_buffers.update(queue, &obstacles_buffer_contents);
+        self.species_buffers.update(queue, species_settings);
+
+        if *need_init {
+            self.flock = CpuFlock::new(boids_count, init_parameters_uniform_buffer.content(), &species_settings.species);
+            *need_init = false;
+        } else {
+            for _ in 0..steps {
+                self.flock.step(self.rule_set, simulation_parameters_uniform_buffer.content(), &obstacles_buffer_contents, species_settings, pointer_force);
+            }
+        }
+
+        let [position_buffer, velocity_buffer, _, species_id_buffer, _, id_buffer] = &self.boids_buffers.ping_buffers;
+        queue.write_buffer(position_buffer, 0, bytemuck::cast_slice(&self.flock.positions));
+        queue.write_buffer(velocity_buffer, 0, bytemuck::cast_slice(&self.flock.velocities));
+        queue.write_buffer(species_id_buffer, 0, bytemuck::cast_slice(&self.flock.species));
+        queue.write_buffer(id_buffer, 0, bytemuck::cast_slice(&self.flock.ids));
+        self.predators_buffers.update(queue, &self.flock.predators);
+    }
+
+    fn render(
+        &mut self,
+        _app_state: &mut oxyde::AppState,
+        _output_view: &wgpu::TextureView,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        vertices_buffer: &wgpu::Buffer,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let boids_count = self.boids_buffers.boids_count;
+
+        let mut display_encoder: wgpu::CommandEncoder = _app_state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Boids Display Encoder") });
+
+        {
+            let mut scope = simulation_profiler.scope("Render Boids", &mut display_encoder, &_app_state.device);
+            let screen_render_pass = &mut scope.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: _output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                ..Default::default()
+            });
+            oxyde::fit_viewport_to_gui_available_rect(screen_render_pass, _app_state);
+
+            self.obstacles_renderer.draw(screen_render_pass, simulation_parameters_uniform_buffer);
+
+            screen_render_pass.set_pipeline(&self.render_pipeline);
+            screen_render_pass.set_vertex_buffer(0, vertices_buffer.slice(..));
+            screen_render_pass.set_vertex_buffer(1, self.sorting_id_buffer.slice(..));
+            screen_render_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
+            screen_render_pass.set_bind_group(1, &self.boids_buffers.ping_bind_group, &[]);
+            screen_render_pass.set_bind_group(2, &self.species_buffers.display_bind_group, &[]);
+            screen_render_pass.draw(0..3, 0..boids_count);
+
+            self.predators_renderer.draw(screen_render_pass, simulation_parameters_uniform_buffer);
+        }
+
+        simulation_profiler.resolve_queries(&mut display_encoder);
+        _app_state.queue.submit(Some(display_encoder.finish()));
+
+        Ok(())
+    }
+
+    fn set_rule_set(&mut self, _device: &wgpu::Device, rule_set: RuleSet) { self.rule_set = rule_set; }
+
+    fn set_boids_state(&mut self, device: &wgpu::Device, _queue: &wgpu::Queue, state: &BoidsState) {
+        let boids_count = state.boids_count();
+        if boids_count != self.boids_buffers.boids_count {
+            self.boids_buffers = BoidsBuffers::new(device, boids_count, &self.boids_bind_group_layouts);
+            self.sorting_id_buffer = create_sorting_id_buffer(device, boids_count);
+        }
+
+        // Buffers are written from the flock on the next render
+        self.flock.set_boids_state(state);
+    }
+
+    fn boids_state(&self, _device: &wgpu::Device, _queue: &wgpu::Queue) -> BoidsState { self.flock.boids_state() }
+
+    // Positions, velocities, species and ids are written to the ping buffers on each render
+    fn current_buffers(&self) -> &[wgpu::Buffer; 6] { &self.boids_buffers.ping_buffers }
+}
+
+pub fn create_cpu_reference_strategy(
+    rule_set: RuleSet,
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
+    simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+) -> Box<dyn SimulationStrategy> {
+    let boids_count = simulation_parameters_uniform_buffer.content().boids_count;
+
+    // Nothing computes on the GPU, the ping pong buffers are only written from the host so the device limits can stay low
+    let boids_bind_group_layouts = BoidsBindGroupLayouts::new(device, Dimension::Two, wgpu::ShaderStages::NONE, wgpu::ShaderStages::VERTEX);
+    let boids_buffers = BoidsBuffers::new(device, boids_count, &boids_bind_group_layouts);
+    let sorting_id_buffer = create_sorting_id_buffer(device, boids_count);
+
+    let display_shader = create_shader_module(device, "Display Shader", include_str!("../../shaders/display.wgsl"), Dimension::Two);
+
+    let species_buffers = SpeciesBuffers::new(device);
+    // Default species until the first render gives the current settings
+    let flock = CpuFlock::new(boids_count, init_parameters_uniform_buffer.content(), &SpeciesSettings::default().species);
+    let predators_buffers = PredatorsBuffers::new(device, &flock.predators);
+    let predators_renderer = PredatorsRenderer::new(device, config, simulation_parameters_uniform_buffer.layout(), &predators_buffers);
+
+    let render_pipeline = create_render_pipeline(
+        device,
+        config,
+        &display_shader,
+        &boids_bind_group_layouts.read_only.layout,
+        simulation_parameters_uniform_buffer.layout(),
+        &species_buffers.display_bind_group_layout_with_desc.layout,
+    );
+
+    let obstacles_buffers = ObstaclesBuffers::new(device);
+    let obstacles_renderer = ObstaclesRenderer::new(device, config, simulation_parameters_uniform_buffer.layout(), &obstacles_buffers);
+
+    Box::new(CpuReferenceStrategy {
+        render_pipeline,
+        obstacles_buffers,
+        obstacles_renderer,
+        species_buffers,
+        predators_buffers,
+        predators_renderer,
+        flock,
+        rule_set,
+        boids_bind_group_layouts,
+        boids_buffers,
+        sorting_id_buffer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-6;
+
+    fn parameters_with_boundary(boundary_mode: BoundaryMode) -> SimulationParametersUniformBufferContent {
+        SimulationParametersUniformBufferContent { boundary_mode: boundary_mode as u32, ..Default::default() }
+    }
+
+    #[test]
+    fn position_offset_wraps_only_in_toroidal_mode() {
+        let current_position = BoidsPosition::new(0.05, 0.5);
+        let other_position = BoidsPosition::new(0.95, 0.5);
+
+        let offset = position_offset(&parameters_with_boundary(BoundaryMode::SoftRepulsion), &current_position, &other_position);
+        assert!((offset - BoidsVelocity::new(0.9, 0.0)).norm() < EPSILON);
+
+        let offset = position_offset(&parameters_with_boundary(BoundaryMode::Toroidal), &current_position, &other_position);
+        assert!((offset - BoidsVelocity::new(-0.1, 0.0)).norm() < EPSILON);
+    }
+
+    #[test]
+    fn apply_boundary_wraps_and_reflects() {
+        let mut position = BoidsPosition::new(1.25, -0.25);
+        let mut velocity = BoidsVelocity::new(0.1, -0.1);
+        apply_boundary(&parameters_with_boundary(BoundaryMode::Toroidal), &mut position, &mut velocity);
+        assert!((position - BoidsPosition::new(0.25, 0.75)).norm() < EPSILON);
+        assert_eq!(velocity, BoidsVelocity::new(0.1, -0.1));
+
+        let mut position = BoidsPosition::new(1.25, -0.25);
+        let mut velocity = BoidsVelocity::new(0.1, -0.1);
+        apply_boundary(&parameters_with_boundary(BoundaryMode::Reflect), &mut position, &mut velocity);
+        assert!((position - BoidsPosition::new(0.75, 0.25)).norm() < EPSILON);
+        assert_eq!(velocity, BoidsVelocity::new(-0.1, 0.1));
+
+        // Soft repulsion steers back the boids instead
+        let mut position = BoidsPosition::new(1.25, -0.25);
+        let mut velocity = BoidsVelocity::new(0.1, -0.1);
+        apply_boundary(&parameters_with_boundary(BoundaryMode::SoftRepulsion), &mut position, &mut velocity);
+        assert_eq!(position, BoidsPosition::new(1.25, -0.25));
+    }
+
+    #[test]
+    fn limit_speed_clamps_the_norm_and_keeps_the_heading() {
+        let slow = limit_speed(&BoidsVelocity::new(0.001, 0.0), 0.01, 0.1);
+        assert!((slow - BoidsVelocity::new(0.01, 0.0)).norm() < EPSILON);
+
+        let fast = limit_speed(&BoidsVelocity::new(0.0, -1.0), 0.01, 0.1);
+        assert!((fast - BoidsVelocity::new(0.0, -0.1)).norm() < EPSILON);
+
+        assert_eq!(limit_speed(&BoidsVelocity::new(0.03, 0.04), 0.01, 0.1), BoidsVelocity::new(0.03, 0.04));
+        assert_eq!(limit_speed(&BoidsVelocity::zeros(), 0.01, 0.1), BoidsVelocity::zeros());
+
+        // Like the WGSL clamp, the max speed wins when it is below the min speed
+        let inverted = limit_speed(&BoidsVelocity::new(1.0, 0.0), 0.5, 0.1);
+        assert!((inverted - BoidsVelocity::new(0.1, 0.0)).norm() < EPSILON);
+    }
+
+    #[test]
+    fn kernel_weight_of_each_kernel() {
+        assert_eq!(kernel_weight(WeightingKernel::Uniform as u32, 0.5), 1.0);
+        assert!((kernel_weight(WeightingKernel::Linear as u32, 0.25) - 0.75).abs() < EPSILON);
+        assert!((kernel_weight(WeightingKernel::InverseSquare as u32, 0.5) - 4.0).abs() < EPSILON);
+        assert!((kernel_weight(WeightingKernel::Gaussian as u32, 0.5) - (-0.5f32).exp()).abs() < EPSILON);
+
+        // Bounded at null distance
+        assert!((kernel_weight(WeightingKernel::InverseSquare as u32, 0.0) - 10000.0).abs() < 1.0);
+        // Unknown kernels are uniform like in the shader
+        assert_eq!(kernel_weight(u32::MAX, 0.5), 1.0);
+    }
+
+    #[test]
+    fn topological_neighbors_are_the_k_nearest_visible_boids() {
+        let simulation_parameters = SimulationParametersUniformBufferContent {
+            topological_neighbor_count: 2,
+            field_of_view: 360.0,
+            ..parameters_with_boundary(BoundaryMode::Toroidal)
+        };
+        let positions = [
+            BoidsPosition::new(0.5, 0.98),
+            BoidsPosition::new(0.8, 0.98),
+            BoidsPosition::new(0.52, 0.98),
+            // Second nearest of the first boid through the wrapped edge
+            BoidsPosition::new(0.5, 0.01),
+            BoidsPosition::new(0.6, 0.98),
+        ];
+        let neighbors = topological_neighbors(&simulation_parameters, &positions, 0, &positions[0], &BoidsVelocity::new(1.0, 0.0));
+        assert_eq!(neighbors, vec![2, 3]);
+
+        let neighbors = topological_neighbors(&simulation_parameters, &positions, 1, &positions[1], &BoidsVelocity::new(1.0, 0.0));
+        assert_eq!(neighbors, vec![4, 2]);
+
+        // Boids behind are out of view with a narrow field of view
+        let simulation_parameters = SimulationParametersUniformBufferContent { field_of_view: 90.0, ..simulation_parameters };
+        let neighbors = topological_neighbors(&simulation_parameters, &positions, 4, &positions[4], &BoidsVelocity::new(1.0, 0.0));
+        assert_eq!(neighbors, vec![1]);
+    }
+
+    #[test]
+    fn respawn_position_is_drawn_from_the_random_stream() {
+        let mut random_state: BoidsRandomState = 42;
+        let first = respawn_position(&mut random_state);
+        let second = respawn_position(&mut random_state);
+        assert_ne!(random_state, 42);
+        assert_ne!(first, second);
+        for position in [first, second] {
+            assert!(position.iter().all(|x| (0.0..1.0).contains(x)), "{:?} out of the domain", position);
+        }
+    }
+
+    #[test]
+    fn pick_species_follows_the_proportions() {
+        let mut species = SpeciesSettings::default().species;
+        species.push(species[0]);
+        species[0].proportion = 1.0;
+        species[1].proportion = 3.0;
+
+        assert_eq!(pick_species(&species, 0.0), 0);
+        assert_eq!(pick_species(&species, 0.2), 0);
+        assert_eq!(pick_species(&species, 0.3), 1);
+        assert_eq!(pick_species(&species, 1.0), 1);
+        assert_eq!(pick_species(&species[..1], 0.9), 0);
+    }
+
+    #[test]
+    fn lattice_side_fits_every_boid() {
+        assert_eq!(lattice_side(0), 1);
+        assert_eq!(lattice_side(1), 1);
+        assert_eq!(lattice_side(4), 2);
+        assert_eq!(lattice_side(5), 3);
+        assert_eq!(lattice_side(12), 4);
+        for total in 1..2000 {
+            let side = lattice_side(total);
+            assert!(side * side >= total && (side - 1) * (side - 1) < total, "side {} for {} boids", side, total);
+        }
+    }
+
+    #[test]
+    fn resize_keeps_the_boids_of_the_remaining_ids() {
+        let init_parameters = InitParametersUniformBufferContent::default();
+        let species = SpeciesSettings::default().species;
+        let mut flock = CpuFlock::new(8, &init_parameters, &species);
+
+        // Slots no longer match ids, as after restoring a state read back from sorted GPU buffers
+        flock.positions.reverse();
+        flock.ids.reverse();
+        let positions_by_id: Vec<BoidsPosition> = (0..8).map(|id| flock.positions[flock.ids.iter().position(|other_id| *other_id == id).unwrap()]).collect();
+
+        flock.resize(5, &init_parameters, &species);
+        assert_eq!(flock.ids, vec![4, 3, 2, 1, 0]);
+        assert_eq!(flock.positions.len(), 5);
+        assert_eq!(flock.random_states.len(), 5);
+        for (position, id) in flock.positions.iter().zip(&flock.ids) {
+            assert_eq!(*position, positions_by_id[*id as usize]);
+        }
+
+        // New boids take the next ids and are seeded like a fresh init
+        flock.resize(7, &init_parameters, &species);
+        let fresh = CpuFlock::new(7, &init_parameters, &species);
+        assert_eq!(flock.ids, vec![4, 3, 2, 1, 0, 5, 6]);
+        assert_eq!(flock.positions[5..], fresh.positions[5..]);
+        assert_eq!(flock.velocities[5..], fresh.velocities[5..]);
+        assert_eq!(flock.species.len(), 7);
+    }
+}
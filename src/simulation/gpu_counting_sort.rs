@@ -0,0 +1,262 @@
+use oxyde::{wgpu, wgpu_utils::binding_builder};
+
+use super::{shader_builder::create_shader_module, Dimension, WORKGROUP_SIZE};
+
+// Number of elements scanned by a single workgroup (must match SCAN_WORKGROUP_SIZE in prefixSum.wgsl)
+const SCAN_WORKGROUP_SIZE: u32 = 256;
+
+struct PrefixSumLevel {
+    element_count: u32,
+    bind_group: wgpu::BindGroup,
+}
+
+// Sort boids by cell id entirely on GPU:
+// histogram of boids per cell, exclusive prefix sum of the histogram and scatter of boids indices in the sorting id buffer
+pub struct GpuCountingSort {
+    histogram_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+    scan_blocks_pipeline: wgpu::ComputePipeline,
+    add_block_offsets_pipeline: wgpu::ComputePipeline,
+
+    sort_bind_group_layout_with_desc: binding_builder::BindGroupLayoutWithDesc,
+    prefix_sum_bind_group_layout_with_desc: binding_builder::BindGroupLayoutWithDesc,
+
+    sort_bind_group: wgpu::BindGroup,
+    cell_scatter_offset_buffer: wgpu::Buffer,
+
+    // Each level scans the block sums of the previous one, the first level being the boids per cell count
+    prefix_sum_levels: Vec<PrefixSumLevel>,
+    _block_sums_buffers: Vec<wgpu::Buffer>,
+}
+
+fn create_cell_scatter_offset_buffer_and_sort_bind_group(
+    device: &wgpu::Device,
+    sort_bind_group_layout: &binding_builder::BindGroupLayoutWithDesc,
+    sorting_id_buffer: &wgpu::Buffer,
+    boids_per_cell_count_buffer: &wgpu::Buffer,
+    cell_count: u32,
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let cell_scatter_offset_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Cell scatter offset"),
+        size: cell_count as u64 * std::mem::size_of::<u32>() as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let sort_bind_group = binding_builder::BindGroupBuilder::new(sort_bind_group_layout)
+        .resource(boids_per_cell_count_buffer.as_entire_binding())
+        .resource(cell_scatter_offset_buffer.as_entire_binding())
+        .resource(sorting_id_buffer.as_entire_binding())
+        .create(device, Some("Counting sort"));
+
+    (cell_scatter_offset_buffer, sort_bind_group)
+}
+
+fn create_prefix_sum_levels(
+    device: &wgpu::Device,
+    prefix_sum_bind_group_layout: &binding_builder::BindGroupLayoutWithDesc,
+    boids_per_cell_count_buffer: &wgpu::Buffer,
+    cell_count: u32,
+) -> (Vec<PrefixSumLevel>, Vec<wgpu::Buffer>) {
+    let mut prefix_sum_levels = Vec::new();
+    let mut block_sums_buffers: Vec<wgpu::Buffer> = Vec::new();
+
+    let mut element_count = cell_count;
+    loop {
+        let block_count = element_count.div_ceil(SCAN_WORKGROUP_SIZE);
+
+        let block_sums_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Prefix sum block sums"),
+            size: block_count as u64 * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let data_buffer = block_sums_buffers.last().unwrap_or(boids_per_cell_count_buffer);
+        let bind_group = binding_builder::BindGroupBuilder::new(prefix_sum_bind_group_layout)
+            .resource(data_buffer.as_entire_binding())
+            .resource(block_sums_buffer.as_entire_binding())
+            .create(device, Some("Prefix sum level"));
+
+        prefix_sum_levels.push(PrefixSumLevel { element_count, bind_group });
+        block_sums_buffers.push(block_sums_buffer);
+
+        // The last level fits in a single workgroup
+        if block_count == 1 {
+            break;
+        }
+        element_count = block_count;
+    }
+
+    (prefix_sum_levels, block_sums_buffers)
+}
+
+impl GpuCountingSort {
+    pub fn new(
+        device: &wgpu::Device,
+        dimension: Dimension,
+        read_only_bind_group_layout: &wgpu::BindGroupLayout,
+        simulation_parameters_uniform_buffer_layout: &wgpu::BindGroupLayout,
+        sorting_id_buffer: &wgpu::Buffer,
+        boids_per_cell_count_buffer: &wgpu::Buffer,
+        cell_count: u32,
+    ) -> Self {
+        let storage_binding = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<u32>() as u64),
+        };
+
+        let sort_bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding_compute(storage_binding)
+            .add_binding_compute(storage_binding)
+            .add_binding_compute(storage_binding)
+            .create(device, Some("Counting sort"));
+
+        let prefix_sum_bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding_compute(storage_binding)
+            .add_binding_compute(storage_binding)
+            .create(device, Some("Prefix sum"));
+
+        let (cell_scatter_offset_buffer, sort_bind_group) = create_cell_scatter_offset_buffer_and_sort_bind_group(
+            device,
+            &sort_bind_group_layout_with_desc,
+            sorting_id_buffer,
+            boids_per_cell_count_buffer,
+            cell_count,
+        );
+
+        let (prefix_sum_levels, block_sums_buffers) =
+            create_prefix_sum_levels(device, &prefix_sum_bind_group_layout_with_desc, boids_per_cell_count_buffer, cell_count);
+
+        let counting_sort_shader = create_shader_module(device, "Counting Sort Shader", include_str!("../../shaders/countingSort.wgsl"), dimension);
+
+        let prefix_sum_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Prefix Sum Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../shaders/prefixSum.wgsl").into()),
+        });
+
+        let sort_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Counting Sort Pipeline"),
+            bind_group_layouts: &[
+                read_only_bind_group_layout,
+                &sort_bind_group_layout_with_desc.layout,
+                simulation_parameters_uniform_buffer_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let prefix_sum_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Prefix Sum Pipeline"),
+            bind_group_layouts: &[&prefix_sum_bind_group_layout_with_desc.layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, layout: &wgpu::PipelineLayout, module: &wgpu::ShaderModule, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                module,
+                entry_point,
+            })
+        };
+
+        Self {
+            histogram_pipeline: create_pipeline("Histogram pipeline", &sort_pipeline_layout, &counting_sort_shader, "histogram"),
+            scatter_pipeline: create_pipeline("Scatter pipeline", &sort_pipeline_layout, &counting_sort_shader, "scatter"),
+            scan_blocks_pipeline: create_pipeline("Scan blocks pipeline", &prefix_sum_pipeline_layout, &prefix_sum_shader, "scan_blocks"),
+            add_block_offsets_pipeline: create_pipeline(
+                "Add block offsets pipeline",
+                &prefix_sum_pipeline_layout,
+                &prefix_sum_shader,
+                "add_block_offsets",
+            ),
+            sort_bind_group_layout_with_desc,
+            prefix_sum_bind_group_layout_with_desc,
+            sort_bind_group,
+            cell_scatter_offset_buffer,
+            prefix_sum_levels,
+            _block_sums_buffers: block_sums_buffers,
+        }
+    }
+
+    // Recreate bind groups and intermediate buffers when the sorting id or the boids per cell count buffers are reallocated
+    pub fn set_buffers(&mut self, device: &wgpu::Device, sorting_id_buffer: &wgpu::Buffer, boids_per_cell_count_buffer: &wgpu::Buffer, cell_count: u32) {
+        (self.cell_scatter_offset_buffer, self.sort_bind_group) = create_cell_scatter_offset_buffer_and_sort_bind_group(
+            device,
+            &self.sort_bind_group_layout_with_desc,
+            sorting_id_buffer,
+            boids_per_cell_count_buffer,
+            cell_count,
+        );
+
+        (self.prefix_sum_levels, self._block_sums_buffers) =
+            create_prefix_sum_levels(device, &self.prefix_sum_bind_group_layout_with_desc, boids_per_cell_count_buffer, cell_count);
+    }
+
+    // Fill boids_per_cell_count_buffer with the exclusive prefix sum of boids per cell
+    // and sorting_id_buffer with boids indices sorted by cell id, from the positions of the given boids data bind group
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+        read_only_bind_group: &wgpu::BindGroup,
+        simulation_parameters_bind_group: &wgpu::BindGroup,
+        boids_per_cell_count_buffer: &wgpu::Buffer,
+        boids_count: u32,
+    ) {
+        let dispatch_group_count = boids_count.div_ceil(WORKGROUP_SIZE);
+
+        encoder.clear_buffer(boids_per_cell_count_buffer, 0, None);
+
+        {
+            let mut scope = simulation_profiler.scope("Histogram cell id", encoder, device);
+            let compute_pass = &mut scope.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Histogram Pass"), timestamp_writes: None });
+
+            compute_pass.set_pipeline(&self.histogram_pipeline);
+            compute_pass.set_bind_group(0, read_only_bind_group, &[]);
+            compute_pass.set_bind_group(1, &self.sort_bind_group, &[]);
+            compute_pass.set_bind_group(2, simulation_parameters_bind_group, &[]);
+            compute_pass.dispatch_workgroups(dispatch_group_count, 1, 1);
+        }
+
+        {
+            let mut scope = simulation_profiler.scope("Prefix sum cell count", encoder, device);
+            let compute_pass = &mut scope.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Prefix Sum Pass"), timestamp_writes: None });
+
+            compute_pass.set_pipeline(&self.scan_blocks_pipeline);
+            for level in self.prefix_sum_levels.iter() {
+                compute_pass.set_bind_group(0, &level.bind_group, &[]);
+                compute_pass.dispatch_workgroups(level.element_count.div_ceil(SCAN_WORKGROUP_SIZE), 1, 1);
+            }
+
+            // Propagate scanned block sums from the top level down, the top level being a single block it needs no offset
+            compute_pass.set_pipeline(&self.add_block_offsets_pipeline);
+            for level in self.prefix_sum_levels.iter().rev().skip(1) {
+                compute_pass.set_bind_group(0, &level.bind_group, &[]);
+                compute_pass.dispatch_workgroups(level.element_count.div_ceil(SCAN_WORKGROUP_SIZE), 1, 1);
+            }
+        }
+
+        // The prefix sum is kept for neighbors search, scatter works on a copy of it
+        encoder.copy_buffer_to_buffer(
+            boids_per_cell_count_buffer,
+            0,
+            &self.cell_scatter_offset_buffer,
+            0,
+            self.cell_scatter_offset_buffer.size(),
+        );
+
+        {
+            let mut scope = simulation_profiler.scope("Scatter sorting id", encoder, device);
+            let compute_pass = &mut scope.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Scatter Pass"), timestamp_writes: None });
+
+            compute_pass.set_pipeline(&self.scatter_pipeline);
+            compute_pass.set_bind_group(0, read_only_bind_group, &[]);
+            compute_pass.set_bind_group(1, &self.sort_bind_group, &[]);
+            compute_pass.set_bind_group(2, simulation_parameters_bind_group, &[]);
+            compute_pass.dispatch_workgroups(dispatch_group_count, 1, 1);
+        }
+    }
+}
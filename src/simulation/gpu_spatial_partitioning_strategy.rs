@@ -1,67 +1,106 @@
-use oxyde::{wgpu, wgpu_utils::{binding_builder, buffers::StagingBufferWrapper, uniform_buffer::UniformBufferWrapper, wgsl_preprocessor::WGSLShaderBuilder}};
+use oxyde::{egui, wgpu, wgpu_utils::{binding_builder, uniform_buffer::UniformBufferWrapper}};
 
 use super::{
-    parameters::InitParametersUniformBufferContent, types::*, SimulationParametersUniformBufferContent, SimulationStrategy
+    boids_buffers::{BoidsBindGroupLayouts, BoidsBuffers, BoidsResizePass}, boids_state::BoidsState, gpu_counting_sort::GpuCountingSort, obstacles::{Obstacles, ObstaclesBuffers, ObstaclesRenderer}, parameters::{InitParametersUniformBufferContent, RuleSet}, perspective_boids_renderer::PerspectiveBoidsRenderer, pointer_force::{PointerForceBuffer, PointerForceUniformBufferContent}, predators::{init_predators, GpuPredatorsPass, PredatorsBuffers, PredatorsRenderer}, shader_builder::{create_rules_shader_module, create_shader_module}, species::{SpeciesBuffers, SpeciesSettings}, types::*, Dimension, SimulationParametersUniformBufferContent, SimulationStrategy, WORKGROUP_SIZE
 };
 
-const WORKGROUP_SIZE: u32 = 64;
+// 2D boids are drawn as flat triangles over the viewport, 3D ones as meshes seen from an orbit camera
+// Obstacles only exist in 2D so only the flat display draws them
+enum BoidsDisplay {
+    Flat(wgpu::RenderPipeline, ObstaclesRenderer, Box<PredatorsRenderer>),
+    Perspective(Box<PerspectiveBoidsRenderer>),
+}
 
 struct GpuSpatialPartitioningStrategy {
-    render_pipeline: wgpu::RenderPipeline,
-    compute_pipeline: wgpu::ComputePipeline,
+    dimension: Dimension,
+    display: BoidsDisplay,
+    // Kept to create the naive and grid pipelines again when the rule set changes
+    compute_pipeline_layout: wgpu::PipelineLayout,
+    naive_compute_pipeline: wgpu::ComputePipeline,
+    grid_compute_pipeline: wgpu::ComputePipeline,
     init_pipeline: wgpu::ComputePipeline,
 
-    sorting_id_staging_buffer: StagingBufferWrapper<BoidSortingId, false>,
+    sorting_id_bind_group_layout_with_desc: binding_builder::BindGroupLayoutWithDesc,
     sorting_id_buffer: wgpu::Buffer,
     sorting_id_bind_group: wgpu::BindGroup,
-
-    boids_per_cell_count_staging_buffer: StagingBufferWrapper<u32, false>,
-    cell_id_staging_buffer: StagingBufferWrapper<BoidsCellId, true>,
+    obstacles_buffers: ObstaclesBuffers,
+    species_buffers: SpeciesBuffers,
+    predators_buffers: PredatorsBuffers,
+    predators_pass: GpuPredatorsPass,
+    pointer_force_buffer: PointerForceBuffer,
+    environment_bind_group: wgpu::BindGroup,
+
+    grid_size: u32,
+    boids_per_cell_count_bind_group_layout_with_desc: binding_builder::BindGroupLayoutWithDesc,
     boids_per_cell_count_buffer: wgpu::Buffer,
     boids_per_cell_count_bind_group: wgpu::BindGroup,
+    counting_sort: GpuCountingSort,
 
     ping_pong_state: bool,
-    ping_pong_bind_group: wgpu::BindGroup,
-    pong_ping_bind_group: wgpu::BindGroup,
-    ping_bind_group: wgpu::BindGroup,
-    pong_bind_group: wgpu::BindGroup,
-
-    cell_id_ping_buffer: wgpu::Buffer,
-    cell_id_pong_buffer: wgpu::Buffer,
-    use_spatial_partitioning: bool,
+    boids_bind_group_layouts: BoidsBindGroupLayouts,
+    boids_buffers: BoidsBuffers,
+    boids_resize_pass: BoidsResizePass,
+}
+
+// Naive and grid compute pipelines, their shaders include the rule set
+fn create_compute_pipelines(
+    device: &wgpu::Device,
+    dimension: Dimension,
+    rule_set: RuleSet,
+    compute_pipeline_layout: &wgpu::PipelineLayout,
+) -> (wgpu::ComputePipeline, wgpu::ComputePipeline) {
+    let naive_compute_shader =
+        create_rules_shader_module(device, "Naive Compute Shader", include_str!("../../shaders/computeNative.wgsl"), dimension, rule_set);
+
+    let grid_compute_shader =
+        create_rules_shader_module(device, "Grid Compute Shader", include_str!("../../shaders/computeGrid.wgsl"), dimension, rule_set);
+
+    let naive_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Naive compute pipeline"),
+        layout: Some(compute_pipeline_layout),
+        module: &naive_compute_shader,
+        entry_point: "cs_main",
+    });
+
+    let grid_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Grid compute pipeline"),
+        layout: Some(compute_pipeline_layout),
+        module: &grid_compute_shader,
+        entry_point: "cs_main",
+    });
+
+    (naive_compute_pipeline, grid_compute_pipeline)
 }
 
 fn create_pipelines(
     device: &wgpu::Device,
-    surface_configuration: &wgpu::SurfaceConfiguration,
-    display_shader: &wgpu::ShaderModule,
-    compute_shader: &wgpu::ShaderModule,
+    dimension: Dimension,
+    rule_set: RuleSet,
     init_shader: &wgpu::ShaderModule,
 
     ping_pong_bind_group_layout: &wgpu::BindGroupLayout,
-    read_only_bind_group_layout: &wgpu::BindGroupLayout,
     sorting_id_bind_group_layout: &wgpu::BindGroupLayout,
     boids_per_cell_count_bind_group_layout: &wgpu::BindGroupLayout,
+    environment_bind_group_layout: &wgpu::BindGroupLayout,
 
     init_parameters_uniform_buffer_layout: &wgpu::BindGroupLayout,
     simulation_parameters_uniform_buffer_layout: &wgpu::BindGroupLayout,
-) -> (wgpu::ComputePipeline, wgpu::ComputePipeline, wgpu::RenderPipeline) {
-    let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Compute pipeline"),
-        layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Pipeline"),
-            bind_group_layouts: &[
-                simulation_parameters_uniform_buffer_layout,
-                ping_pong_bind_group_layout,
-                sorting_id_bind_group_layout,
-                boids_per_cell_count_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
-        })),
-        module: compute_shader,
-        entry_point: "cs_main",
+) -> (wgpu::ComputePipeline, wgpu::ComputePipeline, wgpu::ComputePipeline, wgpu::PipelineLayout) {
+    // Naive and grid strategies share the same layout so they can be switched at any time
+    let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Compute Pipeline"),
+        bind_group_layouts: &[
+            simulation_parameters_uniform_buffer_layout,
+            ping_pong_bind_group_layout,
+            sorting_id_bind_group_layout,
+            boids_per_cell_count_bind_group_layout,
+            environment_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
     });
 
+    let (naive_compute_pipeline, grid_compute_pipeline) = create_compute_pipelines(device, dimension, rule_set, &compute_pipeline_layout);
+
     let init_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
         label: Some("Init pipeline"),
         layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -70,6 +109,8 @@ fn create_pipelines(
                 init_parameters_uniform_buffer_layout,
                 simulation_parameters_uniform_buffer_layout,
                 ping_pong_bind_group_layout,
+                // species proportions
+                environment_bind_group_layout,
             ],
             push_constant_ranges: &[],
         })),
@@ -77,11 +118,23 @@ fn create_pipelines(
         entry_point: "cs_main",
     });
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    (init_pipeline, naive_compute_pipeline, grid_compute_pipeline, compute_pipeline_layout)
+}
+
+// Display pipeline drawing each boid as an instance of the vertices buffer triangle
+pub(super) fn create_render_pipeline(
+    device: &wgpu::Device,
+    surface_configuration: &wgpu::SurfaceConfiguration,
+    display_shader: &wgpu::ShaderModule,
+    read_only_bind_group_layout: &wgpu::BindGroupLayout,
+    simulation_parameters_uniform_buffer_layout: &wgpu::BindGroupLayout,
+    species_display_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
         layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[simulation_parameters_uniform_buffer_layout, read_only_bind_group_layout],
+            bind_group_layouts: &[simulation_parameters_uniform_buffer_layout, read_only_bind_group_layout, species_display_bind_group_layout],
             push_constant_ranges: &[],
         })),
 
@@ -114,376 +167,355 @@ fn create_pipelines(
         depth_stencil: None,
         multisample: wgpu::MultisampleState::default(),
         multiview: None,
-    });
+    })
+}
 
-    (init_pipeline, compute_pipeline, render_pipeline)
+
+// Sorting id starts as identity, so the naive strategy can use it for display without any sort
+pub(super) fn create_sorting_id_buffer(device: &wgpu::Device, boids_count: u32) -> wgpu::Buffer {
+    wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Sorting id"),
+            contents: bytemuck::cast_slice(&(0..boids_count as BoidSortingId).collect::<Vec<_>>()),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+        },
+    )
 }
 
+fn create_sorting_id_bind_group(
+    device: &wgpu::Device,
+    sorting_id_bind_group_layout: &binding_builder::BindGroupLayoutWithDesc,
+    sorting_id_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    binding_builder::BindGroupBuilder::new(sorting_id_bind_group_layout)
+        .resource(sorting_id_buffer.as_entire_binding())
+        .create(device, Some("sorting_id_bind_group"))
+}
 
-// fn that create buffers and bind groups for boids data
-fn create_boids_buffers_and_bind_groups(
+// Obstacles, species, predators and pointer force buffers never change size so their bind group is created once
+fn create_environment_bind_group(
     device: &wgpu::Device,
-    boids_count: u32,
-    ping_pong_buffer_visibility: wgpu::ShaderStages,
-    read_only_buffer_visibility: wgpu::ShaderStages,
-) -> (
-    wgpu::Buffer,
-    wgpu::Buffer,
-    wgpu::Buffer,
-    wgpu::Buffer,
-    wgpu::Buffer,
-    wgpu::Buffer,
-    binding_builder::BindGroupLayoutWithDesc,
-    wgpu::BindGroup,
-    wgpu::BindGroup,
-    binding_builder::BindGroupLayoutWithDesc,
-    wgpu::BindGroup,
-    wgpu::BindGroup,
-) {
-    let boids_count = boids_count as u64;
-    // let usage = wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
-    let usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC;
-
-    let position_buffer_descriptor = wgpu::BufferDescriptor {
-        label: Some("Position"),
-        size: boids_count * std::mem::size_of::<BoidsPosition>() as u64,
-        usage,
-        mapped_at_creation: false,
-    };
+    obstacles_buffers: &ObstaclesBuffers,
+    species_buffers: &SpeciesBuffers,
+    predators_buffers: &PredatorsBuffers,
+    pointer_force_buffer: &PointerForceBuffer,
+) -> (binding_builder::BindGroupLayoutWithDesc, wgpu::BindGroup) {
+    let environment_bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
+        .add_binding_compute(ObstaclesBuffers::obstacles_binding_type())
+        .add_binding_compute(ObstaclesBuffers::vertices_binding_type())
+        .add_binding_compute(SpeciesBuffers::species_binding_type())
+        .add_binding_compute(SpeciesBuffers::interactions_binding_type())
+        .add_binding_compute(PredatorsBuffers::predators_binding_type(true))
+        .add_binding_compute(PointerForceBuffer::binding_type())
+        .create(device, Some("Environment"));
+
+    let environment_bind_group = binding_builder::BindGroupBuilder::new(&environment_bind_group_layout_with_desc)
+        .resource(obstacles_buffers.obstacles_buffer.as_entire_binding())
+        .resource(obstacles_buffers.vertices_buffer.as_entire_binding())
+        .resource(species_buffers.species_buffer.as_entire_binding())
+        .resource(species_buffers.interactions_buffer.as_entire_binding())
+        .resource(predators_buffers.predators_buffer.as_entire_binding())
+        .resource(pointer_force_buffer.buffer.as_entire_binding())
+        .create(device, Some("environment_bind_group"));
+
+    (environment_bind_group_layout_with_desc, environment_bind_group)
+}
 
-    let velocity_buffer_descriptor = wgpu::BufferDescriptor {
-        label: Some("Velocity"),
-        size: boids_count * std::mem::size_of::<BoidsVelocity>() as u64,
-        usage,
-        mapped_at_creation: false,
-    };
+// one more cell to store the total count at the end of the prefix sum
+fn cell_count_from_grid_size(dimension: Dimension, grid_size: u32) -> u32 { grid_size.pow(dimension.count()) + 1 }
 
-    let cell_id_buffer_descriptor = wgpu::BufferDescriptor {
-        label: Some("Cell Id"),
-        size: boids_count * std::mem::size_of::<BoidsCellId>() as u64,
-        usage,
+fn create_boids_per_cell_count_buffer_and_bind_group(
+    device: &wgpu::Device,
+    boids_per_cell_count_bind_group_layout: &binding_builder::BindGroupLayoutWithDesc,
+    dimension: Dimension,
+    grid_size: u32,
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let boids_per_cell_count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Boids per cell count"),
+        size: cell_count_from_grid_size(dimension, grid_size) as u64 * std::mem::size_of::<u32>() as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
-    };
+    });
 
-    let position_ping_buffer = device.create_buffer(&position_buffer_descriptor);
-    let velocity_ping_buffer = device.create_buffer(&velocity_buffer_descriptor);
-    let cell_id_ping_buffer = device.create_buffer(&cell_id_buffer_descriptor);
-
-    let position_pong_buffer = device.create_buffer(&position_buffer_descriptor);
-    let velocity_pong_buffer = device.create_buffer(&velocity_buffer_descriptor);
-    let cell_id_pong_buffer = device.create_buffer(&cell_id_buffer_descriptor);
-
-    let ping_pong_bind_group_layout_builder_descriptor = binding_builder::BindGroupLayoutBuilder::new()
-        .add_binding(
-            ping_pong_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(position_buffer_descriptor.size),
-            },
-        )
-        .add_binding(
-            ping_pong_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(velocity_buffer_descriptor.size),
-            },
-        )
-        .add_binding(
-            ping_pong_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(cell_id_buffer_descriptor.size),
-            },
-        )
-        .add_binding(
-            ping_pong_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: false },
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(position_buffer_descriptor.size),
-            },
-        )
-        .add_binding(
-            ping_pong_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: false },
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(velocity_buffer_descriptor.size),
-            },
-        )
-        .add_binding(
-            ping_pong_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: false },
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(cell_id_buffer_descriptor.size),
-            },
-        )
-        .create(device, Some("Boids data (ping <-> pong)"));
-
-    let ping_pong_bind_group = binding_builder::BindGroupBuilder::new(&ping_pong_bind_group_layout_builder_descriptor)
-        .resource(position_ping_buffer.as_entire_binding())
-        .resource(velocity_ping_buffer.as_entire_binding())
-        .resource(cell_id_ping_buffer.as_entire_binding())
-        .resource(position_pong_buffer.as_entire_binding())
-        .resource(velocity_pong_buffer.as_entire_binding())
-        .resource(cell_id_pong_buffer.as_entire_binding())
-        .create(device, Some("Boids data (ping -> pong)"));
-
-    let pong_ping_bind_group = binding_builder::BindGroupBuilder::new(&ping_pong_bind_group_layout_builder_descriptor)
-        .resource(position_pong_buffer.as_entire_binding())
-        .resource(velocity_pong_buffer.as_entire_binding())
-        .resource(cell_id_pong_buffer.as_entire_binding())
-        .resource(position_ping_buffer.as_entire_binding())
-        .resource(velocity_ping_buffer.as_entire_binding())
-        .resource(cell_id_ping_buffer.as_entire_binding())
-        .create(device, Some("Boids data (pong -> ping)"));
-
-    // read only bind group for final display
-    let has_dynamic_offset = false;
-    let read_only_bind_group_layout_builder_descriptor = binding_builder::BindGroupLayoutBuilder::new()
-        .add_binding(
-            read_only_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset,
-                min_binding_size: wgpu::BufferSize::new(position_buffer_descriptor.size),
-            },
-        )
-        .add_binding(
-            read_only_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset,
-                min_binding_size: wgpu::BufferSize::new(velocity_buffer_descriptor.size),
-            },
-        )
-        .add_binding(
-            read_only_buffer_visibility,
-            wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset,
-                min_binding_size: wgpu::BufferSize::new(cell_id_buffer_descriptor.size),
-            },
-        )
-        .create(device, Some("Boids data (read only)"));
-
-    let ping_bind_group = binding_builder::BindGroupBuilder::new(&read_only_bind_group_layout_builder_descriptor)
-        .resource(position_ping_buffer.as_entire_binding())
-        .resource(velocity_ping_buffer.as_entire_binding())
-        .resource(cell_id_ping_buffer.as_entire_binding())
-        .create(device, Some("Boids data (ping read only)"));
-
-    let pong_bind_group = binding_builder::BindGroupBuilder::new(&read_only_bind_group_layout_builder_descriptor)
-        .resource(position_pong_buffer.as_entire_binding())
-        .resource(velocity_pong_buffer.as_entire_binding())
-        .resource(cell_id_pong_buffer.as_entire_binding())
-        .create(device, Some("Boids data (pong read only)"));
-
-    (
-        position_ping_buffer,
-        velocity_ping_buffer,
-        cell_id_ping_buffer,
-        position_pong_buffer,
-        velocity_pong_buffer,
-        cell_id_pong_buffer,
-        ping_pong_bind_group_layout_builder_descriptor,
-        ping_pong_bind_group,
-        pong_ping_bind_group,
-        read_only_bind_group_layout_builder_descriptor,
-        ping_bind_group,
-        pong_bind_group,
-    )
+    let boids_per_cell_count_bind_group = binding_builder::BindGroupBuilder::new(boids_per_cell_count_bind_group_layout)
+        .resource(boids_per_cell_count_buffer.as_entire_binding())
+        .create(device, Some("boids_per_cell_count_bind_group"));
+
+    (boids_per_cell_count_buffer, boids_per_cell_count_bind_group)
 }
 
 impl GpuSpatialPartitioningStrategy {
-
-    fn sort_from_cell_id(&mut self, boids_count: u32) {
-        let boid_count_usize = boids_count as usize;
-    
-        let boids_per_cell_count_slice = self.boids_per_cell_count_staging_buffer.values_as_slice_mut();
-        // count boids per cell
-        boids_per_cell_count_slice.fill(0);
-        self.cell_id_staging_buffer.iter().for_each(|boid_cell_id| {
-            boids_per_cell_count_slice[*boid_cell_id as usize] += 1;
-        });
-    
-        // partial sum of boids per cell
-        for i in 1..boids_per_cell_count_slice.len() {
-            boids_per_cell_count_slice[i] += boids_per_cell_count_slice[i - 1];
-        }
-    
-        // sort boids
-        self.sorting_id_staging_buffer.clear();
-        let sorting_id_values_slice = self.sorting_id_staging_buffer.values_as_slice_mut();
-        for i in 0..boid_count_usize {
-            let boid_cell_id = self.cell_id_staging_buffer[i];
-            let boid_target_index = boids_per_cell_count_slice[boid_cell_id as usize] - 1;
-            boids_per_cell_count_slice[boid_cell_id as usize] -= 1;
-            sorting_id_values_slice[boid_target_index as usize] = i as BoidSortingId;
-        }
-    
-        // Debug display
-        // println!("boids_sorting_id_value: {:?}\n", self.sorting_id_staging_buffer.values_as_slice());
-        // println!("boids_per_cell_count: {:?}\n", self.boids_per_cell_count_staging_buffer.values_as_slice());
-    
-        // let boids_cell_id_using_sorting_order = self.sorting_id_staging_buffer.iter().map(|boid_sorting_id| self.cell_id_staging_buffer[*boid_sorting_id as usize].x).collect::<Vec<_>>();
-        // println!("boids_cell_id_using_sorting_order: {:?} (sorted : {:?})\n\n",
-        //     boids_cell_id_using_sorting_order,
-        //     boids_cell_id_using_sorting_order.windows(2).all(|x| x[0] <= x[1])
-        // );
+    fn encode_init(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+    ) {
+        let mut scope = simulation_profiler.scope("Init Boids", encoder, device);
+        let compute_pass = &mut scope.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Compute Pass"), timestamp_writes: None });
+
+        compute_pass.set_pipeline(&self.init_pipeline);
+        compute_pass.set_bind_group(0, init_parameters_uniform_buffer.bind_group(), &[]);
+        compute_pass.set_bind_group(1, simulation_parameters_uniform_buffer.bind_group(), &[]);
+        compute_pass.set_bind_group(
+            2,
+            if self.ping_pong_state {
+                &self.boids_buffers.ping_pong_bind_group
+            } else {
+                &self.boids_buffers.pong_ping_bind_group
+            },
+            &[],
+        );
+        compute_pass.set_bind_group(3, &self.environment_bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.boids_buffers.boids_count.div_ceil(WORKGROUP_SIZE), 1, 1);
     }
-}
 
-impl SimulationStrategy for GpuSpatialPartitioningStrategy {
-    fn render(
+    // Reallocate every buffer depending on the boids count, boids whose id is below the new count are kept and new ones are seeded by the init shader
+    // The previous buffers are returned to be kept alive until the copy is submitted
+    fn resize_boids_buffers(
         &mut self,
-        _app_state: &mut oxyde::AppState,
-        _output_view: &wgpu::TextureView,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
         init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
         simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
-        vertices_buffer: &wgpu::Buffer,
         simulation_profiler: &mut wgpu_profiler::GpuProfiler,
-        need_init: &mut bool,
-    ) -> Result<(), wgpu::SurfaceError> {
+        boids_count: u32,
+    ) -> BoidsBuffers {
+        let previous_boids_buffers = self.reallocate_boids_buffers(device, boids_count);
 
-        let boids_count = simulation_parameters_uniform_buffer.content().boids_count;
-        let dispatch_group_count = std::cmp::max(1, boids_count / WORKGROUP_SIZE);
+        // Seed every boid then overwrite the ones kept from the previous buffers
+        self.encode_init(device, encoder, init_parameters_uniform_buffer, simulation_parameters_uniform_buffer, simulation_profiler);
+        self.boids_resize_pass
+            .encode(device, encoder, &self.boids_bind_group_layouts, &previous_boids_buffers, &self.boids_buffers, self.ping_pong_state);
 
-        let mut compute_encoder: wgpu::CommandEncoder = _app_state
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Compute Boids Encoder") });
+        previous_boids_buffers
+    }
 
-        if *need_init {
-            {
-                let mut scope = simulation_profiler.scope("Init Boids", &mut compute_encoder, &_app_state.device);
-                let compute_pass = &mut scope.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Compute Pass"), timestamp_writes: None });
-
-                compute_pass.set_pipeline(&self.init_pipeline);
-                compute_pass.set_bind_group(0, init_parameters_uniform_buffer.bind_group(), &[]);
-                compute_pass.set_bind_group(1, simulation_parameters_uniform_buffer.bind_group(), &[]);
-                compute_pass.set_bind_group(
-                    2,
-                    if self.ping_pong_state {
-                        &self.ping_pong_bind_group
-                    } else {
-                        &self.pong_ping_bind_group
-                    },
-                    &[],
-                );
-                compute_pass.dispatch_workgroups(dispatch_group_count, 1, 1);
-            }
+    // New boids buffers and everything bound with them, the previous boids buffers are returned
+    fn reallocate_boids_buffers(&mut self, device: &wgpu::Device, boids_count: u32) -> BoidsBuffers {
+        let previous_boids_buffers = std::mem::replace(&mut self.boids_buffers, BoidsBuffers::new(device, boids_count, &self.boids_bind_group_layouts));
 
-            *need_init = false;
-        } else {
-            // explicit swap ping pong buffers
-            self.ping_pong_state = !self.ping_pong_state;
-
-            {
-                let mut scope = simulation_profiler.scope("Compute Boids", &mut compute_encoder, &_app_state.device);
-                let mut compute_pass = scope.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Compute Pass"), timestamp_writes: None });
-
-                compute_pass.set_pipeline(&self.compute_pipeline);
-                compute_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
-                compute_pass.set_bind_group(
-                    1,
-                    if self.ping_pong_state {
-                        &self.ping_pong_bind_group
-                    } else {
-                        &self.pong_ping_bind_group
-                    },
-                    &[],
-                );
-                compute_pass.set_bind_group(2, &self.sorting_id_bind_group, &[]);
-                compute_pass.set_bind_group(3, &self.boids_per_cell_count_bind_group, &[]);
-                compute_pass.dispatch_workgroups(dispatch_group_count, 1, 1);
-            }
+        self.sorting_id_buffer = create_sorting_id_buffer(device, boids_count);
+        self.sorting_id_bind_group = create_sorting_id_bind_group(device, &self.sorting_id_bind_group_layout_with_desc, &self.sorting_id_buffer);
+
+        self.counting_sort.set_buffers(
+            device,
+            &self.sorting_id_buffer,
+            &self.boids_per_cell_count_buffer,
+            cell_count_from_grid_size(self.dimension, self.grid_size),
+        );
+
+        previous_boids_buffers
+    }
+
+    // One simulation step from the current buffers to the other ones
+    fn encode_step(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+        use_spatial_partitioning: bool,
+    ) {
+        let boids_count = self.boids_buffers.boids_count;
+
+        // explicit swap ping pong buffers
+        self.ping_pong_state = !self.ping_pong_state;
+
+        // Sort boids of the source buffers just before using them, so switching from naive to grid needs no extra step
+        if use_spatial_partitioning {
+            self.counting_sort.encode(
+                device,
+                encoder,
+                simulation_profiler,
+                if self.ping_pong_state {
+                    &self.boids_buffers.ping_bind_group
+                } else {
+                    &self.boids_buffers.pong_bind_group
+                },
+                simulation_parameters_uniform_buffer.bind_group(),
+                &self.boids_per_cell_count_buffer,
+                boids_count,
+            );
         }
 
-        _app_state.queue.submit(Some(compute_encoder.finish()));
+        self.predators_pass.encode(
+            device,
+            encoder,
+            simulation_profiler,
+            simulation_parameters_uniform_buffer,
+            if self.ping_pong_state {
+                &self.boids_buffers.ping_bind_group
+            } else {
+                &self.boids_buffers.pong_bind_group
+            },
+        );
 
-        if self.use_spatial_partitioning {
+        let mut scope = simulation_profiler.scope("Compute Boids", encoder, device);
+        let mut compute_pass = scope.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Compute Pass"), timestamp_writes: None });
 
-            let mut read_encoder: wgpu::CommandEncoder = _app_state
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Read Cell Id encoder") });
-
-            {
-                let mut scope = simulation_profiler.scope("Read cell id", &mut read_encoder, &_app_state.device);
-                self.cell_id_staging_buffer.encode_read(
-                    &mut scope,
-                    if self.ping_pong_state {
-                        &self.cell_id_pong_buffer
-                    } else {
-                        &self.cell_id_ping_buffer
-                    },
-                );
-            }
+        compute_pass.set_pipeline(if use_spatial_partitioning {
+            &self.grid_compute_pipeline
+        } else {
+            &self.naive_compute_pipeline
+        });
+        compute_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
+        compute_pass.set_bind_group(
+            1,
+            if self.ping_pong_state {
+                &self.boids_buffers.ping_pong_bind_group
+            } else {
+                &self.boids_buffers.pong_ping_bind_group
+            },
+            &[],
+        );
+        compute_pass.set_bind_group(2, &self.sorting_id_bind_group, &[]);
+        compute_pass.set_bind_group(3, &self.boids_per_cell_count_bind_group, &[]);
+        compute_pass.set_bind_group(4, &self.environment_bind_group, &[]);
+        compute_pass.dispatch_workgroups(boids_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
 
-            _app_state.queue.submit(Some(read_encoder.finish()));
+    // The grid only lives in the boids per cell count buffer, boids are sorted again with the new grid size on the next step
+    fn resize_grid(&mut self, device: &wgpu::Device, grid_size: u32) {
+        self.grid_size = grid_size;
+        (self.boids_per_cell_count_buffer, self.boids_per_cell_count_bind_group) =
+            create_boids_per_cell_count_buffer_and_bind_group(device, &self.boids_per_cell_count_bind_group_layout_with_desc, self.dimension, grid_size);
+
+        self.counting_sort.set_buffers(
+            device,
+            &self.sorting_id_buffer,
+            &self.boids_per_cell_count_buffer,
+            cell_count_from_grid_size(self.dimension, grid_size),
+        );
+    }
+}
 
-            // map buffer wait for CPU read
-            self.cell_id_staging_buffer.map_buffer();
-            _app_state.device.poll(wgpu::Maintain::Wait);
-            self.cell_id_staging_buffer.read_and_unmap_buffer();
+impl SimulationStrategy for GpuSpatialPartitioningStrategy {
+    fn simulate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+        need_init: &mut bool,
+        steps: u32,
+        use_spatial_partitioning: bool,
+        obstacles: &Obstacles,
+        species_settings: &SpeciesSettings,
+        pointer_force: &PointerForceUniformBufferContent,
+    ) {
+        let boids_count = simulation_parameters_uniform_buffer.content().boids_count;
 
-            self.sort_from_cell_id(boids_count);
+        let grid_size = simulation_parameters_uniform_buffer.content().grid_size;
+        if grid_size != self.grid_size {
+            self.resize_grid(device, grid_size);
+        }
 
-            let mut copy_encoder: wgpu::CommandEncoder = _app_state
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("copy sorting Encoder") });
+        self.obstacles_buffers.update(queue, &obstacles.buffer_contents());
+        self.species_buffers.update(queue, species_settings);
+        self.pointer_force_buffer.update(queue, pointer_force);
 
-            // Copy from staging buffer on GPU side
-            {
-                let mut scope = simulation_profiler.scope("Write sorting id", &mut copy_encoder, &_app_state.device);
-                self.sorting_id_staging_buffer.encode_write(&_app_state.queue, &mut scope, &self.sorting_id_buffer);
-            }
+        let mut compute_encoder: wgpu::CommandEncoder = device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Compute Boids Encoder") });
 
-            {   
-                let mut scope = simulation_profiler.scope("Write cell count", &mut copy_encoder, &_app_state.device);
-                self.boids_per_cell_count_staging_buffer.encode_write(&_app_state.queue, &mut scope, &self.boids_per_cell_count_buffer);
-            }
+        let _previous_boids_buffers = (boids_count != self.boids_buffers.boids_count).then(|| {
+            self.resize_boids_buffers(
+                device,
+                &mut compute_encoder,
+                init_parameters_uniform_buffer,
+                simulation_parameters_uniform_buffer,
+                simulation_profiler,
+                boids_count,
+            )
+        });
 
-            // TODO: why there is random crash (wgpu parent device is lost) during copy with specific simulation parameters?
-            _app_state.queue.submit(Some(copy_encoder.finish()));
+        if *need_init {
+            self.predators_buffers.update(queue, &init_predators(self.dimension, init_parameters_uniform_buffer.content().seed));
+            self.encode_init(
+                device,
+                &mut compute_encoder,
+                init_parameters_uniform_buffer,
+                simulation_parameters_uniform_buffer,
+                simulation_profiler,
+            );
 
+            *need_init = false;
+        } else {
+            for _ in 0..steps {
+                self.encode_step(
+                    device,
+                    &mut compute_encoder,
+                    simulation_parameters_uniform_buffer,
+                    simulation_profiler,
+                    use_spatial_partitioning,
+                );
+            }
         }
 
+        queue.submit(Some(compute_encoder.finish()));
+    }
+
+    fn render(
+        &mut self,
+        _app_state: &mut oxyde::AppState,
+        _output_view: &wgpu::TextureView,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        vertices_buffer: &wgpu::Buffer,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let boids_count = self.boids_buffers.boids_count;
         let mut display_encoder: wgpu::CommandEncoder = _app_state
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Boids Display Encoder") });
 
-        {
-            let mut scope = simulation_profiler.scope("Render Boids", &mut display_encoder, &_app_state.device);
-            let screen_render_pass = &mut scope.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: _output_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
-                })],
-                depth_stencil_attachment: None,
-                ..Default::default()
-            });
-            oxyde::fit_viewport_to_gui_available_rect(screen_render_pass, _app_state);
-
-            screen_render_pass.set_pipeline(&self.render_pipeline);
-            screen_render_pass.set_vertex_buffer(0, vertices_buffer.slice(..));
-            screen_render_pass.set_vertex_buffer(1, self.sorting_id_buffer.slice(..));
-            screen_render_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
-            screen_render_pass.set_bind_group(
-                1,
-                if self.ping_pong_state {
-                    &self.pong_bind_group
-                } else {
-                    &self.ping_bind_group
-                },
-                &[],
-            );
-            screen_render_pass.draw(0..3, 0..boids_count);
+        let read_only_bind_group = if self.ping_pong_state {
+            &self.boids_buffers.pong_bind_group
+        } else {
+            &self.boids_buffers.ping_bind_group
+        };
+
+        match &mut self.display {
+            BoidsDisplay::Flat(render_pipeline, obstacles_renderer, predators_renderer) => {
+                let mut scope = simulation_profiler.scope("Render Boids", &mut display_encoder, &_app_state.device);
+                let screen_render_pass = &mut scope.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Render Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: _output_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                    })],
+                    depth_stencil_attachment: None,
+                    ..Default::default()
+                });
+                oxyde::fit_viewport_to_gui_available_rect(screen_render_pass, _app_state);
+
+                obstacles_renderer.draw(screen_render_pass, simulation_parameters_uniform_buffer);
+
+                screen_render_pass.set_pipeline(render_pipeline);
+                screen_render_pass.set_vertex_buffer(0, vertices_buffer.slice(..));
+                screen_render_pass.set_vertex_buffer(1, self.sorting_id_buffer.slice(..));
+                screen_render_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
+                screen_render_pass.set_bind_group(1, read_only_bind_group, &[]);
+                screen_render_pass.set_bind_group(2, &self.species_buffers.display_bind_group, &[]);
+                screen_render_pass.draw(0..3, 0..boids_count);
+
+                predators_renderer.draw(screen_render_pass, simulation_parameters_uniform_buffer);
+            },
+            BoidsDisplay::Perspective(perspective_boids_renderer) => perspective_boids_renderer.encode(
+                _app_state,
+                &mut display_encoder,
+                _output_view,
+                simulation_parameters_uniform_buffer,
+                &self.sorting_id_buffer,
+                boids_count,
+                read_only_bind_group,
+                &self.species_buffers.display_bind_group,
+                simulation_profiler,
+            ),
         }
 
         // Why only one resolve_queries on the last encoder works ?
@@ -492,146 +524,172 @@ impl SimulationStrategy for GpuSpatialPartitioningStrategy {
 
         Ok(())
     }
+
+    fn set_rule_set(&mut self, device: &wgpu::Device, rule_set: RuleSet) {
+        (self.naive_compute_pipeline, self.grid_compute_pipeline) =
+            create_compute_pipelines(device, self.dimension, rule_set, &self.compute_pipeline_layout);
+    }
+
+    fn set_boids_state(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, state: &BoidsState) {
+        if state.boids_count() != self.boids_buffers.boids_count {
+            self.reallocate_boids_buffers(device, state.boids_count());
+        }
+        state.write_buffers(queue, self.boids_buffers.current_buffers(self.ping_pong_state), self.dimension, self.grid_size);
+    }
+
+    fn boids_state(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> BoidsState {
+        BoidsState::read_buffers(device, queue, self.boids_buffers.current_buffers(self.ping_pong_state), self.dimension)
+    }
+
+    fn current_buffers(&self) -> &[wgpu::Buffer; 6] { self.boids_buffers.current_buffers(self.ping_pong_state) }
+
+    fn display_ui(&mut self, ui: &mut egui::Ui) {
+        if let BoidsDisplay::Perspective(perspective_boids_renderer) = &mut self.display {
+            perspective_boids_renderer.display_ui(ui);
+        }
+    }
 }
 
 pub fn create_gpu_spatial_partitioning_strategy (
+    dimension: Dimension,
+    rule_set: RuleSet,
     device: &wgpu::Device,
     config: &wgpu::SurfaceConfiguration,
     init_parameters_uniform_buffer: &UniformBufferWrapper<InitParametersUniformBufferContent>,
     simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
-    use_spatial_partitioning: bool,
 ) -> Box<dyn SimulationStrategy> {
     
     let initial_boids_count = simulation_parameters_uniform_buffer.content().boids_count;
-    let cell_id_staging_buffer = StagingBufferWrapper::new(device, initial_boids_count as usize);
-
-    let (
-        _,
-        _,
-        cell_id_ping_buffer,
-        _,
-        _,
-        cell_id_pong_buffer,
-        ping_pong_bind_group_layout_builder_descriptor,
-        ping_pong_bind_group,
-        pong_ping_bind_group,
-        read_only_bind_group_layout_builder_descriptor,
-        ping_bind_group,
-        pong_bind_group,
-    ) = create_boids_buffers_and_bind_groups(
+
+    let boids_bind_group_layouts = BoidsBindGroupLayouts::new(
         device,
-        initial_boids_count,
+        dimension,
         wgpu::ShaderStages::COMPUTE,
-        wgpu::ShaderStages::VERTEX,
+        // read only boids data is also used by the counting sort
+        wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::COMPUTE,
     );
+    let boids_buffers = BoidsBuffers::new(device, initial_boids_count, &boids_bind_group_layouts);
+    let boids_resize_pass = BoidsResizePass::new(device, dimension, &boids_bind_group_layouts);
 
     // Boids sorting id buffer
-    let sorting_id_staging_buffer =
-        StagingBufferWrapper::new_from_data(device, &(0..initial_boids_count as BoidSortingId).collect::<Vec<_>>());
+    let sorting_id_buffer = create_sorting_id_buffer(device, initial_boids_count);
 
-    let sorting_id_buffer = wgpu::util::DeviceExt::create_buffer_init(
-        device,
-        &wgpu::util::BufferInitDescriptor {
-            label: None,
-            contents: bytemuck::cast_slice(sorting_id_staging_buffer.values_as_slice()),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        },
-    );
+    let obstacles_buffers = ObstaclesBuffers::new(device);
+    let species_buffers = SpeciesBuffers::new(device);
+    let predators_buffers = PredatorsBuffers::new(device, &init_predators(dimension, init_parameters_uniform_buffer.content().seed));
+    let pointer_force_buffer = PointerForceBuffer::new(device);
 
     // bind group for sorting_id
     let sorting_id_bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
         .add_binding_compute(wgpu::BindingType::Buffer {
             ty: wgpu::BufferBindingType::Storage { read_only: true },
             has_dynamic_offset: false,
-            min_binding_size: wgpu::BufferSize::new(sorting_id_staging_buffer.bytes_size() as u64),
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<BoidSortingId>() as u64),
         })
         .create(device, None);
 
-    let sorting_id_bind_group = binding_builder::BindGroupBuilder::new(&sorting_id_bind_group_layout_with_desc)
-        .resource(sorting_id_buffer.as_entire_binding())
-        .create(device, Some("sorting_id_bind_group"));
+    let sorting_id_bind_group = create_sorting_id_bind_group(device, &sorting_id_bind_group_layout_with_desc, &sorting_id_buffer);
 
-    let grid_size = simulation_parameters_uniform_buffer.content().grid_size as usize;
+    let (environment_bind_group_layout_with_desc, environment_bind_group) =
+        create_environment_bind_group(device, &obstacles_buffers, &species_buffers, &predators_buffers, &pointer_force_buffer);
 
-    println!("grid_size: {}", grid_size);
-    let boids_per_cell_count_staging_buffer = StagingBufferWrapper::new_from_data(device, &vec![0; grid_size * grid_size + 1]);
-
-    let boids_per_cell_count_buffer = wgpu::util::DeviceExt::create_buffer_init(
-        device,
-        &wgpu::util::BufferInitDescriptor {
-            label: None,
-            contents: bytemuck::cast_slice(boids_per_cell_count_staging_buffer.values_as_slice()),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        },
-    );
+    let grid_size = simulation_parameters_uniform_buffer.content().grid_size;
 
     // bind group for boid per cell count using binding_builder
     let boids_per_cell_count_bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
         .add_binding_compute(wgpu::BindingType::Buffer {
             ty: wgpu::BufferBindingType::Storage { read_only: true },
             has_dynamic_offset: false,
-            min_binding_size: wgpu::BufferSize::new(boids_per_cell_count_staging_buffer.bytes_size() as u64),
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<u32>() as u64),
         })
         .create(device, None);
 
-    let boids_per_cell_count_bind_group = binding_builder::BindGroupBuilder::new(&boids_per_cell_count_bind_group_layout_with_desc)
-        .resource(boids_per_cell_count_buffer.as_entire_binding())
-        .create(device, Some("boids_per_cell_count_bind_group"));
-    
-    let compute_shader_source = if use_spatial_partitioning { include_str!("../../shaders/computeGrid.wgsl") } else { include_str!("../../shaders/computeNative.wgsl") };
-    
-    let source = WGSLShaderBuilder::new(compute_shader_source.to_string())
-        .add_include_from_folder("shaders")
-        .build()
-        .unwrap();
-
-    let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Compute Shader"),
-        source,
-    });
-    
-    let init_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Init Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("../../shaders/init.wgsl").into()),
-    });
+    let (boids_per_cell_count_buffer, boids_per_cell_count_bind_group) =
+        create_boids_per_cell_count_buffer_and_bind_group(device, &boids_per_cell_count_bind_group_layout_with_desc, dimension, grid_size);
 
-    let display_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Display Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("../../shaders/display.wgsl").into()),
-    });
+    let counting_sort = GpuCountingSort::new(
+        device,
+        dimension,
+        &boids_bind_group_layouts.read_only.layout,
+        simulation_parameters_uniform_buffer.layout(),
+        &sorting_id_buffer,
+        &boids_per_cell_count_buffer,
+        cell_count_from_grid_size(dimension, grid_size),
+    );
+    
+    let init_shader = create_shader_module(device, "Init Shader", include_str!("../../shaders/init.wgsl"), dimension);
 
-    let (init_pipeline, compute_pipeline, render_pipeline) = create_pipelines(
+    let (init_pipeline, naive_compute_pipeline, grid_compute_pipeline, compute_pipeline_layout) = create_pipelines(
         device,
-        config,
-        &display_shader,
-        &compute_shader,
+        dimension,
+        rule_set,
         &init_shader,
-        &ping_pong_bind_group_layout_builder_descriptor.layout,
-        &read_only_bind_group_layout_builder_descriptor.layout,
+        &boids_bind_group_layouts.ping_pong.layout,
         &sorting_id_bind_group_layout_with_desc.layout,
         &boids_per_cell_count_bind_group_layout_with_desc.layout,
+        &environment_bind_group_layout_with_desc.layout,
         init_parameters_uniform_buffer.layout(),
         simulation_parameters_uniform_buffer.layout(),
     );
 
+    let predators_pass = GpuPredatorsPass::new(
+        device,
+        dimension,
+        simulation_parameters_uniform_buffer.layout(),
+        &boids_bind_group_layouts.read_only.layout,
+        &predators_buffers,
+    );
+
+    let display = match dimension {
+        Dimension::Two => {
+            let display_shader = create_shader_module(device, "Display Shader", include_str!("../../shaders/display.wgsl"), dimension);
+            BoidsDisplay::Flat(
+                create_render_pipeline(
+                    device,
+                    config,
+                    &display_shader,
+                    &boids_bind_group_layouts.read_only.layout,
+                    simulation_parameters_uniform_buffer.layout(),
+                    &species_buffers.display_bind_group_layout_with_desc.layout,
+                ),
+                ObstaclesRenderer::new(device, config, simulation_parameters_uniform_buffer.layout(), &obstacles_buffers),
+                Box::new(PredatorsRenderer::new(device, config, simulation_parameters_uniform_buffer.layout(), &predators_buffers)),
+            )
+        },
+        Dimension::Three => BoidsDisplay::Perspective(Box::new(PerspectiveBoidsRenderer::new(
+            device,
+            config,
+            &boids_bind_group_layouts.read_only.layout,
+            simulation_parameters_uniform_buffer.layout(),
+            &species_buffers.display_bind_group_layout_with_desc.layout,
+            &predators_buffers,
+        ))),
+    };
+
     Box::new(GpuSpatialPartitioningStrategy {
-        render_pipeline,
-        compute_pipeline,
+        dimension,
+        display,
+        compute_pipeline_layout,
+        naive_compute_pipeline,
+        grid_compute_pipeline,
         init_pipeline,
-        sorting_id_staging_buffer,
+        sorting_id_bind_group_layout_with_desc,
         sorting_id_buffer,
         sorting_id_bind_group,
-        boids_per_cell_count_staging_buffer,
-        cell_id_staging_buffer,
+        obstacles_buffers,
+        species_buffers,
+        predators_buffers,
+        predators_pass,
+        pointer_force_buffer,
+        environment_bind_group,
+        grid_size,
+        boids_per_cell_count_bind_group_layout_with_desc,
         boids_per_cell_count_buffer,
         boids_per_cell_count_bind_group,
+        counting_sort,
         ping_pong_state: true,
-        ping_pong_bind_group,
-        pong_ping_bind_group,
-        ping_bind_group,
-        pong_bind_group,
-        cell_id_ping_buffer,
-        cell_id_pong_buffer,
-        use_spatial_partitioning,
+        boids_bind_group_layouts,
+        boids_resize_pass,
+        boids_buffers,
     })
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use anyhow::{bail, Result};
+use oxyde::egui;
+
+use super::{
+    boids_state::BoidsState,
+    cpu_reference_strategy::{hash1, pcg_hash, pick_species, random_float},
+    parameters::InitParametersUniformBufferContent,
+    species::SpeciesBufferContent,
+    Dimension,
+};
+
+// Brightness and hue (in radians, none for grey pixels) of each pixel of a PNG, rows from top to bottom
+pub struct ImageMask {
+    width: u32,
+    height: u32,
+    brightness: Vec<f32>,
+    hues: Vec<Option<f32>>,
+}
+
+// Hue of the HSV model, none when the pixel has no chroma
+fn hue(r: f32, g: f32, b: f32) -> Option<f32> {
+    let max = r.max(g).max(b);
+    let chroma = max - r.min(g).min(b);
+    if chroma <= 0.0 {
+        return None;
+    }
+    let sector = if max == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    Some(sector * std::f32::consts::FRAC_PI_3)
+}
+
+impl ImageMask {
+    pub fn load(path: &str) -> Result<Self> {
+        let mut decoder = png::Decoder::new(std::fs::File::open(path)?);
+        // 8 bits per channel, palettes expanded to RGB
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+
+        let channels = info.color_type.samples();
+        let (brightness, hues) = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| {
+                let pixel: Vec<f32> = pixel.iter().map(|value| *value as f32 / 255.0).collect();
+                // Transparent pixels are dark
+                let (r, g, b, alpha) = match pixel[..] {
+                    [grey] => (grey, grey, grey, 1.0),
+                    [grey, alpha] => (grey, grey, grey, alpha),
+                    [r, g, b] => (r, g, b, 1.0),
+                    [r, g, b, alpha] => (r, g, b, alpha),
+                    _ => unreachable!(),
+                };
+                ((0.299 * r + 0.587 * g + 0.114 * b) * alpha, hue(r, g, b))
+            })
+            .unzip();
+
+        Ok(Self { width: info.width, height: info.height, brightness, hues })
+    }
+
+    // Boids sampled with a density proportional to the brightness, the image fitted in the middle of the domain
+    // In 3D the image lies in the z = 0.5 plane
+    pub fn sample(
+        &self,
+        boids_count: u32,
+        init_parameters: &InitParametersUniformBufferContent,
+        hue_sets_heading: bool,
+        species: &[SpeciesBufferContent],
+        dimension: Dimension,
+    ) -> Result<BoidsState> {
+        let cumulative_brightness: Vec<f32> = self
+            .brightness
+            .iter()
+            .scan(0.0, |total, brightness| {
+                *total += brightness;
+                Some(*total)
+            })
+            .collect();
+        let total_brightness = cumulative_brightness.last().copied().unwrap_or(0.0);
+        if total_brightness <= 0.0 {
+            bail!("the image is black");
+        }
+
+        let scale = 1.0 / self.width.max(self.height) as f32;
+        let mut state = BoidsState::default();
+        for index in 0..boids_count {
+            // Same streams and species as init.wgsl
+            let alterated_index = index.wrapping_mul(142857).wrapping_add(init_parameters.seed);
+            let mut random = pcg_hash(alterated_index);
+
+            let threshold = random_float(&mut random) * total_brightness;
+            let pixel = cumulative_brightness.partition_point(|total| *total <= threshold).min(cumulative_brightness.len() - 1);
+            let column = (pixel as u32 % self.width) as f32 + random_float(&mut random);
+            let row = (pixel as u32 / self.width) as f32 + random_float(&mut random);
+            let x = 0.5 + (column - self.width as f32 * 0.5) * scale;
+            // Image rows go down, the simulation y axis goes up
+            let y = 0.5 - (row - self.height as f32 * 0.5) * scale;
+
+            let random_heading = random_float(&mut random) * std::f32::consts::TAU;
+            let heading = match self.hues[pixel] {
+                Some(hue) if hue_sets_heading => hue,
+                _ => random_heading,
+            };
+
+            let z = match dimension {
+                Dimension::Two => 0.0,
+                Dimension::Three => 0.5,
+            };
+            state.positions.push(nalgebra_glm::vec4(x, y, z, 0.0));
+            state.velocities.push(nalgebra_glm::vec4(heading.cos(), heading.sin(), 0.0, 0.0) * init_parameters.speed);
+            state.species.push(pick_species(species, hash1(alterated_index.wrapping_add(2 * dimension.count()))));
+            state.random_states.push(pcg_hash(index ^ pcg_hash(init_parameters.seed)));
+            state.ids.push(index);
+        }
+        Ok(state)
+    }
+}
+
+pub struct ImageMaskSettings {
+    pub path: String,
+    // Colored pixels give their hue as heading, grey ones a random heading
+    pub hue_sets_heading: bool,
+}
+
+impl Default for ImageMaskSettings {
+    fn default() -> Self {
+        Self {
+            path: "mask.png".to_string(),
+            hue_sets_heading: false,
+        }
+    }
+}
+
+impl ImageMaskSettings {
+    // True when the boids should be initialized from the image
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.horizontal(|ui| {
+            ui.label("Image mask (PNG)");
+            ui.text_edit_singleline(&mut self.path);
+        });
+        ui.checkbox(&mut self.hue_sets_heading, "Hue sets heading");
+        ui.button("Init boids from image").clicked()
+    }
+}
@@ -0,0 +1,333 @@
+use oxyde::{
+    egui, wgpu,
+    wgpu_utils::{binding_builder, uniform_buffer::UniformBufferWrapper},
+};
+
+use super::{shader_builder::create_shader_module, wgsl_struct::wgsl_struct, wgsl_struct::WgslStruct, Dimension, SimulationParametersUniformBufferContent};
+
+// Obstacles buffers have a fixed capacity so the bind groups using them never need to be recreated
+pub const MAX_OBSTACLES: usize = 64;
+pub const MAX_OBSTACLE_VERTICES: usize = 512;
+
+// Step of the central differences giving the gradient of the signed distance
+pub const OBSTACLE_GRADIENT_STEP: f32 = 0.001;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum ObstacleKind {
+    Circle = 0,
+    // Axis aligned box
+    Box = 1,
+    Polygon = 2,
+}
+
+impl ObstacleKind {
+    pub const ALL: [ObstacleKind; 3] = [ObstacleKind::Circle, ObstacleKind::Box, ObstacleKind::Polygon];
+
+    pub fn from_u32(value: u32) -> Self { Self::ALL.into_iter().find(|kind| *kind as u32 == value).unwrap_or(ObstacleKind::Circle) }
+
+    fn wgsl_name(&self) -> &'static str {
+        match self {
+            ObstacleKind::Circle => "CIRCLE",
+            ObstacleKind::Box => "BOX",
+            ObstacleKind::Polygon => "POLYGON",
+        }
+    }
+
+    // Constants to compare with the kind of an obstacle in shaders
+    pub fn wgsl_constants() -> String {
+        Self::ALL
+            .iter()
+            .map(|kind| format!("const OBSTACLE_KIND_{}: u32 = {}u;\n", kind.wgsl_name(), *kind as u32))
+            .collect()
+    }
+}
+
+wgsl_struct! {
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct ObstacleBufferContent as "Obstacle" {
+        pub center: nalgebra_glm::Vec2,
+        // Bounds around the center of boxes and polygons
+        pub half_extents: nalgebra_glm::Vec2,
+        // Only used by circles
+        pub radius: f32,
+        // ObstacleKind as u32
+        pub kind: u32,
+        // Range of the polygon vertices in the obstacle vertices buffer
+        pub first_vertex: u32,
+        pub vertex_count: u32,
+    }
+}
+
+// Declarations shaders get along with the simulation parameters
+pub fn wgsl_declarations() -> String {
+    ObstacleBufferContent::wgsl_declaration()
+        + &ObstacleKind::wgsl_constants()
+        + &format!("const OBSTACLE_GRADIENT_STEP: f32 = {:?};\n", OBSTACLE_GRADIENT_STEP)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ObstacleShape {
+    Circle { radius: f32 },
+    Box { half_extents: nalgebra_glm::Vec2 },
+    // Vertices relative to the center, in order
+    Polygon { vertices: Vec<nalgebra_glm::Vec2> },
+}
+
+impl ObstacleShape {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ObstacleShape::Circle { .. } => "Circle",
+            ObstacleShape::Box { .. } => "Box",
+            ObstacleShape::Polygon { .. } => "Polygon",
+        }
+    }
+}
+
+fn regular_polygon(sides: u32, radius: f32) -> Vec<nalgebra_glm::Vec2> {
+    (0..sides)
+        .map(|side| {
+            let angle = side as f32 / sides as f32 * std::f32::consts::TAU;
+            nalgebra_glm::vec2(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Obstacle {
+    pub center: nalgebra_glm::Vec2,
+    pub shape: ObstacleShape,
+}
+
+// Obstacles as uploaded to the GPU, polygons vertices are absolute positions
+#[derive(Default)]
+pub struct ObstaclesBufferContents {
+    pub obstacles: Vec<ObstacleBufferContent>,
+    pub vertices: Vec<nalgebra_glm::Vec2>,
+}
+
+// Static obstacles of the xy plane, boids avoid them from their signed distance (in 3D they are columns along z)
+#[derive(Default)]
+pub struct Obstacles {
+    pub list: Vec<Obstacle>,
+}
+
+impl Obstacles {
+    // Obstacles above the buffers capacity are ignored
+    pub fn buffer_contents(&self) -> ObstaclesBufferContents {
+        let mut contents = ObstaclesBufferContents::default();
+
+        for obstacle in &self.list {
+            if contents.obstacles.len() == MAX_OBSTACLES {
+                break;
+            }
+
+            let mut content = ObstacleBufferContent {
+                center: obstacle.center,
+                half_extents: nalgebra_glm::Vec2::zeros(),
+                radius: 0.0,
+                kind: ObstacleKind::Circle as u32,
+                first_vertex: 0,
+                vertex_count: 0,
+            };
+
+            match &obstacle.shape {
+                ObstacleShape::Circle { radius } => content.radius = *radius,
+                ObstacleShape::Box { half_extents } => {
+                    content.kind = ObstacleKind::Box as u32;
+                    content.half_extents = *half_extents;
+                },
+                ObstacleShape::Polygon { vertices } => {
+                    if vertices.len() < 3 || contents.vertices.len() + vertices.len() > MAX_OBSTACLE_VERTICES {
+                        continue;
+                    }
+                    content.kind = ObstacleKind::Polygon as u32;
+                    content.half_extents = vertices.iter().fold(nalgebra_glm::Vec2::zeros(), |half_extents, vertex| half_extents.sup(&vertex.abs()));
+                    content.first_vertex = contents.vertices.len() as u32;
+                    content.vertex_count = vertices.len() as u32;
+                    contents.vertices.extend(vertices.iter().map(|vertex| obstacle.center + vertex));
+                },
+            }
+
+            contents.obstacles.push(content);
+        }
+
+        contents
+    }
+
+    pub fn count(&self) -> u32 { self.buffer_contents().obstacles.len() as u32 }
+
+    pub fn display_ui(&mut self, ui: &mut egui::Ui, dimension: Dimension) {
+        egui::CollapsingHeader::new("Obstacles").default_open(true).show(ui, |ui| {
+            // Shapes are in the xy plane, the 3D simulation ignores them
+            if dimension == Dimension::Three {
+                ui.label("Obstacles are only simulated in 2D");
+                return;
+            }
+
+            let center = nalgebra_glm::vec2(0.5, 0.5);
+            ui.horizontal(|ui| {
+                if ui.button("Add circle").clicked() {
+                    self.list.push(Obstacle { center, shape: ObstacleShape::Circle { radius: 0.05 } });
+                }
+                if ui.button("Add box").clicked() {
+                    self.list.push(Obstacle { center, shape: ObstacleShape::Box { half_extents: nalgebra_glm::vec2(0.08, 0.04) } });
+                }
+                if ui.button("Add polygon").clicked() {
+                    self.list.push(Obstacle { center, shape: ObstacleShape::Polygon { vertices: regular_polygon(5, 0.06) } });
+                }
+            });
+
+            let mut removed_index = None;
+            for (index, obstacle) in self.list.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label(obstacle.shape.label());
+                        ui.add(egui::DragValue::new(&mut obstacle.center.x).speed(0.005).clamp_range(0.0..=1.0).prefix("x: "));
+                        ui.add(egui::DragValue::new(&mut obstacle.center.y).speed(0.005).clamp_range(0.0..=1.0).prefix("y: "));
+                        if ui.button("Remove").clicked() {
+                            removed_index = Some(index);
+                        }
+                    });
+
+                    match &mut obstacle.shape {
+                        ObstacleShape::Circle { radius } => {
+                            ui.add(egui::Slider::new(radius, 0.005..=0.5).prefix("Radius"));
+                        },
+                        ObstacleShape::Box { half_extents } => {
+                            ui.add(egui::Slider::new(&mut half_extents.x, 0.005..=0.5).prefix("Half width"));
+                            ui.add(egui::Slider::new(&mut half_extents.y, 0.005..=0.5).prefix("Half height"));
+                        },
+                        // Vertices relative to the center, the polygon may be concave as long as its edges don't cross
+                        ObstacleShape::Polygon { vertices } => {
+                            for vertex in vertices.iter_mut() {
+                                ui.horizontal(|ui| {
+                                    ui.add(egui::DragValue::new(&mut vertex.x).speed(0.002).clamp_range(-0.5..=0.5).prefix("x: "));
+                                    ui.add(egui::DragValue::new(&mut vertex.y).speed(0.002).clamp_range(-0.5..=0.5).prefix("y: "));
+                                });
+                            }
+                        },
+                    }
+                });
+            }
+
+            if let Some(index) = removed_index {
+                self.list.remove(index);
+            }
+
+            if self.list.len() > MAX_OBSTACLES {
+                ui.label(format!("Only the first {} obstacles are simulated", MAX_OBSTACLES));
+            }
+        });
+    }
+}
+
+// Fixed capacity buffers, written each frame from the obstacles list
+pub struct ObstaclesBuffers {
+    pub obstacles_buffer: wgpu::Buffer,
+    pub vertices_buffer: wgpu::Buffer,
+}
+
+impl ObstaclesBuffers {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let create_buffer = |label: &str, size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        Self {
+            obstacles_buffer: create_buffer("Obstacles", MAX_OBSTACLES * std::mem::size_of::<ObstacleBufferContent>()),
+            vertices_buffer: create_buffer("Obstacle vertices", MAX_OBSTACLE_VERTICES * std::mem::size_of::<nalgebra_glm::Vec2>()),
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, contents: &ObstaclesBufferContents) {
+        queue.write_buffer(&self.obstacles_buffer, 0, bytemuck::cast_slice(&contents.obstacles));
+        queue.write_buffer(&self.vertices_buffer, 0, bytemuck::cast_slice(&contents.vertices));
+    }
+
+    pub fn obstacles_binding_type() -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ObstacleBufferContent>() as u64),
+        }
+    }
+
+    pub fn vertices_binding_type() -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<nalgebra_glm::Vec2>() as u64),
+        }
+    }
+}
+
+// Second render pipeline of the flat display, drawing each obstacle as a quad cut by its signed distance
+pub struct ObstaclesRenderer {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ObstaclesRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_configuration: &wgpu::SurfaceConfiguration,
+        simulation_parameters_uniform_buffer_layout: &wgpu::BindGroupLayout,
+        obstacles_buffers: &ObstaclesBuffers,
+    ) -> Self {
+        let visibility = wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT;
+        let bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding(visibility, ObstaclesBuffers::obstacles_binding_type())
+            .add_binding(visibility, ObstaclesBuffers::vertices_binding_type())
+            .create(device, Some("Obstacles (display)"));
+
+        let bind_group = binding_builder::BindGroupBuilder::new(&bind_group_layout_with_desc)
+            .resource(obstacles_buffers.obstacles_buffer.as_entire_binding())
+            .resource(obstacles_buffers.vertices_buffer.as_entire_binding())
+            .create(device, Some("Obstacles (display)"));
+
+        let display_shader = create_shader_module(device, "Display Obstacles Shader", include_str!("../../shaders/displayObstacles.wgsl"), Dimension::Two);
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Obstacles Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Obstacles Pipeline Layout"),
+                bind_group_layouts: &[simulation_parameters_uniform_buffer_layout, &bind_group_layout_with_desc.layout],
+                push_constant_ranges: &[],
+            })),
+            vertex: wgpu::VertexState { module: &display_shader, entry_point: "vs_main", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &display_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_configuration.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self { render_pipeline, bind_group }
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        simulation_parameters_uniform_buffer: &'a UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..simulation_parameters_uniform_buffer.content().obstacle_count);
+    }
+}
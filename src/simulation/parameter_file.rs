@@ -0,0 +1,134 @@
+use anyhow::{bail, Context, Result};
+
+use super::{
+    parameters::{BoundaryMode, InitMode, InitParametersUniformBufferContent, NeighborhoodMode, SteeringModel, WeightingKernel},
+    wgsl_struct::WgslStruct,
+    SimulationParametersUniformBufferContent,
+};
+
+enum Section {
+    Init,
+    Simulation,
+}
+
+// Values allowed for the u32 fields holding an enum, with their labels
+fn enum_variants(section: &Section, field: &str) -> Option<Vec<(u32, &'static str)>> {
+    match (section, field) {
+        (Section::Init, "init_mode") => Some(InitMode::ALL.iter().map(|mode| (*mode as u32, mode.label())).collect()),
+        (Section::Simulation, "boundary_mode") => Some(BoundaryMode::ALL.iter().map(|mode| (*mode as u32, mode.label())).collect()),
+        (Section::Simulation, "steering_model") => Some(SteeringModel::ALL.iter().map(|model| (*model as u32, model.label())).collect()),
+        (Section::Simulation, "cohesion_kernel" | "alignment_kernel" | "separation_kernel") => {
+            Some(WeightingKernel::ALL.iter().map(|kernel| (*kernel as u32, kernel.label())).collect())
+        },
+        (Section::Simulation, "neighborhood_mode") => Some(NeighborhoodMode::ALL.iter().map(|mode| (*mode as u32, mode.label())).collect()),
+        (Section::Simulation, "predator_catch") => Some(vec![(0, "off"), (1, "on")]),
+        _ => None,
+    }
+}
+
+// Text file overriding some parameters, fields named as in the WGSL structs under an [init] or a [simulation] section:
+//
+// # Comment
+// [init]
+// seed = 42
+// init_mode = 2
+// [simulation]
+// view_radius = 0.03
+//
+// Enums are given by their u32 value and must be one of the variants, vectors as comma separated components
+pub fn load_parameter_file(
+    path: &str,
+    init_parameters: &mut InitParametersUniformBufferContent,
+    simulation_parameters: &mut SimulationParametersUniformBufferContent,
+) -> Result<()> {
+    let content = std::fs::read_to_string(path)?;
+
+    let mut section = None;
+    for (line_index, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = Some(match name.trim() {
+                "init" => Section::Init,
+                "simulation" => Section::Simulation,
+                name => bail!("{}:{}: unknown section {}", path, line_index + 1, name),
+            });
+            continue;
+        }
+
+        let Some((field, value)) = line.split_once('=') else {
+            bail!("{}:{}: expected field = value", path, line_index + 1);
+        };
+        let (field, value) = (field.trim(), value.trim());
+        let Some(section) = &section else {
+            bail!("{}:{}: {} outside of a section", path, line_index + 1, field);
+        };
+
+        // Shaders would silently fall back to some default on other values
+        if let Some(variants) = enum_variants(section, field) {
+            if !value.parse::<u32>().is_ok_and(|value| variants.iter().any(|(variant, _)| *variant == value)) {
+                let expected: Vec<String> = variants.iter().map(|(variant, label)| format!("{} ({})", variant, label)).collect();
+                bail!("{}:{}: invalid {} {}, expected one of {}", path, line_index + 1, field, value, expected.join(", "));
+            }
+        }
+
+        match section {
+            Section::Init => init_parameters.set_field(field, value),
+            Section::Simulation => simulation_parameters.set_field(field, value),
+        }
+        .with_context(|| format!("{}:{}", path, line_index + 1))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Loads the given content over the default parameters
+    fn load(name: &str, content: &str) -> Result<(InitParametersUniformBufferContent, SimulationParametersUniformBufferContent)> {
+        let path = std::env::temp_dir().join(format!("rusty_boids_{}_{}.params", std::process::id(), name)).to_string_lossy().into_owned();
+        std::fs::write(&path, content).unwrap();
+        let mut init_parameters = InitParametersUniformBufferContent::default();
+        let mut simulation_parameters = SimulationParametersUniformBufferContent::default();
+        let result = load_parameter_file(&path, &mut init_parameters, &mut simulation_parameters);
+        let _ = std::fs::remove_file(&path);
+        result.map(|_| (init_parameters, simulation_parameters))
+    }
+
+    fn error_of(name: &str, content: &str) -> String { format!("{:#}", load(name, content).err().unwrap()) }
+
+    #[test]
+    fn valid_file_sets_the_given_fields() {
+        let content = "# Comment\n[init]\nseed = 42\ninit_mode = 2 # Ring\n\n[simulation]\nview_radius = 0.03\nboundary_mode = 1\n";
+        let (init_parameters, simulation_parameters) = load("valid", content).unwrap();
+
+        assert_eq!(init_parameters.seed, 42);
+        assert_eq!(init_parameters.init_mode, 2);
+        assert_eq!(simulation_parameters.view_radius, 0.03);
+        assert_eq!(simulation_parameters.boundary_mode, 1);
+        assert_eq!(simulation_parameters.max_speed, SimulationParametersUniformBufferContent::default().max_speed);
+    }
+
+    #[test]
+    fn unknown_field_is_refused() {
+        let error = error_of("unknown_field", "[simulation]\nview_radius = 0.03\nno_such_field = 1\n");
+        assert!(error.contains(":3") && error.contains("no field no_such_field"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_enum_value_is_refused() {
+        let error = error_of("unknown_enum", "[simulation]\nboundary_mode = 42\n");
+        assert!(error.contains(":2") && error.contains("invalid boundary_mode 42"), "{}", error);
+    }
+
+    #[test]
+    fn malformed_number_is_refused() {
+        let error = error_of("malformed_number", "[init]\nspeed = 0.0.1\n");
+        assert!(error.contains(":2") && error.contains("value 0.0.1 for speed"), "{}", error);
+    }
+}
@@ -1,26 +1,500 @@
 use oxyde::egui;
 
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Default)]
-pub struct InitParametersUniformBufferContent {
-    pub seed: u32,
-}
-
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct SimulationParametersUniformBufferContent {
-    pub view_radius: f32,
-    //  The separation radius is the view radius times this factor
-    pub separation_radius_factor: f32,
-    pub cohesion_scale: f32,
-    pub aligment_scale: f32,
-    pub separation_scale: f32,
-    // Grid size is the number of cells per axis
-    pub repulsion_margin: f32,
-    pub repulsion_strength: f32,
-    // to move in an other buffer as they are only used for the grid optimization
-    pub boids_count: u32,
-    pub grid_size: u32,
+use super::{predators::MAX_PREDATORS, wgsl_struct::wgsl_struct, Dimension};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum BoundaryMode {
+    // Steer back boids in the repulsion margin
+    SoftRepulsion = 0,
+    // Bounce on the edges
+    Reflect = 1,
+    // Periodic domain, boids leaving on one side come back on the other
+    Toroidal = 2,
+}
+
+impl BoundaryMode {
+    pub const ALL: [BoundaryMode; 3] = [BoundaryMode::SoftRepulsion, BoundaryMode::Reflect, BoundaryMode::Toroidal];
+
+    pub fn from_u32(value: u32) -> Self { Self::ALL.into_iter().find(|mode| *mode as u32 == value).unwrap_or(BoundaryMode::SoftRepulsion) }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BoundaryMode::SoftRepulsion => "Soft repulsion",
+            BoundaryMode::Reflect => "Reflect",
+            BoundaryMode::Toroidal => "Toroidal",
+        }
+    }
+
+    fn wgsl_name(&self) -> &'static str {
+        match self {
+            BoundaryMode::SoftRepulsion => "SOFT_REPULSION",
+            BoundaryMode::Reflect => "REFLECT",
+            BoundaryMode::Toroidal => "TOROIDAL",
+        }
+    }
+
+    // Constants to compare with simulationParameters.boundary_mode in shaders
+    pub fn wgsl_constants() -> String {
+        Self::ALL
+            .iter()
+            .map(|mode| format!("const BOUNDARY_MODE_{}: u32 = {}u;\n", mode.wgsl_name(), *mode as u32))
+            .collect()
+    }
+}
+
+// How the rules forces make the new velocity
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum SteeringModel {
+    // Rules forces are scaled and summed as the acceleration
+    Additive = 0,
+    // Each rule steers toward a desired velocity at max speed, the steering (desired minus current velocity) being limited to the max steering strength
+    Reynolds = 1,
+}
+
+impl SteeringModel {
+    pub const ALL: [SteeringModel; 2] = [SteeringModel::Additive, SteeringModel::Reynolds];
+
+    pub fn from_u32(value: u32) -> Self { Self::ALL.into_iter().find(|model| *model as u32 == value).unwrap_or(SteeringModel::Additive) }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SteeringModel::Additive => "Additive",
+            SteeringModel::Reynolds => "Reynolds",
+        }
+    }
+
+    fn wgsl_name(&self) -> &'static str {
+        match self {
+            SteeringModel::Additive => "ADDITIVE",
+            SteeringModel::Reynolds => "REYNOLDS",
+        }
+    }
+
+    // Constants to compare with simulationParameters.steering_model in shaders
+    pub fn wgsl_constants() -> String {
+        Self::ALL
+            .iter()
+            .map(|model| format!("const STEERING_MODEL_{}: u32 = {}u;\n", model.wgsl_name(), *model as u32))
+            .collect()
+    }
+}
+
+// Weight of a neighbor contribution from its distance normalized by the rule radius
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum WeightingKernel {
+    Uniform = 0,
+    // 1 - d
+    Linear = 1,
+    // 1 / d², capped for very close neighbors
+    InverseSquare = 2,
+    // Standard deviation of half the radius
+    Gaussian = 3,
+}
+
+impl WeightingKernel {
+    pub const ALL: [WeightingKernel; 4] = [
+        WeightingKernel::Uniform,
+        WeightingKernel::Linear,
+        WeightingKernel::InverseSquare,
+        WeightingKernel::Gaussian,
+    ];
+
+    pub fn from_u32(value: u32) -> Self { Self::ALL.into_iter().find(|kernel| *kernel as u32 == value).unwrap_or(WeightingKernel::Uniform) }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            WeightingKernel::Uniform => "Uniform",
+            WeightingKernel::Linear => "Linear",
+            WeightingKernel::InverseSquare => "Inverse square",
+            WeightingKernel::Gaussian => "Gaussian",
+        }
+    }
+
+    fn wgsl_name(&self) -> &'static str {
+        match self {
+            WeightingKernel::Uniform => "UNIFORM",
+            WeightingKernel::Linear => "LINEAR",
+            WeightingKernel::InverseSquare => "INVERSE_SQUARE",
+            WeightingKernel::Gaussian => "GAUSSIAN",
+        }
+    }
+
+    // Constants to compare with the kernels of the simulation parameters in shaders
+    pub fn wgsl_constants() -> String {
+        Self::ALL
+            .iter()
+            .map(|kernel| format!("const WEIGHTING_KERNEL_{}: u32 = {}u;\n", kernel.wgsl_name(), *kernel as u32))
+            .collect()
+    }
+
+    fn combo_box(ui: &mut egui::Ui, label: &str, value: &mut u32) {
+        let mut kernel = WeightingKernel::from_u32(*value);
+        egui::ComboBox::from_label(label).selected_text(kernel.label()).show_ui(ui, |ui| {
+            for other_kernel in WeightingKernel::ALL {
+                ui.selectable_value(&mut kernel, other_kernel, other_kernel.label());
+            }
+        });
+        *value = kernel as u32;
+    }
+}
+
+// Upper bound of the topological neighbors count, size of the per thread neighbors list in shaders
+pub const MAX_TOPOLOGICAL_NEIGHBORS: u32 = 16;
+
+// Which neighbors a boid interacts with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum NeighborhoodMode {
+    // Every visible boid within the view radius
+    Metric = 0,
+    // The k nearest visible boids whatever their distance
+    Topological = 1,
+}
+
+impl NeighborhoodMode {
+    pub const ALL: [NeighborhoodMode; 2] = [NeighborhoodMode::Metric, NeighborhoodMode::Topological];
+
+    pub fn from_u32(value: u32) -> Self { Self::ALL.into_iter().find(|mode| *mode as u32 == value).unwrap_or(NeighborhoodMode::Metric) }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NeighborhoodMode::Metric => "Metric",
+            NeighborhoodMode::Topological => "Topological",
+        }
+    }
+
+    fn wgsl_name(&self) -> &'static str {
+        match self {
+            NeighborhoodMode::Metric => "METRIC",
+            NeighborhoodMode::Topological => "TOPOLOGICAL",
+        }
+    }
+
+    // Constants to compare with simulationParameters.neighborhood_mode in shaders
+    pub fn wgsl_constants() -> String {
+        Self::ALL
+            .iter()
+            .map(|mode| format!("const NEIGHBORHOOD_MODE_{}: u32 = {}u;\n", mode.wgsl_name(), *mode as u32))
+            .chain(std::iter::once(format!("const MAX_TOPOLOGICAL_NEIGHBORS: u32 = {}u;\n", MAX_TOPOLOGICAL_NEIGHBORS)))
+            .collect()
+    }
+}
+
+// Rules computing the new velocity from the neighbors, unlike the other modes it is chosen at pipeline creation
+// The compute shaders include the rule set file as rules.wgsl
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum RuleSet {
+    // Separation, alignment and cohesion
+    Reynolds,
+    // Nested zones of repulsion, orientation and attraction with a limited turning rate and angular noise
+    Couzin,
+    // Alignment only at constant speed with uniform angular noise
+    Vicsek,
+}
+
+impl RuleSet {
+    pub const ALL: [RuleSet; 3] = [RuleSet::Reynolds, RuleSet::Couzin, RuleSet::Vicsek];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RuleSet::Reynolds => "Reynolds",
+            RuleSet::Couzin => "Couzin",
+            RuleSet::Vicsek => "Vicsek",
+        }
+    }
+
+    pub fn shader_include(&self) -> &'static str {
+        match self {
+            RuleSet::Reynolds => include_str!("../../shaders/flocking.wgsl"),
+            RuleSet::Couzin => include_str!("../../shaders/couzin.wgsl"),
+            RuleSet::Vicsek => include_str!("../../shaders/vicsek.wgsl"),
+        }
+    }
+}
+
+// Initial distribution of the boids
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum InitMode {
+    // Random positions and headings
+    Uniform = 0,
+    // Normal distributions around random centers
+    GaussianClusters = 1,
+    // Boids milling counterclockwise around the center
+    Ring = 2,
+    // Random positions, all boids heading the same way
+    Aligned = 3,
+    // Regular grid filling the domain, random headings
+    Lattice = 4,
+    // Centered disc (a ball in 3D), part of the boids aligned
+    PolarizedDisc = 5,
+}
+
+impl InitMode {
+    pub const ALL: [InitMode; 6] = [
+        InitMode::Uniform,
+        InitMode::GaussianClusters,
+        InitMode::Ring,
+        InitMode::Aligned,
+        InitMode::Lattice,
+        InitMode::PolarizedDisc,
+    ];
+
+    pub fn from_u32(value: u32) -> Self { Self::ALL.into_iter().find(|mode| *mode as u32 == value).unwrap_or(InitMode::Uniform) }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            InitMode::Uniform => "Uniform",
+            InitMode::GaussianClusters => "Gaussian clusters",
+            InitMode::Ring => "Ring (milling)",
+            InitMode::Aligned => "Aligned",
+            InitMode::Lattice => "Lattice",
+            InitMode::PolarizedDisc => "Polarized disc",
+        }
+    }
+
+    fn wgsl_name(&self) -> &'static str {
+        match self {
+            InitMode::Uniform => "UNIFORM",
+            InitMode::GaussianClusters => "GAUSSIAN_CLUSTERS",
+            InitMode::Ring => "RING",
+            InitMode::Aligned => "ALIGNED",
+            InitMode::Lattice => "LATTICE",
+            InitMode::PolarizedDisc => "POLARIZED_DISC",
+        }
+    }
+
+    // Constants to compare with initParameters.init_mode in shaders
+    pub fn wgsl_constants() -> String {
+        Self::ALL
+            .iter()
+            .map(|mode| format!("const INIT_MODE_{}: u32 = {}u;\n", mode.wgsl_name(), *mode as u32))
+            .collect()
+    }
+}
+
+// WGSL declarations of these structs are generated, shaders get them with //!include initParameters.wgsl and //!include simulationParameters.wgsl
+wgsl_struct! {
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct InitParametersUniformBufferContent as "InitParameters" {
+        pub seed: u32,
+        pub init_mode: u32,
+        pub speed: f32,
+        pub cluster_count: u32,
+        // Standard deviation of the clusters
+        pub cluster_sigma: f32,
+        pub ring_radius: f32,
+        pub ring_width: f32,
+        // Heading of the aligned boids in degrees, in the xy plane
+        pub heading: f32,
+        pub disc_radius: f32,
+        // Expected norm of the mean heading of the disc boids, the share of them that is aligned
+        pub polarization: f32,
+    }
+}
+
+impl Default for InitParametersUniformBufferContent {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            init_mode: InitMode::Uniform as u32,
+            speed: 0.04,
+            cluster_count: 4,
+            cluster_sigma: 0.05,
+            ring_radius: 0.3,
+            ring_width: 0.05,
+            heading: 0.0,
+            disc_radius: 0.25,
+            polarization: 0.8,
+        }
+    }
+}
+
+impl InitParametersUniformBufferContent {
+    // Seed and init mode, the settings of the current mode only
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::DragValue::new(&mut self.seed).speed(1).prefix("Seed: "));
+
+        let mut mode = InitMode::from_u32(self.init_mode);
+        egui::ComboBox::from_label("Init mode").selected_text(mode.label()).show_ui(ui, |ui| {
+            for other_mode in InitMode::ALL {
+                ui.selectable_value(&mut mode, other_mode, other_mode.label());
+            }
+        });
+        self.init_mode = mode as u32;
+
+        ui.add(
+            egui::Slider::from_get_set(0.0..=0.2, |optional_value: Option<f64>| {
+                if let Some(v) = optional_value {
+                    self.speed = v as f32;
+                }
+                self.speed as f64
+            })
+            .prefix("Initial speed"),
+        );
+        match mode {
+            InitMode::Uniform | InitMode::Lattice => {},
+            InitMode::GaussianClusters => {
+                ui.add(
+                    egui::Slider::from_get_set(1.0..=32.0, |optional_value: Option<f64>| {
+                        if let Some(v) = optional_value {
+                            self.cluster_count = v as u32;
+                        }
+                        self.cluster_count as f64
+                    })
+                    .prefix("Clusters")
+                    .integer(),
+                );
+
+                ui.add(
+                    egui::Slider::from_get_set(0.001..=0.3, |optional_value: Option<f64>| {
+                        if let Some(v) = optional_value {
+                            self.cluster_sigma = v as f32;
+                        }
+                        self.cluster_sigma as f64
+                    })
+                    .prefix("Cluster σ"),
+                );
+            },
+            InitMode::Ring => {
+                ui.add(
+                    egui::Slider::from_get_set(0.01..=0.5, |optional_value: Option<f64>| {
+                        if let Some(v) = optional_value {
+                            self.ring_radius = v as f32;
+                        }
+                        self.ring_radius as f64
+                    })
+                    .prefix("Ring radius"),
+                );
+
+                ui.add(
+                    egui::Slider::from_get_set(0.0..=0.3, |optional_value: Option<f64>| {
+                        if let Some(v) = optional_value {
+                            self.ring_width = v as f32;
+                        }
+                        self.ring_width as f64
+                    })
+                    .prefix("Ring width"),
+                );
+            },
+            InitMode::Aligned => {
+                ui.add(
+                    egui::Slider::from_get_set(0.0..=360.0, |optional_value: Option<f64>| {
+                        if let Some(v) = optional_value {
+                            self.heading = v as f32;
+                        }
+                        self.heading as f64
+                    })
+                    .prefix("Heading (degrees)"),
+                );
+            },
+            InitMode::PolarizedDisc => {
+                ui.add(
+                    egui::Slider::from_get_set(0.01..=0.5, |optional_value: Option<f64>| {
+                        if let Some(v) = optional_value {
+                            self.disc_radius = v as f32;
+                        }
+                        self.disc_radius as f64
+                    })
+                    .prefix("Disc radius"),
+                );
+
+                ui.add(
+                    egui::Slider::from_get_set(0.0..=360.0, |optional_value: Option<f64>| {
+                        if let Some(v) = optional_value {
+                            self.heading = v as f32;
+                        }
+                        self.heading as f64
+                    })
+                    .prefix("Heading (degrees)"),
+                );
+
+                ui.add(
+                    egui::Slider::from_get_set(0.0..=1.0, |optional_value: Option<f64>| {
+                        if let Some(v) = optional_value {
+                            self.polarization = v as f32;
+                        }
+                        self.polarization as f64
+                    })
+                    .prefix("Polarization"),
+                );
+            },
+        }
+    }
+}
+
+wgsl_struct! {
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct SimulationParametersUniformBufferContent as "SimulationParameters" {
+        pub view_radius: f32,
+        //  The separation radius is the view radius times this factor
+        pub separation_radius_factor: f32,
+        pub cohesion_scale: f32,
+        pub aligment_scale: f32,
+        pub separation_scale: f32,
+        // Grid size is the number of cells per axis
+        pub repulsion_margin: f32,
+        pub repulsion_strength: f32,
+        // to move in an other buffer as they are only used for the grid optimization
+        pub boids_count: u32,
+        pub grid_size: u32,
+        // Duration of one simulation step, set by the time step settings
+        pub delta_time: f32,
+        // BoundaryMode as u32
+        pub boundary_mode: u32,
+        pub max_speed: f32,
+        pub min_speed: f32,
+        // Only used by the Reynolds steering model
+        pub max_steering_strength: f32,
+        // SteeringModel as u32
+        pub steering_model: u32,
+        // Full view angle in degrees, neighbors behind the boid in the remaining blind spot are ignored
+        pub field_of_view: f32,
+        // WeightingKernel as u32 for each rule
+        pub cohesion_kernel: u32,
+        pub alignment_kernel: u32,
+        pub separation_kernel: u32,
+        // Obstacles avoidance starts at the margin from their edge
+        pub obstacle_margin: f32,
+        pub obstacle_strength: f32,
+        // Number of obstacles in the obstacles buffer, set from the obstacles list
+        pub obstacle_count: u32,
+        // Number of species in the species buffer, set from the species settings
+        pub species_count: u32,
+        // Number of active predators in the predators buffer
+        pub predator_count: u32,
+        // Predators always move at this speed
+        pub predator_speed: f32,
+        pub predator_view_radius: f32,
+        pub predator_steering_strength: f32,
+        // Boids closer than this to a predator flee instead of flocking
+        pub predator_alarm_radius: f32,
+        pub predator_flee_strength: f32,
+        // Boids closer than the catch radius to a predator are respawned at a random position, if predator_catch is not 0
+        pub predator_catch: u32,
+        pub predator_catch_radius: f32,
+        // NeighborhoodMode as u32
+        pub neighborhood_mode: u32,
+        // Number of neighbors in topological mode, at most MAX_TOPOLOGICAL_NEIGHBORS
+        pub topological_neighbor_count: u32,
+        // Rings of cells the grid strategy searches around the boid cell for topological neighbors,
+        // bounds the search in sparse regions where fewer than k boids are found
+        pub topological_max_ring: u32,
+        // Couzin zones are the view radius times these factors, attraction going up to the view radius
+        pub couzin_repulsion_radius_factor: f32,
+        pub couzin_orientation_radius_factor: f32,
+        // Degrees per second, a step turns by this times the delta time
+        pub couzin_turning_rate: f32,
+        // Standard deviation of the angular noise in degrees after one second, a step gets this times the square root of the delta time
+        pub couzin_noise: f32,
+        // Amplitude η of the Vicsek noise in radians, from 0 (ordered) to 2π (disordered)
+        pub vicsek_noise: f32,
+        // Boid drawn in white, followed by its id whatever its slot in the buffers (u32::MAX for none)
+        pub highlighted_boid_id: u32,
+    }
 }
 
 impl Default for SimulationParametersUniformBufferContent {
@@ -35,16 +509,127 @@ impl Default for SimulationParametersUniformBufferContent {
             repulsion_margin: 0.1,
             repulsion_strength: 0.5,
             boids_count: 1024,
-            grid_size: grid_size_from_view_radius(view_radius),
+            grid_size: grid_size_from_cell_size(view_radius, Dimension::Two),
+            delta_time: TimeStepSettings::default().fixed_delta_time,
+            boundary_mode: BoundaryMode::SoftRepulsion as u32,
+            max_speed: 0.1,
+            min_speed: 0.01,
+            max_steering_strength: 0.2,
+            steering_model: SteeringModel::Additive as u32,
+            // Full view angle in degrees, a half angle whose cosine is -0.6
+            field_of_view: 253.74,
+            cohesion_kernel: WeightingKernel::Uniform as u32,
+            alignment_kernel: WeightingKernel::Uniform as u32,
+            separation_kernel: WeightingKernel::Uniform as u32,
+            obstacle_margin: 0.05,
+            obstacle_strength: 1.0,
+            obstacle_count: 0,
+            species_count: 1,
+            predator_count: 0,
+            predator_speed: 0.12,
+            predator_view_radius: 0.2,
+            predator_steering_strength: 0.5,
+            predator_alarm_radius: 0.08,
+            predator_flee_strength: 2.0,
+            predator_catch: 0,
+            predator_catch_radius: 0.005,
+            neighborhood_mode: NeighborhoodMode::Metric as u32,
+            // Starlings interact with 6 to 7 neighbors
+            topological_neighbor_count: 7,
+            topological_max_ring: 8,
+            couzin_repulsion_radius_factor: 0.2,
+            couzin_orientation_radius_factor: 0.6,
+            // 6 and 3 degrees per step at 60 steps per second
+            couzin_turning_rate: 360.0,
+            couzin_noise: 23.0,
+            vicsek_noise: 0.5,
+            highlighted_boid_id: u32::MAX,
+        }
+    }
+}
+
+// Smallest cell size allowed to keep the grid (and the boids per cell count buffer) at a reasonable size,
+// at most 200x200 cells in 2D and 50x50x50 in 3D
+fn min_cell_size(dimension: Dimension) -> f32 {
+    match dimension {
+        Dimension::Two => 0.005,
+        Dimension::Three => 0.02,
+    }
+}
+
+pub fn grid_size_from_cell_size(cell_size: f32, dimension: Dimension) -> u32 { (1.0 / cell_size.max(min_cell_size(dimension))).ceil() as u32 }
+
+// Cell size of the grid used for spatial partitioning, the grid size in the simulation parameters is derived from it
+// The neighbors search range adapts to the cell size so any size gives the same result
+pub struct GridSettings {
+    pub cell_size_follows_view_radius: bool,
+    pub cell_size: f32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            cell_size_follows_view_radius: true,
+            cell_size: SimulationParametersUniformBufferContent::default().view_radius,
         }
     }
 }
 
-pub fn grid_size_from_view_radius(view_radius: f32) -> u32 { (1.0 / view_radius).ceil() as u32 }
+impl GridSettings {
+    pub fn grid_size(&self, view_radius: f32, dimension: Dimension) -> u32 {
+        if self.cell_size_follows_view_radius {
+            grid_size_from_cell_size(view_radius, dimension)
+        } else {
+            grid_size_from_cell_size(self.cell_size, dimension)
+        }
+    }
+
+    pub fn display_ui(&mut self, ui: &mut egui::Ui, grid_size: u32, dimension: Dimension) {
+        egui::CollapsingHeader::new("Grid settings").default_open(true).show(ui, |ui| {
+            ui.checkbox(&mut self.cell_size_follows_view_radius, "Cell size follows view radius");
+
+            ui.add_enabled(
+                !self.cell_size_follows_view_radius,
+                egui::Slider::from_get_set(min_cell_size(dimension) as f64..=0.5, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.cell_size = v as f32;
+                    }
+                    self.cell_size as f64
+                })
+                .prefix("Cell size"),
+            );
+
+            ui.label(match dimension {
+                Dimension::Two => format!("Grid size: {}x{}", grid_size, grid_size),
+                Dimension::Three => format!("Grid size: {}x{}x{}", grid_size, grid_size, grid_size),
+            });
+        });
+    }
+}
 
 impl SimulationParametersUniformBufferContent {
     pub fn display_ui(&mut self, ui: &mut egui::Ui) {
         egui::CollapsingHeader::new("Simulation settings").default_open(true).show(ui, |ui| {
+            // Boids buffers are reallocated by the simulation strategy when the count changes
+            ui.add(
+                egui::DragValue::new(&mut self.boids_count)
+                    .speed(16)
+                    .clamp_range(1..=1_000_000)
+                    .prefix("Boids count: "),
+            );
+
+            ui.horizontal(|ui| {
+                let mut highlight = self.highlighted_boid_id != u32::MAX;
+                ui.checkbox(&mut highlight, "Highlight boid");
+                if !highlight {
+                    self.highlighted_boid_id = u32::MAX;
+                } else {
+                    let mut id = self.highlighted_boid_id.min(self.boids_count.saturating_sub(1));
+                    ui.add(egui::DragValue::new(&mut id).clamp_range(0..=self.boids_count.saturating_sub(1)).prefix("Id: "));
+                    self.highlighted_boid_id = id;
+                }
+            });
+
             ui.add(
                 egui::Slider::from_get_set(0.0..=1.0, |optional_value: Option<f64>| {
                     if let Some(v) = optional_value {
@@ -65,6 +650,39 @@ impl SimulationParametersUniformBufferContent {
                 .prefix("view radius"),
             );
 
+            ui.add(
+                egui::Slider::from_get_set(0.0..=360.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.field_of_view = v as f32;
+                    }
+                    self.field_of_view as f64
+                })
+                .prefix("Field of view (degrees)"),
+            );
+
+            let mut neighborhood_mode = NeighborhoodMode::from_u32(self.neighborhood_mode);
+            egui::ComboBox::from_label("Neighborhood").selected_text(neighborhood_mode.label()).show_ui(ui, |ui| {
+                for mode in NeighborhoodMode::ALL {
+                    ui.selectable_value(&mut neighborhood_mode, mode, mode.label());
+                }
+            });
+            self.neighborhood_mode = neighborhood_mode as u32;
+
+            ui.add_enabled(
+                neighborhood_mode == NeighborhoodMode::Topological,
+                egui::DragValue::new(&mut self.topological_neighbor_count)
+                    .clamp_range(1..=MAX_TOPOLOGICAL_NEIGHBORS)
+                    .prefix("Neighbors count: "),
+            );
+            ui.add_enabled(
+                neighborhood_mode == NeighborhoodMode::Topological,
+                egui::DragValue::new(&mut self.topological_max_ring).clamp_range(1..=100).prefix("Max rings of cells: "),
+            );
+
+            WeightingKernel::combo_box(ui, "Cohesion kernel", &mut self.cohesion_kernel);
+            WeightingKernel::combo_box(ui, "Alignment kernel", &mut self.alignment_kernel);
+            WeightingKernel::combo_box(ui, "Separation kernel", &mut self.separation_kernel);
+
             ui.add(
                 egui::Slider::from_get_set(0.0..=1.0, |optional_value: Option<f64>| {
                     if let Some(v) = optional_value {
@@ -113,6 +731,353 @@ impl SimulationParametersUniformBufferContent {
                 })
                 .prefix("Repulsion strength"),
             );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=0.2, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.obstacle_margin = v as f32;
+                    }
+                    self.obstacle_margin as f64
+                })
+                .prefix("Obstacle margin"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=5.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.obstacle_strength = v as f32;
+                    }
+                    self.obstacle_strength as f64
+                })
+                .prefix("Obstacle strength"),
+            );
+
+            let mut boundary_mode = BoundaryMode::from_u32(self.boundary_mode);
+            egui::ComboBox::from_label("Boundary mode").selected_text(boundary_mode.label()).show_ui(ui, |ui| {
+                for mode in BoundaryMode::ALL {
+                    ui.selectable_value(&mut boundary_mode, mode, mode.label());
+                }
+            });
+            self.boundary_mode = boundary_mode as u32;
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=0.5, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.max_speed = v as f32;
+                        self.min_speed = self.min_speed.min(self.max_speed);
+                    }
+                    self.max_speed as f64
+                })
+                .prefix("Max speed"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=0.5, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.min_speed = v as f32;
+                        self.max_speed = self.max_speed.max(self.min_speed);
+                    }
+                    self.min_speed as f64
+                })
+                .prefix("Min speed"),
+            );
+
+            let mut steering_model = SteeringModel::from_u32(self.steering_model);
+            egui::ComboBox::from_label("Steering model").selected_text(steering_model.label()).show_ui(ui, |ui| {
+                for model in SteeringModel::ALL {
+                    ui.selectable_value(&mut steering_model, model, model.label());
+                }
+            });
+            self.steering_model = steering_model as u32;
+
+            ui.add_enabled(
+                steering_model == SteeringModel::Reynolds,
+                egui::Slider::from_get_set(0.0..=1.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.max_steering_strength = v as f32;
+                    }
+                    self.max_steering_strength as f64
+                })
+                .prefix("Max steering strength"),
+            );
+        });
+    }
+
+    pub fn display_couzin_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Couzin model").default_open(true).show(ui, |ui| {
+            // Zones stay nested
+            ui.add(
+                egui::Slider::from_get_set(0.0..=1.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.couzin_repulsion_radius_factor = v as f32;
+                        self.couzin_orientation_radius_factor = self.couzin_orientation_radius_factor.max(self.couzin_repulsion_radius_factor);
+                    }
+                    self.couzin_repulsion_radius_factor as f64
+                })
+                .prefix("Repulsion radius factor"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=1.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.couzin_orientation_radius_factor = v as f32;
+                        self.couzin_repulsion_radius_factor = self.couzin_repulsion_radius_factor.min(self.couzin_orientation_radius_factor);
+                    }
+                    self.couzin_orientation_radius_factor as f64
+                })
+                .prefix("Orientation radius factor"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=1080.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.couzin_turning_rate = v as f32;
+                    }
+                    self.couzin_turning_rate as f64
+                })
+                .prefix("Turning rate (degrees per second)"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=360.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.couzin_noise = v as f32;
+                    }
+                    self.couzin_noise as f64
+                })
+                .prefix("Angular noise (degrees per √second)"),
+            );
         });
     }
+
+    pub fn display_vicsek_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Vicsek model").default_open(true).show(ui, |ui| {
+            ui.label("Boids move at the max speed");
+            ui.add(
+                egui::Slider::from_get_set(0.0..=std::f64::consts::TAU, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.vicsek_noise = v as f32;
+                    }
+                    self.vicsek_noise as f64
+                })
+                .prefix("Noise η (radians)"),
+            );
+        });
+    }
+
+    pub fn display_predators_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Predators").default_open(true).show(ui, |ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.predator_count)
+                    .clamp_range(0..=MAX_PREDATORS as u32)
+                    .prefix("Predators count: "),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=0.5, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.predator_speed = v as f32;
+                    }
+                    self.predator_speed as f64
+                })
+                .prefix("Predator speed"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=0.5, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.predator_view_radius = v as f32;
+                    }
+                    self.predator_view_radius as f64
+                })
+                .prefix("Predator view radius"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=2.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.predator_steering_strength = v as f32;
+                    }
+                    self.predator_steering_strength as f64
+                })
+                .prefix("Predator steering strength"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=0.3, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.predator_alarm_radius = v as f32;
+                    }
+                    self.predator_alarm_radius as f64
+                })
+                .prefix("Alarm radius"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=5.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.predator_flee_strength = v as f32;
+                    }
+                    self.predator_flee_strength as f64
+                })
+                .prefix("Flee strength"),
+            );
+
+            let mut catch = self.predator_catch != 0;
+            ui.checkbox(&mut catch, "Caught boids respawn");
+            self.predator_catch = catch as u32;
+
+            ui.add_enabled(
+                catch,
+                egui::Slider::from_get_set(0.0..=0.05, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.predator_catch_radius = v as f32;
+                    }
+                    self.predator_catch_radius as f64
+                })
+                .prefix("Catch radius"),
+            );
+        });
+    }
+}
+
+// Converts the frame time into simulation steps, each step integrating delta_time of the simulation parameters
+pub struct TimeStepSettings {
+    // Fixed: constant delta time with the remaining time carried over to the next frames
+    // Variable: frame time split in equal steps no longer than the fixed delta time
+    pub fixed_timestep: bool,
+    pub fixed_delta_time: f32,
+    pub time_scale: f32,
+    // Above this, simulation time is dropped rather than trying to catch up
+    pub max_substeps: u32,
+    accumulator: f32,
+}
+
+impl Default for TimeStepSettings {
+    fn default() -> Self {
+        Self {
+            fixed_timestep: true,
+            fixed_delta_time: 1.0 / 60.0,
+            time_scale: 1.0,
+            max_substeps: 4,
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl TimeStepSettings {
+    // Number of steps to run this frame and the delta time of each of them
+    pub fn advance(&mut self, frame_time: f32) -> (u32, f32) {
+        let simulation_time = frame_time * self.time_scale;
+
+        if self.fixed_timestep {
+            self.accumulator += simulation_time;
+            let steps = (self.accumulator / self.fixed_delta_time).floor() as u32;
+            if steps > self.max_substeps {
+                self.accumulator = 0.0;
+                return (self.max_substeps, self.fixed_delta_time);
+            }
+            self.accumulator -= steps as f32 * self.fixed_delta_time;
+            (steps, self.fixed_delta_time)
+        } else {
+            self.accumulator = 0.0;
+            let steps = (simulation_time / self.fixed_delta_time).ceil() as u32;
+            if steps > self.max_substeps {
+                return (self.max_substeps, self.fixed_delta_time);
+            }
+            (steps, simulation_time / steps.max(1) as f32)
+        }
+    }
+
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Time step settings").default_open(true).show(ui, |ui| {
+            ui.checkbox(&mut self.fixed_timestep, "Fixed time step");
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=4.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.time_scale = v as f32;
+                    }
+                    self.time_scale as f64
+                })
+                .prefix("Time scale"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.001..=0.05, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.fixed_delta_time = v as f32;
+                    }
+                    self.fixed_delta_time as f64
+                })
+                .prefix("Fixed delta time"),
+            );
+
+            ui.add(egui::DragValue::new(&mut self.max_substeps).clamp_range(1..=32).prefix("Max substeps: "));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Powers of two so the accumulated times are exact
+    fn time_step_settings(fixed_timestep: bool) -> TimeStepSettings {
+        TimeStepSettings {
+            fixed_timestep,
+            fixed_delta_time: 0.25,
+            time_scale: 1.0,
+            max_substeps: 4,
+            accumulator: 0.0,
+        }
+    }
+
+    #[test]
+    fn fixed_time_step_carries_the_leftover_over() {
+        let mut settings = time_step_settings(true);
+        assert_eq!(settings.advance(0.125), (0, 0.25));
+        assert_eq!(settings.advance(0.375), (2, 0.25));
+        assert_eq!(settings.advance(0.125), (0, 0.25));
+        assert_eq!(settings.advance(0.125), (1, 0.25));
+
+        // Scaled before accumulating
+        settings.time_scale = 2.0;
+        assert_eq!(settings.advance(0.375), (3, 0.25));
+    }
+
+    #[test]
+    fn fixed_time_step_drops_the_time_above_the_max_substeps() {
+        let mut settings = time_step_settings(true);
+        assert_eq!(settings.advance(0.125), (0, 0.25));
+        assert_eq!(settings.advance(10.0), (4, 0.25));
+        // Nothing left to catch up
+        assert_eq!(settings.advance(0.125), (0, 0.25));
+        assert_eq!(settings.advance(0.125), (1, 0.25));
+
+        // Exactly the max substeps is not capped and keeps the leftover
+        assert_eq!(settings.advance(1.125), (4, 0.25));
+        assert_eq!(settings.advance(0.125), (1, 0.25));
+    }
+
+    #[test]
+    fn variable_time_step_splits_the_frame_time() {
+        let mut settings = time_step_settings(false);
+        assert_eq!(settings.advance(0.125), (1, 0.125));
+        assert_eq!(settings.advance(0.25), (1, 0.25));
+        assert_eq!(settings.advance(0.75), (3, 0.25));
+        assert_eq!(settings.advance(0.625), (3, 0.625 / 3.0));
+        assert_eq!(settings.advance(0.0), (0, 0.0));
+
+        // Too long frames run the max substeps of the fixed delta time
+        assert_eq!(settings.advance(10.0), (4, 0.25));
+
+        // Leftover of the fixed time step is dropped when switching
+        let mut settings = time_step_settings(true);
+        settings.advance(0.125);
+        settings.fixed_timestep = false;
+        assert_eq!(settings.advance(0.25), (1, 0.25));
+        settings.fixed_timestep = true;
+        assert_eq!(settings.advance(0.125), (0, 0.25));
+    }
 }
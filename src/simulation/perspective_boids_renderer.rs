@@ -0,0 +1,265 @@
+use oxyde::{egui, wgpu, wgpu_utils::{binding_builder, uniform_buffer::UniformBufferWrapper}, AppState};
+
+use super::{
+    camera::{CameraUniformBufferContent, OrbitCamera},
+    predators::PredatorsBuffers,
+    shader_builder::create_shader_module,
+    types::*,
+    Dimension,
+    SimulationParametersUniformBufferContent,
+};
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct MeshVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+// Dart pointing toward +z, same size as the 2d triangle
+// Flat shaded so each triangle has its own vertices with the face normal
+fn create_boid_mesh() -> Vec<MeshVertex> {
+    let nose = nalgebra_glm::vec3(0.0, 0.0, 0.02);
+    let tail_left = nalgebra_glm::vec3(-0.01, 0.0, -0.02);
+    let tail_right = nalgebra_glm::vec3(0.01, 0.0, -0.02);
+    let top = nalgebra_glm::vec3(0.0, 0.005, -0.015);
+    let bottom = nalgebra_glm::vec3(0.0, -0.005, -0.015);
+
+    let triangles = [
+        [nose, tail_left, top],
+        [nose, top, tail_right],
+        [nose, tail_right, bottom],
+        [nose, bottom, tail_left],
+        [tail_left, tail_right, top],
+        [tail_left, bottom, tail_right],
+    ];
+
+    triangles
+        .iter()
+        .flat_map(|triangle| {
+            let normal = nalgebra_glm::normalize(&nalgebra_glm::cross(&(triangle[1] - triangle[0]), &(triangle[2] - triangle[0])));
+            triangle.map(|position| MeshVertex { position: position.into(), normal: normal.into() })
+        })
+        .collect()
+}
+
+fn create_depth_texture_view(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("Boids depth texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+// Meshes lit by the vertex shader, tested against the depth buffer
+fn create_mesh_render_pipeline(
+    device: &wgpu::Device,
+    surface_configuration: &wgpu::SurfaceConfiguration,
+    label: &str,
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    vertex_buffers: &[wgpu::VertexBufferLayout],
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        })),
+
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: surface_configuration.format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        // The mesh is lit on both sides so no culling is needed
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+const MESH_VERTEX_BUFFER_LAYOUT: wgpu::VertexBufferLayout = wgpu::VertexBufferLayout {
+    array_stride: std::mem::size_of::<MeshVertex>() as _,
+    step_mode: wgpu::VertexStepMode::Vertex,
+    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+};
+
+// Draws each 3D boid as an instance of a dart mesh seen from an orbit camera, with a depth buffer
+// Predators are bigger red darts from the same mesh
+pub struct PerspectiveBoidsRenderer {
+    render_pipeline: wgpu::RenderPipeline,
+    predators_render_pipeline: wgpu::RenderPipeline,
+    predators_bind_group: wgpu::BindGroup,
+    camera: OrbitCamera,
+    camera_uniform_buffer: UniformBufferWrapper<CameraUniformBufferContent>,
+    mesh_vertex_buffer: wgpu::Buffer,
+    mesh_vertex_count: u32,
+    // Recreated when the surface is resized
+    depth_texture_view: wgpu::TextureView,
+    depth_texture_size: (u32, u32),
+}
+
+impl PerspectiveBoidsRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_configuration: &wgpu::SurfaceConfiguration,
+        read_only_bind_group_layout: &wgpu::BindGroupLayout,
+        simulation_parameters_uniform_buffer_layout: &wgpu::BindGroupLayout,
+        species_display_bind_group_layout: &wgpu::BindGroupLayout,
+        predators_buffers: &PredatorsBuffers,
+    ) -> Self {
+        let camera_uniform_buffer = UniformBufferWrapper::new(device, CameraUniformBufferContent::default(), wgpu::ShaderStages::VERTEX);
+
+        let mesh = create_boid_mesh();
+        let mesh_vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Boid mesh"),
+                contents: bytemuck::cast_slice(&mesh),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        );
+
+        let display_shader = create_shader_module(device, "Display 3D Shader", include_str!("../../shaders/display3d.wgsl"), Dimension::Three);
+
+        let render_pipeline = create_mesh_render_pipeline(
+            device,
+            surface_configuration,
+            "Render 3D Pipeline",
+            &display_shader,
+            &[
+                simulation_parameters_uniform_buffer_layout,
+                read_only_bind_group_layout,
+                camera_uniform_buffer.layout(),
+                species_display_bind_group_layout,
+            ],
+            &[
+                MESH_VERTEX_BUFFER_LAYOUT,
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<BoidSortingId>() as _,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![2 => Uint32],
+                },
+            ],
+        );
+
+        let predators_bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding(wgpu::ShaderStages::VERTEX, PredatorsBuffers::predators_binding_type(true))
+            .create(device, Some("Predators (3D display)"));
+        let predators_bind_group = binding_builder::BindGroupBuilder::new(&predators_bind_group_layout_with_desc)
+            .resource(predators_buffers.predators_buffer.as_entire_binding())
+            .create(device, Some("Predators (3D display)"));
+
+        let predators_display_shader =
+            create_shader_module(device, "Display 3D Predators Shader", include_str!("../../shaders/displayPredators3d.wgsl"), Dimension::Three);
+        let predators_render_pipeline = create_mesh_render_pipeline(
+            device,
+            surface_configuration,
+            "Render 3D Predators Pipeline",
+            &predators_display_shader,
+            &[simulation_parameters_uniform_buffer_layout, &predators_bind_group_layout_with_desc.layout, camera_uniform_buffer.layout()],
+            &[MESH_VERTEX_BUFFER_LAYOUT],
+        );
+
+        Self {
+            render_pipeline,
+            predators_render_pipeline,
+            predators_bind_group,
+            camera: OrbitCamera::default(),
+            camera_uniform_buffer,
+            mesh_vertex_buffer,
+            mesh_vertex_count: mesh.len() as u32,
+            depth_texture_view: create_depth_texture_view(device, surface_configuration.width, surface_configuration.height),
+            depth_texture_size: (surface_configuration.width, surface_configuration.height),
+        }
+    }
+
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) {
+        self.camera.display_ui(ui);
+    }
+
+    pub fn encode(
+        &mut self,
+        app_state: &mut AppState,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        sorting_id_buffer: &wgpu::Buffer,
+        boids_count: u32,
+        read_only_bind_group: &wgpu::BindGroup,
+        species_display_bind_group: &wgpu::BindGroup,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+    ) {
+        let surface_size = (app_state.config.width, app_state.config.height);
+        if surface_size != self.depth_texture_size {
+            self.depth_texture_view = create_depth_texture_view(&app_state.device, surface_size.0, surface_size.1);
+            self.depth_texture_size = surface_size;
+        }
+
+        // Every frame, whether the camera settings are shown or not
+        self.camera.handle_input(app_state.egui_renderer.context());
+
+        // The viewport is fitted to the area left by the GUI, so is the aspect ratio
+        let available_rect = app_state.egui_renderer.context().available_rect();
+        let aspect_ratio = available_rect.width() / available_rect.height().max(1.0);
+        self.camera_uniform_buffer.content_mut().view_projection = self.camera.view_projection(aspect_ratio);
+        self.camera_uniform_buffer.update_content(&app_state.queue);
+
+        let mut scope = simulation_profiler.scope("Render Boids", encoder, &app_state.device);
+        let screen_render_pass = &mut scope.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render 3D Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture_view,
+                depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Discard }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+        oxyde::fit_viewport_to_gui_available_rect(screen_render_pass, app_state);
+
+        screen_render_pass.set_pipeline(&self.render_pipeline);
+        screen_render_pass.set_vertex_buffer(0, self.mesh_vertex_buffer.slice(..));
+        screen_render_pass.set_vertex_buffer(1, sorting_id_buffer.slice(..));
+        screen_render_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
+        screen_render_pass.set_bind_group(1, read_only_bind_group, &[]);
+        screen_render_pass.set_bind_group(2, self.camera_uniform_buffer.bind_group(), &[]);
+        screen_render_pass.set_bind_group(3, species_display_bind_group, &[]);
+        screen_render_pass.draw(0..self.mesh_vertex_count, 0..boids_count);
+
+        screen_render_pass.set_pipeline(&self.predators_render_pipeline);
+        screen_render_pass.set_bind_group(1, &self.predators_bind_group, &[]);
+        screen_render_pass.draw(0..self.mesh_vertex_count, 0..simulation_parameters_uniform_buffer.content().predator_count);
+    }
+}
@@ -0,0 +1,182 @@
+use oxyde::{
+    egui, wgpu,
+    winit::event::{ElementState, MouseButton, WindowEvent},
+};
+
+use super::wgsl_struct::{wgsl_struct, WgslStruct};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum PointerForceMode {
+    None = 0,
+    // Left drag pulls boids toward the cursor
+    Attract = 1,
+    // Right drag pushes them away
+    Repulse = 2,
+}
+
+impl PointerForceMode {
+    pub const ALL: [PointerForceMode; 3] = [PointerForceMode::None, PointerForceMode::Attract, PointerForceMode::Repulse];
+
+    pub fn from_u32(value: u32) -> Self { Self::ALL.into_iter().find(|mode| *mode as u32 == value).unwrap_or(PointerForceMode::None) }
+
+    fn wgsl_name(&self) -> &'static str {
+        match self {
+            PointerForceMode::None => "NONE",
+            PointerForceMode::Attract => "ATTRACT",
+            PointerForceMode::Repulse => "REPULSE",
+        }
+    }
+
+    // Constants to compare with pointerForce.mode in shaders
+    pub fn wgsl_constants() -> String {
+        Self::ALL
+            .iter()
+            .map(|mode| format!("const POINTER_FORCE_MODE_{}: u32 = {}u;\n", mode.wgsl_name(), *mode as u32))
+            .collect()
+    }
+}
+
+wgsl_struct! {
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, PartialEq, Debug)]
+    pub struct PointerForceUniformBufferContent as "PointerForce" {
+        // Cursor in the [0, 1]² simulation space
+        pub position: nalgebra_glm::Vec2,
+        pub radius: f32,
+        pub strength: f32,
+        // PointerForceMode as u32
+        pub mode: u32,
+        pub _padding: u32,
+    }
+}
+
+// Declarations shaders get along with the simulation parameters
+pub fn wgsl_declarations() -> String { PointerForceUniformBufferContent::wgsl_declaration() + &PointerForceMode::wgsl_constants() }
+
+// Temporary attractor or repulsor following the cursor while a mouse button is held over the simulation
+pub struct PointerForce {
+    pub radius: f32,
+    pub strength: f32,
+    // Physical pixels, as given by the window events
+    cursor_position: Option<(f32, f32)>,
+    mode: PointerForceMode,
+}
+
+impl Default for PointerForce {
+    fn default() -> Self {
+        Self {
+            radius: 0.1,
+            strength: 1.0,
+            cursor_position: None,
+            mode: PointerForceMode::None,
+        }
+    }
+}
+
+impl PointerForce {
+    // Presses over the gui are left to egui
+    pub fn handle_window_event(&mut self, event: &WindowEvent, egui_context: &egui::Context) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => self.cursor_position = Some((position.x as f32, position.y as f32)),
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+                self.mode = PointerForceMode::None;
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let mode = match button {
+                    MouseButton::Left => PointerForceMode::Attract,
+                    MouseButton::Right => PointerForceMode::Repulse,
+                    _ => return,
+                };
+                match state {
+                    ElementState::Pressed if !egui_context.wants_pointer_input() => self.mode = mode,
+                    ElementState::Released if self.mode == mode => self.mode = PointerForceMode::None,
+                    _ => {},
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // The cursor is mapped through the viewport of oxyde::fit_viewport_to_gui_available_rect, the simulation space [0, 1]² filling the available rect
+    pub fn buffer_content(&self, egui_context: &egui::Context) -> PointerForceUniformBufferContent {
+        let (position, mode) = match self.cursor_position {
+            Some((x, y)) => {
+                let available_rect = egui_context.available_rect();
+                let cursor = egui::pos2(x, y) / egui_context.pixels_per_point();
+                (
+                    nalgebra_glm::vec2(
+                        (cursor.x - available_rect.min.x) / available_rect.width().max(1.0),
+                        1.0 - (cursor.y - available_rect.min.y) / available_rect.height().max(1.0),
+                    ),
+                    self.mode,
+                )
+            },
+            None => (nalgebra_glm::Vec2::zeros(), PointerForceMode::None),
+        };
+
+        PointerForceUniformBufferContent {
+            position,
+            radius: self.radius,
+            strength: self.strength,
+            mode: mode as u32,
+            _padding: 0,
+        }
+    }
+
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Pointer force").default_open(true).show(ui, |ui| {
+            ui.label("Left drag attracts, right drag repulses");
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=0.5, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.radius = v as f32;
+                    }
+                    self.radius as f64
+                })
+                .prefix("Radius"),
+            );
+
+            ui.add(
+                egui::Slider::from_get_set(0.0..=5.0, |optional_value: Option<f64>| {
+                    if let Some(v) = optional_value {
+                        self.strength = v as f32;
+                    }
+                    self.strength as f64
+                })
+                .prefix("Strength"),
+            );
+        });
+    }
+}
+
+// Written each frame from the pointer force, bound with the other environment buffers
+pub struct PointerForceBuffer {
+    pub buffer: wgpu::Buffer,
+}
+
+impl PointerForceBuffer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pointer force"),
+            size: std::mem::size_of::<PointerForceUniformBufferContent>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self { buffer }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, pointer_force: &PointerForceUniformBufferContent) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(pointer_force));
+    }
+
+    pub fn binding_type() -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<PointerForceUniformBufferContent>() as u64),
+        }
+    }
+}
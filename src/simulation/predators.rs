@@ -0,0 +1,229 @@
+use oxyde::{wgpu, wgpu_utils::{binding_builder, uniform_buffer::UniformBufferWrapper}};
+
+use super::{
+    cpu_reference_strategy::hash1,
+    shader_builder::create_shader_module,
+    wgsl_struct::{wgsl_struct, WgslStruct},
+    Dimension, SimulationParametersUniformBufferContent, WORKGROUP_SIZE,
+};
+
+// Predators buffer has a fixed capacity so the bind groups using it never need to be recreated
+pub const MAX_PREDATORS: usize = 64;
+
+wgsl_struct! {
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, PartialEq, Debug)]
+    pub struct PredatorBufferContent as "Predator" {
+        // vec4 in 2D and 3D, unused components are 0
+        pub position: nalgebra_glm::Vec4,
+        pub velocity: nalgebra_glm::Vec4,
+    }
+}
+
+// Declarations shaders get along with the simulation parameters
+pub fn wgsl_declarations() -> String { PredatorBufferContent::wgsl_declaration() }
+
+// Random positions and velocities like init.wgsl does for boids, with a salted seed so predators don't start on the first boids
+// Every predator of the buffer is initialized so the predators count can grow without a new init
+pub fn init_predators(dimension: Dimension, seed: u32) -> Vec<PredatorBufferContent> {
+    let dimension = dimension.count();
+    (0..MAX_PREDATORS as u32)
+        .map(|index| {
+            let alterated_index = index.wrapping_mul(142857).wrapping_add(seed ^ 0x5bd1e995);
+            let mut position = nalgebra_glm::Vec4::zeros();
+            let mut velocity = nalgebra_glm::Vec4::zeros();
+            for axis in 0..dimension {
+                position[axis as usize] = hash1(alterated_index.wrapping_add(axis));
+                velocity[axis as usize] = hash1(alterated_index.wrapping_add(dimension + axis)) * 2.0 - 1.0;
+            }
+            PredatorBufferContent { position, velocity: velocity.normalize() * 0.04 }
+        })
+        .collect()
+}
+
+// Chevron clearly bigger than the boids triangle, in screen units
+const PREDATOR_MESH: [f32; 12] = [
+    0.0, 0.03, -0.015, -0.02, 0.0, -0.008, //
+    0.0, 0.03, 0.0, -0.008, 0.015, -0.02,
+];
+
+pub struct PredatorsBuffers {
+    pub predators_buffer: wgpu::Buffer,
+}
+
+impl PredatorsBuffers {
+    pub fn new(device: &wgpu::Device, predators: &[PredatorBufferContent]) -> Self {
+        let predators_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Predators"),
+            size: (MAX_PREDATORS * std::mem::size_of::<PredatorBufferContent>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: true,
+        });
+        predators_buffer.slice(..).get_mapped_range_mut().copy_from_slice(bytemuck::cast_slice(predators));
+        predators_buffer.unmap();
+
+        Self { predators_buffer }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, predators: &[PredatorBufferContent]) {
+        queue.write_buffer(&self.predators_buffer, 0, bytemuck::cast_slice(predators));
+    }
+
+    pub fn predators_binding_type(read_only: bool) -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<PredatorBufferContent>() as u64),
+        }
+    }
+}
+
+// Predators chase the nearest boid, each step moves them before the boids
+pub struct GpuPredatorsPass {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl GpuPredatorsPass {
+    pub fn new(
+        device: &wgpu::Device,
+        dimension: Dimension,
+        simulation_parameters_uniform_buffer_layout: &wgpu::BindGroupLayout,
+        read_only_bind_group_layout: &wgpu::BindGroupLayout,
+        predators_buffers: &PredatorsBuffers,
+    ) -> Self {
+        let bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding_compute(PredatorsBuffers::predators_binding_type(false))
+            .create(device, Some("Predators"));
+
+        let bind_group = binding_builder::BindGroupBuilder::new(&bind_group_layout_with_desc)
+            .resource(predators_buffers.predators_buffer.as_entire_binding())
+            .create(device, Some("Predators"));
+
+        let shader = create_shader_module(device, "Predators Shader", include_str!("../../shaders/predators.wgsl"), dimension);
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Predators pipeline"),
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Predators Pipeline"),
+                bind_group_layouts: &[
+                    simulation_parameters_uniform_buffer_layout,
+                    read_only_bind_group_layout,
+                    &bind_group_layout_with_desc.layout,
+                ],
+                push_constant_ranges: &[],
+            })),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        Self { pipeline, bind_group }
+    }
+
+    // Predators look at the boids of the given read only bind group, the source of the step
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        simulation_profiler: &mut wgpu_profiler::GpuProfiler,
+        simulation_parameters_uniform_buffer: &UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+        read_only_bind_group: &wgpu::BindGroup,
+    ) {
+        let predator_count = simulation_parameters_uniform_buffer.content().predator_count;
+        if predator_count == 0 {
+            return;
+        }
+
+        let mut scope = simulation_profiler.scope("Compute Predators", encoder, device);
+        let compute_pass = &mut scope.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Predators Pass"), timestamp_writes: None });
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
+        compute_pass.set_bind_group(1, read_only_bind_group, &[]);
+        compute_pass.set_bind_group(2, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(predator_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+}
+
+// Draws each predator as an instance of the predator mesh, over the flat 2D display
+pub struct PredatorsRenderer {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    mesh_vertex_buffer: wgpu::Buffer,
+}
+
+impl PredatorsRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_configuration: &wgpu::SurfaceConfiguration,
+        simulation_parameters_uniform_buffer_layout: &wgpu::BindGroupLayout,
+        predators_buffers: &PredatorsBuffers,
+    ) -> Self {
+        let bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding(wgpu::ShaderStages::VERTEX, PredatorsBuffers::predators_binding_type(true))
+            .create(device, Some("Predators (display)"));
+
+        let bind_group = binding_builder::BindGroupBuilder::new(&bind_group_layout_with_desc)
+            .resource(predators_buffers.predators_buffer.as_entire_binding())
+            .create(device, Some("Predators (display)"));
+
+        let mesh_vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Predator mesh"),
+                contents: bytemuck::cast_slice(&PREDATOR_MESH),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        );
+
+        let display_shader = create_shader_module(device, "Display Predators Shader", include_str!("../../shaders/displayPredators.wgsl"), Dimension::Two);
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Predators Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Predators Pipeline Layout"),
+                bind_group_layouts: &[simulation_parameters_uniform_buffer_layout, &bind_group_layout_with_desc.layout],
+                push_constant_ranges: &[],
+            })),
+            vertex: wgpu::VertexState {
+                module: &display_shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<nalgebra_glm::Vec2>() as _,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &display_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_configuration.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            render_pipeline,
+            bind_group,
+            mesh_vertex_buffer,
+        }
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        simulation_parameters_uniform_buffer: &'a UniformBufferWrapper<SimulationParametersUniformBufferContent>,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, self.mesh_vertex_buffer.slice(..));
+        render_pass.set_bind_group(0, simulation_parameters_uniform_buffer.bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..(PREDATOR_MESH.len() / 2) as u32, 0..simulation_parameters_uniform_buffer.content().predator_count);
+    }
+}
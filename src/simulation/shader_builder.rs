@@ -0,0 +1,49 @@
+use oxyde::{wgpu, wgpu_utils::wgsl_preprocessor::WGSLShaderBuilder};
+
+use super::{
+    parameters::{BoundaryMode, InitMode, InitParametersUniformBufferContent, NeighborhoodMode, RuleSet, SimulationParametersUniformBufferContent, SteeringModel, WeightingKernel},
+    camera::CameraUniformBufferContent,
+    obstacles, pointer_force, predators, species,
+    wgsl_struct::WgslStruct,
+    Dimension,
+};
+
+// Includes generated from rust types, they are not files of the shaders folder so they can only be included by the main shader file
+fn generated_includes(dimension: Dimension) -> [(&'static str, String); 4] {
+    [
+        ("dimension.wgsl", dimension.shader_include().to_string()),
+        ("initParameters.wgsl", InitParametersUniformBufferContent::wgsl_declaration() + &InitMode::wgsl_constants()),
+        (
+            "simulationParameters.wgsl",
+            SimulationParametersUniformBufferContent::wgsl_declaration()
+                + &BoundaryMode::wgsl_constants()
+                + &SteeringModel::wgsl_constants()
+                + &WeightingKernel::wgsl_constants()
+                + &NeighborhoodMode::wgsl_constants()
+                + &obstacles::wgsl_declarations()
+                + &species::wgsl_declarations()
+                + &predators::wgsl_declarations()
+                + &pointer_force::wgsl_declarations(),
+        ),
+        ("camera.wgsl", CameraUniformBufferContent::wgsl_declaration()),
+    ]
+}
+
+// Resolve generated includes then regular includes from the shaders folder
+// The dimension selects the variant of the simulation shaders, included as dimension.wgsl
+pub fn create_shader_module(device: &wgpu::Device, label: &str, source: &str, dimension: Dimension) -> wgpu::ShaderModule {
+    let mut source = source.to_string();
+    for (file_name, content) in generated_includes(dimension) {
+        source = source.replace(&format!("//!include {}", file_name), &content);
+    }
+
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: WGSLShaderBuilder::new(source).add_include_from_folder("shaders").build().unwrap(),
+    })
+}
+
+// Compute shaders of the boids get the rule set as rules.wgsl
+pub fn create_rules_shader_module(device: &wgpu::Device, label: &str, source: &str, dimension: Dimension, rule_set: RuleSet) -> wgpu::ShaderModule {
+    create_shader_module(device, label, &source.replace("//!include rules.wgsl", rule_set.shader_include()), dimension)
+}
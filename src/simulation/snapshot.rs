@@ -0,0 +1,231 @@
+use std::io::{Read, Seek, Write};
+
+use anyhow::{bail, Result};
+use oxyde::egui;
+
+use super::{
+    boids_state::BoidsState,
+    parameters::InitParametersUniformBufferContent,
+    types::{BoidId, BoidsRandomState, BoidsSpeciesId},
+    Dimension,
+    SimulationParametersUniformBufferContent,
+};
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"RBOIDSSN";
+// Increased whenever the layout of the file or of the parameters changes, older snapshots are refused
+const SNAPSHOT_VERSION: u32 = 4;
+// Bytes of each boid after the parameters
+const BOID_SIZE: usize = 2 * std::mem::size_of::<nalgebra_glm::Vec4>()
+    + std::mem::size_of::<BoidsSpeciesId>()
+    + std::mem::size_of::<BoidsRandomState>()
+    + std::mem::size_of::<BoidId>();
+
+// Boids state with everything needed to restore it, saved as a little endian binary file:
+// magic, version, dimension, boids count, init parameters and simulation parameters (each one prefixed by its size),
+// then positions and velocities as vec4, species ids, random states and boid ids.
+// The rule set, the species settings and the predators aren't saved, a snapshot is loaded with the current ones
+pub struct Snapshot {
+    pub dimension: Dimension,
+    pub init_parameters: InitParametersUniformBufferContent,
+    pub simulation_parameters: SimulationParametersUniformBufferContent,
+    pub state: BoidsState,
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_vec<T: bytemuck::Pod>(reader: &mut impl Read, count: usize) -> Result<Vec<T>> {
+    let mut bytes = vec![0; count * std::mem::size_of::<T>()];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes.chunks_exact(std::mem::size_of::<T>()).map(bytemuck::pod_read_unaligned).collect())
+}
+
+// A size check is the only protection against parameters of another layout with the same version
+fn read_parameters<T: bytemuck::Pod>(reader: &mut impl Read) -> Result<T> {
+    let size = read_u32(reader)? as usize;
+    if size != std::mem::size_of::<T>() {
+        bail!("parameters of {} bytes instead of {}", size, std::mem::size_of::<T>());
+    }
+    Ok(read_vec::<T>(reader, 1)?[0])
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &str) -> Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        for value in [SNAPSHOT_VERSION, self.dimension.count(), self.state.boids_count()] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        for parameters in [bytemuck::bytes_of(&self.init_parameters), bytemuck::bytes_of(&self.simulation_parameters)] {
+            writer.write_all(&(parameters.len() as u32).to_le_bytes())?;
+            writer.write_all(parameters)?;
+        }
+        writer.write_all(bytemuck::cast_slice(&self.state.positions))?;
+        writer.write_all(bytemuck::cast_slice(&self.state.velocities))?;
+        writer.write_all(bytemuck::cast_slice(&self.state.species))?;
+        writer.write_all(bytemuck::cast_slice(&self.state.random_states))?;
+        writer.write_all(bytemuck::cast_slice(&self.state.ids))?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = std::io::BufReader::new(file);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            bail!("not a boids snapshot");
+        }
+        let version = read_u32(&mut reader)?;
+        if version != SNAPSHOT_VERSION {
+            bail!("snapshot version {} instead of {}", version, SNAPSHOT_VERSION);
+        }
+        let dimension = match read_u32(&mut reader)? {
+            2 => Dimension::Two,
+            3 => Dimension::Three,
+            dimension => bail!("invalid dimension {}", dimension),
+        };
+        let boids_count = read_u32(&mut reader)? as usize;
+        let init_parameters = read_parameters(&mut reader)?;
+        let mut simulation_parameters: SimulationParametersUniformBufferContent = read_parameters(&mut reader)?;
+        simulation_parameters.boids_count = boids_count as u32;
+
+        // Checked before allocating anything for the boids, so a corrupted count can't ask for gigabytes
+        let boids_size = file_size - reader.stream_position()?;
+        if boids_size != (boids_count * BOID_SIZE) as u64 {
+            bail!("{} boids take {} bytes but {} are left in the file", boids_count, boids_count * BOID_SIZE, boids_size);
+        }
+
+        let state = BoidsState {
+            positions: read_vec(&mut reader, boids_count)?,
+            velocities: read_vec(&mut reader, boids_count)?,
+            species: read_vec(&mut reader, boids_count)?,
+            random_states: read_vec(&mut reader, boids_count)?,
+            ids: read_vec(&mut reader, boids_count)?,
+        };
+
+        Ok(Self { dimension, init_parameters, simulation_parameters, state })
+    }
+}
+
+pub enum SnapshotAction {
+    Save,
+    Load,
+}
+
+pub struct SnapshotSettings {
+    pub path: String,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self { Self { path: "snapshot.boids".to_string() } }
+}
+
+impl SnapshotSettings {
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) -> Option<SnapshotAction> {
+        let mut action = None;
+        egui::CollapsingHeader::new("Snapshots").default_open(false).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut self.path);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Save snapshot").clicked() {
+                    action = Some(SnapshotAction::Save);
+                }
+                if ui.button("Load snapshot").clicked() {
+                    action = Some(SnapshotAction::Load);
+                }
+            });
+        });
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("rusty_boids_{}_{}.snapshot", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    fn snapshot() -> Snapshot {
+        let boids_count = 3;
+        Snapshot {
+            dimension: Dimension::Three,
+            init_parameters: InitParametersUniformBufferContent { seed: 7, ..Default::default() },
+            simulation_parameters: SimulationParametersUniformBufferContent { view_radius: 0.1, boids_count, ..Default::default() },
+            state: BoidsState {
+                positions: (0..boids_count).map(|i| nalgebra_glm::vec4(0.1 * i as f32, 0.2, 0.3, 0.0)).collect(),
+                velocities: (0..boids_count).map(|i| nalgebra_glm::vec4(0.0, 0.01 * i as f32, 0.0, 0.0)).collect(),
+                species: vec![0, 1, 0],
+                random_states: vec![11, 22, 33],
+                ids: vec![2, 0, 1],
+            },
+        }
+    }
+
+    // Saves the test snapshot, changes its bytes and tries to load it back
+    fn load_modified(name: &str, modify: impl FnOnce(&mut Vec<u8>)) -> Result<Snapshot> {
+        let path = temp_path(name);
+        snapshot().save(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        modify(&mut bytes);
+        std::fs::write(&path, bytes).unwrap();
+        let result = Snapshot::load(&path);
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn save_then_load_restores_the_snapshot() {
+        let loaded = load_modified("round_trip", |_| {}).unwrap();
+        let expected = snapshot();
+
+        assert_eq!(loaded.dimension, expected.dimension);
+        assert_eq!(bytemuck::bytes_of(&loaded.init_parameters), bytemuck::bytes_of(&expected.init_parameters));
+        assert_eq!(bytemuck::bytes_of(&loaded.simulation_parameters), bytemuck::bytes_of(&expected.simulation_parameters));
+        assert_eq!(loaded.state.positions, expected.state.positions);
+        assert_eq!(loaded.state.velocities, expected.state.velocities);
+        assert_eq!(loaded.state.species, expected.state.species);
+        assert_eq!(loaded.state.random_states, expected.state.random_states);
+        assert_eq!(loaded.state.ids, expected.state.ids);
+    }
+
+    #[test]
+    fn load_refuses_a_bad_magic() {
+        let result = load_modified("magic", |bytes| bytes[0] = b'X');
+        assert!(result.is_err_and(|error| error.to_string().contains("not a boids snapshot")));
+    }
+
+    #[test]
+    fn load_refuses_another_version() {
+        let result = load_modified("version", |bytes| bytes[8..12].copy_from_slice(&(SNAPSHOT_VERSION - 1).to_le_bytes()));
+        assert!(result.is_err_and(|error| error.to_string().contains("snapshot version")));
+    }
+
+    #[test]
+    fn load_refuses_parameters_of_another_size() {
+        // The init parameters size follows the magic, the version, the dimension and the boids count
+        let result = load_modified("parameters_size", |bytes| {
+            let size = u32::from_le_bytes(bytes[20..24].try_into().unwrap());
+            bytes[20..24].copy_from_slice(&(size + 4).to_le_bytes());
+        });
+        assert!(result.is_err_and(|error| error.to_string().contains("parameters of")));
+    }
+
+    #[test]
+    fn load_refuses_a_truncated_boids_section() {
+        let result = load_modified("truncated", |bytes| {
+            bytes.pop();
+        });
+        assert!(result.is_err_and(|error| error.to_string().contains("bytes but")));
+    }
+}
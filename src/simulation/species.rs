@@ -0,0 +1,234 @@
+use oxyde::{egui, wgpu, wgpu_utils::binding_builder};
+
+use super::wgsl_struct::{wgsl_struct, WgslStruct};
+
+// Species buffers have a fixed capacity so the bind groups using them never need to be recreated
+pub const MAX_SPECIES: usize = 8;
+
+wgsl_struct! {
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, PartialEq, Debug)]
+    pub struct SpeciesBufferContent as "Species" {
+        pub color: nalgebra_glm::Vec4,
+        // Share of the boids given this species by the init shader, relative to the sum of all proportions
+        pub proportion: f32,
+        // Factors of the simulation parameters for the boids of this species
+        pub speed_factor: f32,
+        pub view_radius_factor: f32,
+        pub cohesion_factor: f32,
+        pub alignment_factor: f32,
+        pub separation_factor: f32,
+        pub _padding0: f32,
+        pub _padding1: f32,
+    }
+}
+
+wgsl_struct! {
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, PartialEq, Debug)]
+    pub struct SpeciesInteractionBufferContent as "SpeciesInteraction" {
+        // Weights of a neighbor of the other species in each rule of a boid of the current species
+        pub alignment: f32,
+        pub cohesion: f32,
+        pub separation: f32,
+    }
+}
+
+impl Default for SpeciesInteractionBufferContent {
+    fn default() -> Self {
+        Self {
+            alignment: 1.0,
+            cohesion: 1.0,
+            separation: 1.0,
+        }
+    }
+}
+
+// Default colors of the species in order
+const SPECIES_COLORS: [[f32; 3]; MAX_SPECIES] = [
+    [0.2, 0.6, 1.0],
+    [1.0, 0.45, 0.2],
+    [0.4, 0.9, 0.3],
+    [0.9, 0.3, 0.7],
+    [1.0, 0.85, 0.2],
+    [0.3, 0.9, 0.9],
+    [0.6, 0.4, 1.0],
+    [0.9, 0.9, 0.9],
+];
+
+fn default_species(index: usize) -> SpeciesBufferContent {
+    let [r, g, b] = SPECIES_COLORS[index % MAX_SPECIES];
+    SpeciesBufferContent {
+        color: nalgebra_glm::vec4(r, g, b, 1.0),
+        proportion: 1.0,
+        speed_factor: 1.0,
+        view_radius_factor: 1.0,
+        cohesion_factor: 1.0,
+        alignment_factor: 1.0,
+        separation_factor: 1.0,
+        _padding0: 0.0,
+        _padding1: 0.0,
+    }
+}
+
+// Declarations shaders get along with the simulation parameters
+pub fn wgsl_declarations() -> String { SpeciesBufferContent::wgsl_declaration() + &SpeciesInteractionBufferContent::wgsl_declaration() }
+
+// Species of the boids and how they interact, the interaction of a boid of species i with a neighbor of species j is at i * count + j
+pub struct SpeciesSettings {
+    pub species: Vec<SpeciesBufferContent>,
+    pub interactions: Vec<SpeciesInteractionBufferContent>,
+}
+
+impl Default for SpeciesSettings {
+    fn default() -> Self {
+        Self {
+            species: vec![default_species(0)],
+            interactions: vec![SpeciesInteractionBufferContent::default()],
+        }
+    }
+}
+
+impl SpeciesSettings {
+    pub fn count(&self) -> u32 { self.species.len() as u32 }
+
+    // Keep the interactions between the remaining species
+    fn resize(&mut self, count: usize) {
+        let previous_count = self.species.len();
+        let previous_interactions = std::mem::take(&mut self.interactions);
+
+        self.species.resize_with(count, || default_species(0));
+        for (index, species) in self.species.iter_mut().enumerate().skip(previous_count) {
+            *species = default_species(index);
+        }
+
+        self.interactions = (0..count * count)
+            .map(|index| {
+                let (current, other) = (index / count, index % count);
+                if current < previous_count && other < previous_count {
+                    previous_interactions[current * previous_count + other]
+                } else {
+                    SpeciesInteractionBufferContent::default()
+                }
+            })
+            .collect();
+    }
+
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Species settings").default_open(true).show(ui, |ui| {
+            let mut count = self.species.len();
+            ui.add(egui::DragValue::new(&mut count).clamp_range(1..=MAX_SPECIES).prefix("Species count: "));
+            if count != self.species.len() {
+                self.resize(count);
+            }
+
+            for (index, species) in self.species.iter_mut().enumerate() {
+                egui::CollapsingHeader::new(format!("Species {}", index)).show(ui, |ui| {
+                    let mut color = [species.color.x, species.color.y, species.color.z];
+                    ui.horizontal(|ui| {
+                        ui.color_edit_button_rgb(&mut color);
+                        ui.label("Color");
+                    });
+                    species.color = nalgebra_glm::vec4(color[0], color[1], color[2], 1.0);
+
+                    ui.add(egui::Slider::new(&mut species.proportion, 0.0..=1.0).prefix("Proportion"));
+                    ui.add(egui::Slider::new(&mut species.speed_factor, 0.1..=3.0).prefix("Speed factor"));
+                    ui.add(egui::Slider::new(&mut species.view_radius_factor, 0.1..=3.0).prefix("View radius factor"));
+                    ui.add(egui::Slider::new(&mut species.cohesion_factor, 0.0..=3.0).prefix("Cohesion factor"));
+                    ui.add(egui::Slider::new(&mut species.alignment_factor, 0.0..=3.0).prefix("Alignment factor"));
+                    ui.add(egui::Slider::new(&mut species.separation_factor, 0.0..=3.0).prefix("Separation factor"));
+                });
+            }
+            ui.label("Proportions are applied by the init");
+
+            // One matrix per rule, rows are the current species and columns the neighbor species
+            let count = self.species.len();
+            for (label, rule) in [
+                ("Alignment", (|interaction| &mut interaction.alignment) as fn(&mut SpeciesInteractionBufferContent) -> &mut f32),
+                ("Cohesion", |interaction| &mut interaction.cohesion),
+                ("Separation", |interaction| &mut interaction.separation),
+            ] {
+                egui::CollapsingHeader::new(format!("{} interactions", label)).show(ui, |ui| {
+                    egui::Grid::new(label).show(ui, |ui| {
+                        ui.label("");
+                        for other in 0..count {
+                            ui.label(format!("{}", other));
+                        }
+                        ui.end_row();
+
+                        for current in 0..count {
+                            ui.label(format!("{}", current));
+                            for other in 0..count {
+                                ui.add(egui::DragValue::new(rule(&mut self.interactions[current * count + other])).speed(0.01).clamp_range(0.0..=2.0));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+        });
+    }
+}
+
+// Fixed capacity buffers, written each frame from the species settings
+pub struct SpeciesBuffers {
+    pub species_buffer: wgpu::Buffer,
+    pub interactions_buffer: wgpu::Buffer,
+
+    // Species colors for display
+    pub display_bind_group_layout_with_desc: binding_builder::BindGroupLayoutWithDesc,
+    pub display_bind_group: wgpu::BindGroup,
+}
+
+impl SpeciesBuffers {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let create_buffer = |label: &str, size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        let species_buffer = create_buffer("Species", MAX_SPECIES * std::mem::size_of::<SpeciesBufferContent>());
+        let interactions_buffer = create_buffer(
+            "Species interactions",
+            MAX_SPECIES * MAX_SPECIES * std::mem::size_of::<SpeciesInteractionBufferContent>(),
+        );
+
+        let display_bind_group_layout_with_desc = binding_builder::BindGroupLayoutBuilder::new()
+            .add_binding(wgpu::ShaderStages::VERTEX, Self::species_binding_type())
+            .create(device, Some("Species (display)"));
+
+        let display_bind_group = binding_builder::BindGroupBuilder::new(&display_bind_group_layout_with_desc)
+            .resource(species_buffer.as_entire_binding())
+            .create(device, Some("Species (display)"));
+
+        Self {
+            species_buffer,
+            interactions_buffer,
+            display_bind_group_layout_with_desc,
+            display_bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, species_settings: &SpeciesSettings) {
+        queue.write_buffer(&self.species_buffer, 0, bytemuck::cast_slice(&species_settings.species));
+        queue.write_buffer(&self.interactions_buffer, 0, bytemuck::cast_slice(&species_settings.interactions));
+    }
+
+    pub fn species_binding_type() -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<SpeciesBufferContent>() as u64),
+        }
+    }
+
+    pub fn interactions_binding_type() -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<SpeciesInteractionBufferContent>() as u64),
+        }
+    }
+}
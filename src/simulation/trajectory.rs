@@ -0,0 +1,458 @@
+use std::{
+    collections::VecDeque,
+    io::{Seek, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{bail, Result};
+use oxyde::{egui, wgpu};
+
+use super::{boids_state::BoidsState, types::BoidId, Dimension};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrajectoryFormat {
+    // One line per boid and sample: step, boid id, position then velocity
+    Csv,
+    // float32 array of shape [samples, boids, 2 * dimension], each boid being its position then its velocity, boids in the order of their ids
+    Npy,
+}
+
+impl TrajectoryFormat {
+    pub const ALL: [TrajectoryFormat; 2] = [TrajectoryFormat::Csv, TrajectoryFormat::Npy];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TrajectoryFormat::Csv => "CSV",
+            TrajectoryFormat::Npy => "NumPy (.npy)",
+        }
+    }
+}
+
+// The npy header is written again with the samples count when recording stops, it keeps a fixed size for that
+const NPY_HEADER_SIZE: usize = 128;
+
+fn npy_header(shape: [u64; 3]) -> Vec<u8> {
+    let dictionary = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}, {}), }}", shape[0], shape[1], shape[2]);
+    // magic, version 1.0, header length then the dictionary padded with spaces and ending with a newline
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&((NPY_HEADER_SIZE - 10) as u16).to_le_bytes());
+    header.extend_from_slice(dictionary.as_bytes());
+    header.resize(NPY_HEADER_SIZE - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+enum TrajectoryWriter {
+    Csv(std::io::BufWriter<std::fs::File>),
+    Npy { file: std::io::BufWriter<std::fs::File>, samples_count: u64 },
+}
+
+impl TrajectoryWriter {
+    fn create(path: &str, format: TrajectoryFormat, dimension: Dimension, boids_count: u32) -> Result<Self> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        Ok(match format {
+            TrajectoryFormat::Csv => {
+                match dimension {
+                    Dimension::Two => writeln!(file, "step,id,x,y,vx,vy")?,
+                    Dimension::Three => writeln!(file, "step,id,x,y,z,vx,vy,vz")?,
+                }
+                TrajectoryWriter::Csv(file)
+            },
+            TrajectoryFormat::Npy => {
+                file.write_all(&npy_header([0, boids_count as u64, 2 * dimension.count() as u64]))?;
+                TrajectoryWriter::Npy { file, samples_count: 0 }
+            },
+        })
+    }
+
+    // Boids of one sample in the order of their ids, each one being 2 * dimension floats
+    fn write_sample(&mut self, step: u64, boids: &[f32], dimension: Dimension) -> Result<()> {
+        match self {
+            TrajectoryWriter::Csv(file) => {
+                for (id, boid) in boids.chunks_exact(2 * dimension.count() as usize).enumerate() {
+                    write!(file, "{},{}", step, id)?;
+                    for value in boid {
+                        write!(file, ",{}", value)?;
+                    }
+                    writeln!(file)?;
+                }
+            },
+            TrajectoryWriter::Npy { file, samples_count } => {
+                file.write_all(bytemuck::cast_slice(boids))?;
+                *samples_count += 1;
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(self, dimension: Dimension, boids_count: u32) -> Result<()> {
+        match self {
+            TrajectoryWriter::Csv(mut file) => file.flush()?,
+            TrajectoryWriter::Npy { mut file, samples_count } => {
+                file.seek(std::io::SeekFrom::Start(0))?;
+                file.write_all(&npy_header([samples_count, boids_count as u64, 2 * dimension.count() as u64]))?;
+                file.flush()?;
+            },
+        }
+        Ok(())
+    }
+}
+
+// Floats of a position or velocity in the buffers, 3D boids are padded to vec4
+fn vector_stride(dimension: Dimension) -> usize {
+    match dimension {
+        Dimension::Two => 2,
+        Dimension::Three => 4,
+    }
+}
+
+// Boids in the order of their ids from vectors of the given stride, sorting moves boids between slots
+fn scatter_by_id(dimension: Dimension, boids_count: u32, positions: &[f32], velocities: &[f32], stride: usize, ids: &[BoidId]) -> Vec<f32> {
+    let components = dimension.count() as usize;
+    let mut boids = vec![0.0; boids_count as usize * 2 * components];
+    for ((position, velocity), id) in positions.chunks_exact(stride).zip(velocities.chunks_exact(stride)).zip(ids) {
+        let boid = &mut boids[*id as usize * 2 * components..][..2 * components];
+        boid[..components].copy_from_slice(&position[..components]);
+        boid[components..].copy_from_slice(&velocity[..components]);
+    }
+    boids
+}
+
+// Staging buffer receiving the positions, the velocities then the ids of one sample
+struct StagingSlot {
+    buffer: wgpu::Buffer,
+    // Set by the map callback, along with map_failed when the mapping failed
+    mapped: Arc<AtomicBool>,
+    map_failed: Arc<AtomicBool>,
+    step: u64,
+}
+
+struct Recording {
+    writer: TrajectoryWriter,
+    dimension: Dimension,
+    boids_count: u32,
+    positions_size: u64,
+    velocities_size: u64,
+    ids_size: u64,
+    slots: Vec<StagingSlot>,
+    // Slots waiting for their mapping, in the order of the samples
+    pending_slots: VecDeque<usize>,
+    step: u64,
+    steps_since_sample: u32,
+    dropped_samples: u32,
+}
+
+impl Recording {
+    fn write_mapped_slot(&mut self, slot_index: usize) -> Result<()> {
+        let slot = &self.slots[slot_index];
+        if slot.map_failed.load(Ordering::Acquire) {
+            bail!("the staging buffer of the sample at step {} could not be mapped", slot.step);
+        }
+        {
+            let data = slot.buffer.slice(..).get_mapped_range();
+            let (positions, rest) = data.split_at(self.positions_size as usize);
+            let (velocities, ids) = rest.split_at(self.velocities_size as usize);
+            let positions: &[f32] = bytemuck::cast_slice(positions);
+            let velocities: &[f32] = bytemuck::cast_slice(velocities);
+            let ids: &[BoidId] = bytemuck::cast_slice(ids);
+
+            let boids = scatter_by_id(self.dimension, self.boids_count, positions, velocities, vector_stride(self.dimension), ids);
+            self.writer.write_sample(slot.step, &boids, self.dimension)?;
+        }
+        slot.buffer.unmap();
+        slot.mapped.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    // Write the samples whose mapping is done, in order
+    fn write_ready_samples(&mut self) -> Result<()> {
+        while let Some(&slot_index) = self.pending_slots.front() {
+            if !self.slots[slot_index].mapped.load(Ordering::Acquire) {
+                break;
+            }
+            self.pending_slots.pop_front();
+            self.write_mapped_slot(slot_index)?;
+        }
+        Ok(())
+    }
+
+    // Count the steps since the last sample, true when a sample is due
+    fn advance(&mut self, steps: u32, interval: u32) -> bool {
+        self.step += steps as u64;
+        self.steps_since_sample += steps;
+        if self.steps_since_sample < interval {
+            return false;
+        }
+        self.steps_since_sample = 0;
+        true
+    }
+
+    fn free_slot(&self) -> Option<usize> { (0..self.slots.len()).find(|slot_index| !self.pending_slots.contains(slot_index)) }
+}
+
+pub struct TrajectoryRecorder {
+    pub path: String,
+    pub format: TrajectoryFormat,
+    // Simulation steps between two samples, frames running several steps are sampled at most once
+    pub interval: u32,
+    recording: Option<Recording>,
+}
+
+impl Default for TrajectoryRecorder {
+    fn default() -> Self {
+        Self {
+            path: "trajectories.csv".to_string(),
+            format: TrajectoryFormat::Csv,
+            interval: 1,
+            recording: None,
+        }
+    }
+}
+
+// Number of staging buffers, a sample is dropped instead of stalling the frame when all of them are still mapping
+const STAGING_SLOTS_COUNT: usize = 2;
+
+impl TrajectoryRecorder {
+    pub fn is_recording(&self) -> bool { self.recording.is_some() }
+
+    // The boids count must stay the same until the recording stops, the current state is the first sample
+    pub fn start(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, buffers: &[wgpu::Buffer; 6], dimension: Dimension) -> Result<()> {
+        self.stop(device);
+
+        let positions_size = buffers[0].size();
+        let boids_count = (positions_size as usize / (vector_stride(dimension) * std::mem::size_of::<f32>())) as u32;
+        let velocities_size = buffers[1].size();
+        let ids_size = buffers[5].size();
+        let slots = (0..STAGING_SLOTS_COUNT)
+            .map(|_| StagingSlot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Trajectory staging"),
+                    size: positions_size + velocities_size + ids_size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                mapped: Arc::new(AtomicBool::new(false)),
+                map_failed: Arc::new(AtomicBool::new(false)),
+                step: 0,
+            })
+            .collect();
+
+        self.recording = Some(self.create_recording(dimension, boids_count, [positions_size, velocities_size, ids_size], slots)?);
+        self.record(device, queue, buffers, 0);
+        Ok(())
+    }
+
+    // Recording of states already on the host, for the CPU reference running without any device
+    pub fn start_host(&mut self, state: &BoidsState, dimension: Dimension) -> Result<()> {
+        self.stop_host();
+        self.recording = Some(self.create_recording(dimension, state.boids_count(), [0; 3], Vec::new())?);
+        self.record_host(state, 0);
+        Ok(())
+    }
+
+    fn create_recording(&self, dimension: Dimension, boids_count: u32, sizes: [u64; 3], slots: Vec<StagingSlot>) -> Result<Recording> {
+        let [positions_size, velocities_size, ids_size] = sizes;
+        let recording = Recording {
+            writer: TrajectoryWriter::create(&self.path, self.format, dimension, boids_count)?,
+            dimension,
+            boids_count,
+            positions_size,
+            velocities_size,
+            ids_size,
+            slots,
+            pending_slots: VecDeque::new(),
+            step: 0,
+            // Due right away for the first sample taken by start
+            steps_since_sample: self.interval,
+            dropped_samples: 0,
+        };
+        log::info!("Recording trajectories of {} boids to {}", boids_count, self.path);
+        Ok(recording)
+    }
+
+    // Wait for the samples still mapping then complete the file
+    pub fn stop(&mut self, device: &wgpu::Device) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+
+        device.poll(wgpu::Maintain::Wait);
+        self.finish(recording);
+    }
+
+    pub fn stop_host(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            self.finish(recording);
+        }
+    }
+
+    fn finish(&self, mut recording: Recording) {
+        let result = recording
+            .write_ready_samples()
+            .and_then(|_| recording.writer.finish(recording.dimension, recording.boids_count));
+        match result {
+            Ok(_) if recording.dropped_samples > 0 => {
+                log::warn!("Trajectories saved to {}, {} samples were dropped as the staging buffers were busy", self.path, recording.dropped_samples)
+            },
+            Ok(_) => log::info!("Trajectories saved to {}", self.path),
+            Err(error) => log::error!("Could not write trajectories to {}: {}", self.path, error),
+        }
+    }
+
+    // To call once the steps of the frame are submitted, the buffers being the ones written by the last step
+    pub fn record(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, buffers: &[wgpu::Buffer; 6], steps: u32) {
+        if self.recording.as_ref().is_some_and(|recording| buffers[0].size() != recording.positions_size) {
+            log::warn!("The boids count changed, trajectories recording stops");
+            self.stop(device);
+        }
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        device.poll(wgpu::Maintain::Poll);
+        if let Err(error) = recording.write_ready_samples() {
+            self.abort(error);
+            return;
+        }
+
+        if !recording.advance(steps, self.interval) {
+            return;
+        }
+
+        let Some(slot_index) = recording.free_slot() else {
+            recording.dropped_samples += 1;
+            return;
+        };
+
+        let slot = &mut recording.slots[slot_index];
+        slot.step = recording.step;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Trajectory Copy Encoder") });
+        encoder.copy_buffer_to_buffer(&buffers[0], 0, &slot.buffer, 0, recording.positions_size);
+        encoder.copy_buffer_to_buffer(&buffers[1], 0, &slot.buffer, recording.positions_size, recording.velocities_size);
+        encoder.copy_buffer_to_buffer(&buffers[5], 0, &slot.buffer, recording.positions_size + recording.velocities_size, recording.ids_size);
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = slot.mapped.clone();
+        let map_failed = slot.map_failed.clone();
+        slot.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if let Err(error) = result {
+                log::error!("Could not map a trajectory staging buffer: {}", error);
+                map_failed.store(true, Ordering::Release);
+            }
+            mapped.store(true, Ordering::Release);
+        });
+        recording.pending_slots.push_back(slot_index);
+    }
+
+    // Same as record for a state already on the host, written right away
+    pub fn record_host(&mut self, state: &BoidsState, steps: u32) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if !recording.advance(steps, self.interval) {
+            return;
+        }
+
+        let positions: &[f32] = bytemuck::cast_slice(&state.positions);
+        let velocities: &[f32] = bytemuck::cast_slice(&state.velocities);
+        let boids = scatter_by_id(recording.dimension, recording.boids_count, positions, velocities, 4, &state.ids);
+        if let Err(error) = recording.writer.write_sample(recording.step, &boids, recording.dimension) {
+            self.abort(error);
+        }
+    }
+
+    // Stops after a write error, the file is still completed so the samples written before it stay readable
+    fn abort(&mut self, error: anyhow::Error) {
+        log::error!("Could not write trajectories to {}, recording stops: {}", self.path, error);
+        if let Some(recording) = self.recording.take() {
+            if let Err(error) = recording.writer.finish(recording.dimension, recording.boids_count) {
+                log::error!("Could not complete the trajectories file {}: {}", self.path, error);
+            }
+        }
+    }
+
+    // True when recording should start or stop
+    pub fn display_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut toggled = false;
+        egui::CollapsingHeader::new("Trajectories").default_open(false).show(ui, |ui| {
+            ui.add_enabled_ui(!self.is_recording(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("File");
+                    ui.text_edit_singleline(&mut self.path);
+                });
+                egui::ComboBox::from_label("Format").selected_text(self.format.label()).show_ui(ui, |ui| {
+                    for format in TrajectoryFormat::ALL {
+                        ui.selectable_value(&mut self.format, format, format.label());
+                    }
+                });
+                ui.add(egui::DragValue::new(&mut self.interval).clamp_range(1..=10_000).prefix("Steps between samples: "));
+            });
+
+            if let Some(recording) = &self.recording {
+                ui.label(format!("Step {}, {} samples dropped", recording.step, recording.dropped_samples));
+            }
+            toggled = ui.button(if self.is_recording() { "Stop recording" } else { "Start recording" }).clicked();
+        });
+        toggled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape_of(header: &[u8]) -> String {
+        let header = String::from_utf8_lossy(header);
+        let start = header.find("'shape': ").unwrap() + "'shape': ".len();
+        header[start..start + header[start..].find(')').unwrap() + 1].to_string()
+    }
+
+    #[test]
+    fn npy_header_has_a_fixed_size() {
+        let header = npy_header([0, 100, 4]);
+
+        assert_eq!(header.len(), NPY_HEADER_SIZE);
+        assert_eq!(&header[..8], b"\x93NUMPY\x01\x00");
+        assert_eq!(u16::from_le_bytes([header[8], header[9]]) as usize, NPY_HEADER_SIZE - 10);
+        assert_eq!(header.last(), Some(&b'\n'));
+        assert_eq!(shape_of(&header), "(0, 100, 4)");
+    }
+
+    #[test]
+    fn finish_writes_the_samples_count_in_the_npy_header() {
+        let path = std::env::temp_dir().join(format!("rusty_boids_{}_trajectory.npy", std::process::id())).to_string_lossy().into_owned();
+        let boids_count = 3;
+        let mut writer = TrajectoryWriter::create(&path, TrajectoryFormat::Npy, Dimension::Two, boids_count).unwrap();
+        let boids = vec![0.5; boids_count as usize * 4];
+        writer.write_sample(0, &boids, Dimension::Two).unwrap();
+        writer.write_sample(1, &boids, Dimension::Two).unwrap();
+        writer.finish(Dimension::Two, boids_count).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(bytes.len(), NPY_HEADER_SIZE + 2 * boids.len() * std::mem::size_of::<f32>());
+        assert_eq!(shape_of(&bytes[..NPY_HEADER_SIZE]), "(2, 3, 4)");
+    }
+
+    #[test]
+    fn scatter_by_id_restores_the_order_of_the_ids() {
+        // Slots hold the boids 2, 0 then 1, the padding of the 3D vectors is skipped
+        let ids = [2, 0, 1];
+        let positions_2d = [2.0, 2.1, 0.0, 0.1, 1.0, 1.1];
+        let velocities_2d = [-2.0, -2.1, -0.0, -0.1, -1.0, -1.1];
+        assert_eq!(
+            scatter_by_id(Dimension::Two, 3, &positions_2d, &velocities_2d, 2, &ids),
+            vec![0.0, 0.1, -0.0, -0.1, 1.0, 1.1, -1.0, -1.1, 2.0, 2.1, -2.0, -2.1],
+        );
+
+        let positions_3d = [2.0, 2.1, 2.2, 9.0, 0.0, 0.1, 0.2, 9.0, 1.0, 1.1, 1.2, 9.0];
+        let velocities_3d = [-2.0, -2.1, -2.2, 9.0, -0.0, -0.1, -0.2, 9.0, -1.0, -1.1, -1.2, 9.0];
+        assert_eq!(
+            scatter_by_id(Dimension::Three, 3, &positions_3d, &velocities_3d, 4, &ids),
+            vec![0.0, 0.1, 0.2, -0.0, -0.1, -0.2, 1.0, 1.1, 1.2, -1.0, -1.1, -1.2, 2.0, 2.1, 2.2, -2.0, -2.1, -2.2],
+        );
+    }
+}
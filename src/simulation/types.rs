@@ -1,4 +1,13 @@
 pub type BoidSortingId = u32;
 pub type BoidsPosition = nalgebra_glm::Vec2;
 pub type BoidsVelocity = nalgebra_glm::Vec2;
-pub type BoidsCellId = u32;
\ No newline at end of file
+pub type BoidsCellId = u32;
+pub type BoidsSpeciesId = u32;
+// State of the random stream of each boid
+pub type BoidsRandomState = u32;
+// Given by the init shader, it travels with the boid when the grid strategy sorts them
+pub type BoidId = u32;
+
+// array<vec3<f32>> has a stride of 16 bytes in shaders, so 3D boids data is stored padded to a vec4
+pub type Boids3dPosition = nalgebra_glm::Vec4;
+pub type Boids3dVelocity = nalgebra_glm::Vec4;
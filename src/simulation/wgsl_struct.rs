@@ -0,0 +1,116 @@
+// Rust types usable as fields of a struct shared with WGSL, with their WGSL name and host-shareable size and alignment
+pub trait WgslType: Sized {
+    const WGSL_NAME: &'static str;
+    const SIZE: usize;
+    const ALIGN: usize;
+
+    // Value written in a parameter file, vectors and matrices as comma separated components (column major)
+    fn parse(value: &str) -> Option<Self>;
+}
+
+fn parse_components(value: &str, count: usize) -> Option<Vec<f32>> {
+    let components = value.split(',').map(|component| component.trim().parse().ok()).collect::<Option<Vec<f32>>>()?;
+    (components.len() == count).then_some(components)
+}
+
+macro_rules! impl_wgsl_type {
+    ($ty:ty, $wgsl_name:literal, $size:literal, $align:literal, $parse:expr) => {
+        impl WgslType for $ty {
+            const WGSL_NAME: &'static str = $wgsl_name;
+            const SIZE: usize = $size;
+            const ALIGN: usize = $align;
+
+            fn parse(value: &str) -> Option<Self> { $parse(value) }
+        }
+    };
+}
+
+impl_wgsl_type!(f32, "f32", 4, 4, |value: &str| value.parse().ok());
+impl_wgsl_type!(u32, "u32", 4, 4, |value: &str| value.parse().ok());
+impl_wgsl_type!(i32, "i32", 4, 4, |value: &str| value.parse().ok());
+impl_wgsl_type!(nalgebra_glm::Vec2, "vec2<f32>", 8, 8, |value| parse_components(value, 2).map(|components| nalgebra_glm::Vec2::from_column_slice(&components)));
+impl_wgsl_type!(nalgebra_glm::Vec4, "vec4<f32>", 16, 16, |value| parse_components(value, 4).map(|components| nalgebra_glm::Vec4::from_column_slice(&components)));
+impl_wgsl_type!(nalgebra_glm::Mat4, "mat4x4<f32>", 64, 16, |value| parse_components(value, 16).map(|components| nalgebra_glm::Mat4::from_column_slice(&components)));
+
+pub trait WgslStruct {
+    // Declaration of the struct to include in shaders
+    fn wgsl_declaration() -> String;
+
+    // Set a field from its name and its text value, padding fields starting with an underscore are not settable
+    fn set_field(&mut self, field: &str, value: &str) -> anyhow::Result<()>;
+}
+
+// Declare a repr(C) struct along with its WGSL declaration
+// Offsets, size and alignment are checked at compile time against the WGSL layout rules so both sides can't drift apart
+macro_rules! wgsl_struct {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident as $wgsl_name:literal {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident: $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        #[repr(C)]
+        $(#[$attr])*
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        const _: () = {
+            use $crate::simulation::wgsl_struct::WgslType;
+
+            let mut offset = 0usize;
+            let mut align = 1usize;
+            $(
+                offset = offset.next_multiple_of(<$ty as WgslType>::ALIGN);
+                assert!(
+                    offset == std::mem::offset_of!($name, $field),
+                    concat!("Field ", stringify!($name), "::", stringify!($field), " is not at its WGSL offset")
+                );
+                offset += <$ty as WgslType>::SIZE;
+                if <$ty as WgslType>::ALIGN > align {
+                    align = <$ty as WgslType>::ALIGN;
+                }
+            )*
+            assert!(
+                offset.next_multiple_of(align) == std::mem::size_of::<$name>(),
+                concat!("Size of ", stringify!($name), " differs from its WGSL size, add explicit padding fields")
+            );
+        };
+
+        impl $crate::simulation::wgsl_struct::WgslStruct for $name {
+            fn wgsl_declaration() -> String {
+                use $crate::simulation::wgsl_struct::WgslType;
+
+                let mut declaration = format!("struct {} {{\n", $wgsl_name);
+                $(
+                    declaration += &format!("  {}: {},\n", stringify!($field), <$ty as WgslType>::WGSL_NAME);
+                )*
+                declaration += "}\n";
+                declaration
+            }
+
+            fn set_field(&mut self, field: &str, value: &str) -> anyhow::Result<()> {
+                use $crate::simulation::wgsl_struct::WgslType;
+
+                match field {
+                    $(
+                        stringify!($field) if !field.starts_with('_') => {
+                            self.$field = <$ty as WgslType>::parse(value)
+                                .ok_or_else(|| anyhow::anyhow!("invalid {} value {} for {}", <$ty as WgslType>::WGSL_NAME, value, field))?;
+                        },
+                    )*
+                    _ => anyhow::bail!("{} has no field {}", $wgsl_name, field),
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+pub(crate) use wgsl_struct;